@@ -10,15 +10,23 @@
 # and then create symlinks for all the tools you are interested in:
 #
 # cd /usr/share/bash-completion/completions/
+# ln -s tbtools-completion.bash tbacl
 # ln -s tbtools-completion.bash tbadapters
 # ln -s tbtools-completion.bash tbauth
+# ln -s tbtools-completion.bash tbauthd
+# ln -s tbtools-completion.bash tbdiff
+# ln -s tbtools-completion.bash tbdpcd
 # ln -s tbtools-completion.bash tbdump
 # ln -s tbtools-completion.bash tbget
 # ln -s tbtools-completion.bash tblist
 # ln -s tbtools-completion.bash tbmargin
 # ln -s tbtools-completion.bash tbmonitor
+# ln -s tbtools-completion.bash tbnvm
 # ln -s tbtools-completion.bash tbpd
+# ln -s tbtools-completion.bash tbreset
+# ln -s tbtools-completion.bash tbrouterop
 # ln -s tbtools-completion.bash tbset
+# ln -s tbtools-completion.bash tbsnapshot
 # ln -s tbtools-completion.bash tbtrace
 # ln -s tbtools-completion.bash tbtunnels
 #
@@ -136,16 +144,72 @@ _tbtools_complete_registers()
     COMPREPLY+=($(compgen -W "$registers" -- "$reg"))
 }
 
+_tbacl()
+{
+    local cur prev words cword domain route
+    _init_completion || return
+
+    local commands='list add remove clear help'
+    local command word
+
+    for word in "${words[@]:1:cword-1}"; do
+        if [[ " $commands " == *" $word "* ]]; then
+            command=$word
+            break
+        fi
+    done
+
+    if [[ -z $command ]]; then
+        if [[ $cur == -* ]]; then
+            COMPREPLY+=($(compgen -W '--domain --help --version' -- "$cur"))
+            return
+        fi
+        case $prev in
+            --domain | -d)
+                _tbtools_complete_domains
+                return
+                ;;
+        esac
+        COMPREPLY+=($(compgen -W "$commands" -- "$cur"))
+    else
+        case $command in
+            add)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--route --help' -- "$cur"))
+                elif [[ $prev == --route || $prev == -r ]]; then
+                    _tbtools_domain_route_adapter
+                    _tbtools_complete_routers $domain
+                fi
+                ;;
+            remove)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--index --help' -- "$cur"))
+                fi
+                ;;
+            *)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--help' -- "$cur"))
+                fi
+                ;;
+        esac
+    fi
+} &&
+    complete -F _tbacl tbacl
+
 _tbadapters()
 {
     local cur prev words cword domain route
     _init_completion || return
 
     if [[ $cur == -* ]]; then
-        COMPREPLY+=($(compgen -W '--domain --route --adapter --script --help
-            --version' -- "$cur"))
+        COMPREPLY+=($(compgen -W '--domain --route --adapter --script --json --snapshot
+            --router-ops --help --version' -- "$cur"))
     else
         case $prev in
+            --snapshot)
+                _filedir
+                return
+                ;;
             --domain | -d)
                 _tbtools_complete_domains
                 return
@@ -172,13 +236,18 @@ _tbauth()
 
     if [[ $cur == -* ]]; then
         COMPREPLY+=($(compgen -W '--add-key-path --challenge-key-path --help
-            --deauthorize --domain --route --version' -- "$cur"))
+            --deauthorize --domain --forget --key-dir --route --store
+            --version' -- "$cur"))
     else
         case $prev in
             --add-key-path | --challenge-key-path | -[AC])
                 _filedir
                 return
                 ;;
+            --key-dir)
+                _filedir -d
+                return
+                ;;
             --domain | -d)
                 _tbtools_complete_domains
                 return
@@ -193,6 +262,76 @@ _tbauth()
 } &&
     complete -F _tbauth tbauth
 
+_tbauthd()
+{
+    local cur prev words cword
+    _init_completion || return
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY+=($(compgen -W '--domain --dry-run --help --key-dir --once
+            --policy --version' -- "$cur"))
+    else
+        case $prev in
+            --domain | -d)
+                _tbtools_complete_domains
+                return
+                ;;
+            --policy | -p)
+                _filedir
+                return
+                ;;
+            --key-dir)
+                _filedir -d
+                return
+                ;;
+        esac
+    fi
+} &&
+    complete -F _tbauthd tbauthd
+
+_tbdiff()
+{
+    local cur prev words cword
+    _init_completion || return
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY+=($(compgen -W '--domain --no-counters --help --version' -- "$cur"))
+    else
+        case $prev in
+            --domain | -d)
+                _tbtools_complete_domains
+                return
+                ;;
+        esac
+        _filedir
+    fi
+} &&
+    complete -F _tbdiff tbdiff
+
+_tbdpcd()
+{
+    local cur prev words cword
+    _init_completion || return
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY+=($(compgen -W '--aux --file --verbose --bandwidth --enable-bw
+            --disable-bw --request-bw --help --version' -- "$cur"))
+    else
+        case $prev in
+            --aux | -a)
+                COMPREPLY+=($(compgen -W "$(ls /dev/drm_dp_aux* 2> /dev/null |
+                    sed 's,/dev/drm_dp_aux,,' | xargs)" -- "$cur"))
+                return
+                ;;
+            --file | -f)
+                _filedir
+                return
+                ;;
+        esac
+    fi
+} &&
+    complete -F _tbdpcd tbdpcd
+
 _tbdump()
 {
     local cur prev words cword domain route path counters
@@ -200,9 +339,14 @@ _tbdump()
 
     if [[ $cur == -* ]]; then
         COMPREPLY+=($(compgen -W '--domain --route --adapter --path --counters
-            --verbose --cap-id --vs-cap-id --nregs --help --drom' -- "$cur"))
+            --sideband --index --verbose --cap-id --vs-cap-id --nregs --help --drom
+            --snapshot --json' -- "$cur"))
     else
         case $prev in
+            --snapshot)
+                _filedir
+                return
+                ;;
             --domain | -d)
                 _tbtools_complete_domains
                 return
@@ -235,9 +379,14 @@ _tbget()
 
     if [[ $cur == -* ]]; then
         COMPREPLY+=($(compgen -W '--domain --route --adapter --path --counters
-            --binary --decimal --query --help --verbose --version' -- "$cur"))
+            --sideband --index --binary --decimal --query --json --help --verbose --snapshot
+            --version' -- "$cur"))
     else
         case $prev in
+            --snapshot)
+                _filedir
+                return
+                ;;
             --domain | -d)
                 _tbtools_complete_domains
                 return
@@ -266,8 +415,15 @@ _tblist()
     _init_completion || return
 
     if [[ $cur == -* ]]; then
-        COMPREPLY+=($(compgen -W '--all --script --tree --verbose --help
-            --version' -- "$cur"))
+        COMPREPLY+=($(compgen -W '--all --script --tree --dot --mermaid --json --verbose
+            --snapshot --help --version' -- "$cur"))
+    else
+        case $prev in
+            --snapshot)
+                _filedir
+                return
+                ;;
+        esac
     fi
 } &&
     complete -F _tblist tblist
@@ -278,7 +434,7 @@ _tbmargin()
     _init_completion || return
 
     if [[ $cur == -* ]]; then
-        COMPREPLY+=($(compgen -W '--domain --route --adapter --index --caps
+        COMPREPLY+=($(compgen -W '--domain --route --adapter --index --caps --json
             --help --version' -- "$cur"))
     else
         case $prev in
@@ -319,6 +475,39 @@ _tbmonitor()
 } &&
     complete -F _tbmonitor tbmonitor
 
+_tbnvm()
+{
+    local cur prev words cword domain route adapter
+    _init_completion || return
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY+=($(compgen -W '--domain --route --adapter --index --on-disconnect
+            --flush-only --timeout --force --dry-run --clear --help --version' -- "$cur"))
+    else
+        case $prev in
+            --domain | -d)
+                _tbtools_complete_domains
+                return
+                ;;
+            --route | -r)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_routers $domain
+                return
+                ;;
+            --adapter | -a)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_lane_adapters $route $domain
+                return
+                ;;
+            --index | -i | --timeout | -t)
+                return
+                ;;
+        esac
+        _filedir
+    fi
+} &&
+    complete -F _tbnvm tbnvm
+
 _tbpd()
 {
     local cur prev words cword domain route
@@ -352,6 +541,113 @@ _tbpd()
 } &&
     complete -F _tbpd tbpd
 
+_tbreset()
+{
+    local cur prev words cword domain route adapter
+    _init_completion || return
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY+=($(compgen -W '--domain --route --adapter --timeout --help
+            --version' -- "$cur"))
+    else
+        case $prev in
+            --domain | -d)
+                _tbtools_complete_domains
+                return
+                ;;
+            --route | -r)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_routers $domain
+                return
+                ;;
+            --adapter | -a)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_lane_adapters $route $domain
+                return
+                ;;
+        esac
+    fi
+} &&
+    complete -F _tbreset tbreset
+
+_tbrouterop()
+{
+    local cur prev words cword domain route
+    _init_completion || return
+
+    local commands='nvm-read nvm-dump nvm-version nvm-sector-size drom-read
+        pcie-downstream-mapping query-dp-resource capabilities run help'
+    local command word
+
+    for word in "${words[@]:1:cword-1}"; do
+        if [[ " ${commands//$'\n'/ } " == *" $word "* ]]; then
+            command=$word
+            break
+        fi
+    done
+
+    if [[ -z $command ]]; then
+        if [[ $cur == -* ]]; then
+            COMPREPLY+=($(compgen -W '--domain --route --timeout --help
+                --version' -- "$cur"))
+            return
+        fi
+        case $prev in
+            --domain | -d)
+                _tbtools_complete_domains
+                return
+                ;;
+            --route | -r)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_routers $domain
+                return
+                ;;
+            --timeout | -t)
+                return
+                ;;
+        esac
+        COMPREPLY+=($(compgen -W "$commands" -- "$cur"))
+    else
+        case $command in
+            nvm-read | drom-read)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--address --len --help' -- "$cur"))
+                fi
+                ;;
+            nvm-dump)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--len --help' -- "$cur"))
+                elif [[ $prev != --len && $prev != -N ]]; then
+                    _filedir
+                fi
+                ;;
+            query-dp-resource)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--adapter --help' -- "$cur"))
+                else
+                    case $prev in
+                        --adapter | -a)
+                            _tbtools_domain_route_adapter
+                            _tbtools_complete_all_adapters $route $domain
+                            ;;
+                    esac
+                fi
+                ;;
+            run)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--metadata --help' -- "$cur"))
+                fi
+                ;;
+            *)
+                if [[ $cur == -* ]]; then
+                    COMPREPLY=($(compgen -W '--help' -- "$cur"))
+                fi
+                ;;
+        esac
+    fi
+} &&
+    complete -F _tbrouterop tbrouterop
+
 _tbset()
 {
     local cur prev words cword domain route adapter
@@ -359,7 +655,7 @@ _tbset()
 
     if [[ $cur == -* ]]; then
         COMPREPLY+=($(compgen -W '--domain --route --adapter --path --counters
-            --help --version' -- "$cur"))
+            --sideband --index --help --version' -- "$cur"))
     else
         case $prev in
             --domain | -d)
@@ -384,19 +680,47 @@ _tbset()
 } &&
     complete -F _tbset tbset
 
+_tbsnapshot()
+{
+    local cur prev words cword
+    _init_completion || return
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY+=($(compgen -W '--verbose --help --version' -- "$cur"))
+    else
+        _filedir
+    fi
+} &&
+    complete -F _tbsnapshot tbsnapshot
+
 _tbtunnels()
 {
-    local cur prev words cword domain
+    local cur prev words cword domain route
     _init_completion || return
 
     if [[ $cur == -* ]]; then
-        COMPREPLY+=($(compgen -W '--domain --verbose --help --version' -- "$cur"))
+        COMPREPLY+=($(compgen -W '--domain --route --adapter --verbose --snapshot
+            --router-ops --dot --mermaid --bandwidth --check --json --help --version' -- "$cur"))
     else
         case $prev in
+            --snapshot)
+                _filedir
+                return
+                ;;
             --domain | -d)
                 _tbtools_complete_domains
                 return
                 ;;
+            --route | -r)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_routers $domain
+                return
+                ;;
+            --adapter | -a)
+                _tbtools_domain_route_adapter
+                _tbtools_complete_all_adapters $route $domain
+                return
+                ;;
         esac
     fi
 } &&
@@ -0,0 +1,136 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Register access backends.
+//!
+//! All register accesses of [`Device`](crate::Device) and [`Adapter`](crate::debugfs::Adapter)
+//! go through a [`Backend`]. By default this is [`Debugfs`] that uses the kernel `debugfs`
+//! interface but it can be replaced with [`set()`], for example to run the code against mock
+//! hardware.
+//!
+//! Routers are identified by their kernel name (for example `0-1`) and adapters by their number
+//! within that router.
+//!
+//! # Examples
+//! Use a backend that does not have any registers.
+//! ```
+//! # use std::io::{self, Error, ErrorKind};
+//! use tbtools::{
+//!     ConfigSpace,
+//!     backend::{self, Backend},
+//!     debugfs::Register,
+//! };
+//!
+//! struct Empty;
+//!
+//! impl Backend for Empty {
+//!     fn read_registers(&self, _: &str, _: Option<u8>) -> io::Result<Vec<Register>> {
+//!         Err(Error::from(ErrorKind::NotFound))
+//!     }
+//!
+//!     fn read_paths(&self, _: &str, _: u8) -> io::Result<Vec<Register>> {
+//!         Err(Error::from(ErrorKind::NotFound))
+//!     }
+//!
+//!     fn read_counters(&self, _: &str, _: u8) -> io::Result<Vec<Register>> {
+//!         Err(Error::from(ErrorKind::NotFound))
+//!     }
+//!
+//!     fn write_registers(
+//!         &self,
+//!         _: &str,
+//!         _: Option<u8>,
+//!         _: ConfigSpace,
+//!         _: &[Register],
+//!     ) -> io::Result<()> {
+//!         Err(Error::from(ErrorKind::PermissionDenied))
+//!     }
+//!
+//!     fn read_drom(&self, _: &str) -> io::Result<Vec<u8>> {
+//!         Err(Error::from(ErrorKind::NotFound))
+//!     }
+//! }
+//!
+//! backend::set(Empty);
+//! ```
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    sync::{Arc, RwLock},
+};
+
+use crate::{ConfigSpace, debugfs::Register};
+
+pub use crate::debugfs::Debugfs;
+
+static BACKEND: RwLock<Option<Arc<dyn Backend>>> = RwLock::new(None);
+
+/// Provides access to the router and adapter configuration spaces.
+pub trait Backend: Send + Sync {
+    /// Reads all router config space registers of `router` or if `adapter` is given, all adapter
+    /// config space registers of that adapter.
+    ///
+    /// Registers are returned sorted by their absolute offset. If the adapter does not exist
+    /// returns [`ErrorKind::NotFound`].
+    fn read_registers(&self, router: &str, adapter: Option<u8>) -> Result<Vec<Register>>;
+
+    /// Reads all path config space registers of `adapter`.
+    fn read_paths(&self, router: &str, adapter: u8) -> Result<Vec<Register>>;
+
+    /// Reads all counters config space registers of `adapter`.
+    fn read_counters(&self, router: &str, adapter: u8) -> Result<Vec<Register>>;
+
+    /// Reads sideband registers of USB4 port `adapter` of `device`, or if `adapter` is `None`
+    /// sideband registers of retimer `device`.
+    fn read_sideband(&self, _device: &str, _adapter: Option<u8>) -> Result<Vec<Register>> {
+        Err(Error::from(ErrorKind::Unsupported))
+    }
+
+    /// Writes `regs` to config space `space` of `router` or `adapter`.
+    ///
+    /// For [`ConfigSpace::Sideband`] of a retimer `router` is the kernel name of the retimer.
+    fn write_registers(
+        &self,
+        router: &str,
+        adapter: Option<u8>,
+        space: ConfigSpace,
+        regs: &[Register],
+    ) -> Result<()>;
+
+    /// Clears all counters of `adapter`.
+    fn clear_counters(&self, _router: &str, _adapter: u8) -> Result<()> {
+        Err(Error::from(ErrorKind::Unsupported))
+    }
+
+    /// Reads raw DROM contents of `router`.
+    fn read_drom(&self, router: &str) -> Result<Vec<u8>>;
+
+    /// Returns `true` if registers of `router` can be written.
+    fn registers_writable(&self, _router: &str) -> bool {
+        false
+    }
+}
+
+/// Replaces the current backend with `backend`.
+pub fn set(backend: impl Backend + 'static) {
+    set_shared(Arc::new(backend));
+}
+
+pub(crate) fn set_shared(backend: Arc<dyn Backend>) {
+    *BACKEND.write().unwrap() = Some(backend);
+}
+
+/// Restores the default [`Debugfs`] backend.
+pub fn reset() {
+    *BACKEND.write().unwrap() = None;
+}
+
+/// Returns the current backend.
+pub fn get() -> Arc<dyn Backend> {
+    BACKEND
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Arc::new(Debugfs))
+}
@@ -0,0 +1,502 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Bandwidth accounting of the USB4 links.
+//!
+//! Sums up the bandwidth reserved by all the tunnels going over each link of the domain and
+//! compares it against the capacity of the link. This follows what the Linux connection manager
+//! does:
+//!
+//! - USB 3 tunnels use the bandwidth allocated in the USB 3 Down adapter of the host router.
+//!   Tunnels to deeper routers share the allocation of the tunnel from the host router.
+//! - DisplayPort tunnels use the allocated bandwidth if bandwidth allocation mode is enabled,
+//!   otherwise the full bandwidth of the negotiated DisplayPort link.
+//! - PCIe tunnels reserve 1500 Mb/s per direction on USB4 v2 (Gen 4) links and nothing on other
+//!   links.
+//! - DMA tunnels do not reserve bandwidth.
+//! - The capacity of a link is its bandwidth minus the 10% guard band the connection manager
+//!   never hands out to tunnels.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::{bandwidth, topology::Topology, tunnel::Tunnel};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut topology = Topology::read()?;
+//!
+//! for device in topology.devices_mut() {
+//!     if device.is_router() {
+//!         device.read_adapters()?;
+//!         for adapter in device.adapters_mut().unwrap() {
+//!             adapter.read_paths().ok();
+//!         }
+//!     }
+//! }
+//!
+//! let devices = topology.devices();
+//! let mut tunnels = Vec::new();
+//! for device in devices.iter().filter(|d| d.is_router()) {
+//!     for adapter in device.adapters().unwrap() {
+//!         tunnels.extend(Tunnel::discover(device, adapter, devices).into_iter().flatten());
+//!     }
+//! }
+//!
+//! for link in bandwidth::links(&topology, &tunnels) {
+//!     if link.is_oversubscribed() {
+//!         println!("Route {:x} Adapter {} is oversubscribed", link.route(), link.adapter());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    Device,
+    topology::{Link, Topology},
+    tunnel::{Direction, Tunnel, Type},
+};
+
+/// Bandwidth reserved for PCIe per direction on USB4 v2 links in Mb/s.
+const USB4_V2_PCIE_MIN_BW: u32 = 1500;
+/// Lane speed of USB4 v2 (Gen 4) links in Gb/s.
+const USB4_V2_LANE_SPEED: u32 = 40;
+
+/// Bandwidth reserved by a single tunnel on a link.
+#[derive(Clone, Debug)]
+pub struct Consumer {
+    kind: Type,
+    route: u64,
+    adapter: u8,
+    upstream: u32,
+    downstream: u32,
+    estimated: Option<u32>,
+}
+
+impl Consumer {
+    /// Returns type of the tunnel.
+    pub fn kind(&self) -> Type {
+        self.kind
+    }
+
+    /// Returns route of the router where the tunnel starts.
+    pub fn route(&self) -> u64 {
+        self.route
+    }
+
+    /// Returns adapter where the tunnel starts.
+    pub fn adapter(&self) -> u8 {
+        self.adapter
+    }
+
+    /// Returns upstream bandwidth in Mb/s reserved by the tunnel.
+    pub fn upstream(&self) -> u32 {
+        self.upstream
+    }
+
+    /// Returns downstream bandwidth in Mb/s reserved by the tunnel.
+    pub fn downstream(&self) -> u32 {
+        self.downstream
+    }
+
+    /// Returns estimated bandwidth in Mb/s of a DisplayPort tunnel in bandwidth allocation mode.
+    pub fn estimated(&self) -> Option<u32> {
+        self.estimated
+    }
+}
+
+/// Bandwidth usage of a single link.
+#[derive(Clone, Debug)]
+pub struct LinkBandwidth {
+    domain: u32,
+    route: u64,
+    adapter: u8,
+    child_route: u64,
+    up_capacity: Option<u32>,
+    down_capacity: Option<u32>,
+    consumers: Vec<Consumer>,
+}
+
+impl LinkBandwidth {
+    /// Returns domain of the link.
+    pub fn domain(&self) -> u32 {
+        self.domain
+    }
+
+    /// Returns route of the router at the upstream end of the link.
+    pub fn route(&self) -> u64 {
+        self.route
+    }
+
+    /// Returns downstream lane adapter of the router at the upstream end of the link.
+    pub fn adapter(&self) -> u8 {
+        self.adapter
+    }
+
+    /// Returns route of the device at the downstream end of the link.
+    pub fn child_route(&self) -> u64 {
+        self.child_route
+    }
+
+    /// Returns upstream capacity of the link in Mb/s, not including the guard band.
+    pub fn up_capacity(&self) -> Option<u32> {
+        self.up_capacity
+    }
+
+    /// Returns downstream capacity of the link in Mb/s, not including the guard band.
+    pub fn down_capacity(&self) -> Option<u32> {
+        self.down_capacity
+    }
+
+    /// Returns the tunnels reserving bandwidth on this link.
+    pub fn consumers(&self) -> &[Consumer] {
+        &self.consumers
+    }
+
+    /// Returns total upstream bandwidth in Mb/s reserved on this link.
+    pub fn upstream(&self) -> u32 {
+        self.consumers.iter().map(|c| c.upstream).sum()
+    }
+
+    /// Returns total downstream bandwidth in Mb/s reserved on this link.
+    pub fn downstream(&self) -> u32 {
+        self.consumers.iter().map(|c| c.downstream).sum()
+    }
+
+    /// Returns `true` if more bandwidth is reserved than the link can carry in either direction.
+    pub fn is_oversubscribed(&self) -> bool {
+        self.up_capacity.is_some_and(|c| self.upstream() > c)
+            || self.down_capacity.is_some_and(|c| self.downstream() > c)
+    }
+}
+
+fn same_device(a: &Device, b: &Device) -> bool {
+    a.domain_index() == b.domain_index() && a.route() == b.route()
+}
+
+/// Returns allocated USB 3 bandwidth as `(upstream, downstream)`.
+fn usb3_bandwidth(tunnel: &Tunnel, tunnels: &[Tunnel]) -> Option<(u32, u32)> {
+    let mut current = tunnel;
+
+    // Walk up to the tunnel starting from the host router.
+    while !current.src_device().is_host_router() {
+        current = tunnels.iter().find(|t| {
+            t.kind() == tunnel.kind() && same_device(t.dst_device(), current.src_device())
+        })?;
+    }
+
+    let adapter = current.src_adapter();
+    Some((
+        adapter.usb3_allocated_up_bw()?,
+        adapter.usb3_allocated_down_bw()?,
+    ))
+}
+
+/// Returns bandwidth of a DisplayPort tunnel as `(bandwidth, estimated)`.
+fn dp_bandwidth(tunnel: &Tunnel) -> Option<(u32, Option<u32>)> {
+    let adapter = tunnel.src_adapter();
+
+    if adapter.dp_bw_mode_enabled()? {
+        return Some((adapter.dp_allocated_bw()?, adapter.dp_estimated_bw()));
+    }
+
+    let rate = adapter.dp_link_rate()?;
+    let lanes = adapter.dp_lane_count()?;
+    // UHBR rates use 128b/132b encoding, the rest 8b/10b.
+    let bw = if rate >= 10000 {
+        rate * lanes * 128 / 132
+    } else {
+        rate * lanes * 8 / 10
+    };
+
+    Some((bw, None))
+}
+
+fn consumer(tunnel: &Tunnel, tunnels: &[Tunnel], link: &Link) -> Option<Consumer> {
+    let (upstream, downstream, estimated) = match tunnel.kind() {
+        Type::Usb3 | Type::Usb3GenT => {
+            let (up, down) = usb3_bandwidth(tunnel, tunnels)?;
+            (up, down, None)
+        }
+        Type::DisplayPort => {
+            let (bw, estimated) = dp_bandwidth(tunnel)?;
+            match tunnel.direction() {
+                Direction::Downstream => (0, bw, estimated),
+                Direction::Upstream => (bw, 0, estimated),
+            }
+        }
+        Type::Pcie => {
+            if link.rx_speed() >= Some(USB4_V2_LANE_SPEED) {
+                (USB4_V2_PCIE_MIN_BW, USB4_V2_PCIE_MIN_BW, None)
+            } else {
+                (0, 0, None)
+            }
+        }
+        Type::Dma => return None,
+    };
+
+    Some(Consumer {
+        kind: tunnel.kind(),
+        route: tunnel.src_device().route(),
+        adapter: tunnel.src_adapter().adapter(),
+        upstream,
+        downstream,
+        estimated,
+    })
+}
+
+/// Returns `true` if any path of `tunnel` goes over `link`.
+fn crosses(topology: &Topology, tunnel: &Tunnel, link: &Link) -> bool {
+    let parent = topology.link_parent(link);
+    let child = topology.link_child(link);
+
+    tunnel.paths().iter().any(|path| {
+        path.hops().windows(2).any(|pair| {
+            let (a, b) = (pair[0].device(), pair[1].device());
+            (same_device(a, parent) && same_device(b, child))
+                || (same_device(a, child) && same_device(b, parent))
+        })
+    })
+}
+
+// Converts link bandwidth in Gb/s into Mb/s that can be reserved by tunnels. Same as
+// `tb_maximum_bandwidth()` in the kernel this leaves 10% of the link as guard band.
+fn capacity(bw: u32) -> u32 {
+    let bw = bw * 1000;
+    bw - bw / 10
+}
+
+/// Returns bandwidth usage of every link in `topology`.
+///
+/// The `tunnels` must have been discovered from the devices of `topology` with their adapters
+/// read.
+pub fn links(topology: &Topology, tunnels: &[Tunnel]) -> Vec<LinkBandwidth> {
+    topology
+        .links()
+        .iter()
+        .map(|link| {
+            let consumers = tunnels
+                .iter()
+                .filter(|t| crosses(topology, t, link))
+                .filter_map(|t| consumer(t, tunnels, link))
+                .collect();
+
+            LinkBandwidth {
+                domain: topology.link_parent(link).domain_index(),
+                route: topology.link_parent(link).route(),
+                adapter: link.adapter(),
+                child_route: topology.link_child(link).route(),
+                up_capacity: link.tx_bandwidth().map(capacity),
+                down_capacity: link.rx_bandwidth().map(capacity),
+                consumers,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        debugfs,
+        test_util::{ADP_CS, adapter, hop, router, router_with, upstream_lane},
+    };
+
+    fn reserved(kind: Type, upstream: u32, downstream: u32) -> Consumer {
+        Consumer {
+            kind,
+            route: 0,
+            adapter: 0,
+            upstream,
+            downstream,
+            estimated: None,
+        }
+    }
+
+    // DisplayPort IN adapter capability with `ADP_DP_CS_2`, `DP_STATUS` and `ADP_DP_CS_8`.
+    fn dp_in(cs_2: u32, dp_status: u32, cs_8: u32) -> [u32; 9] {
+        [1 << 31 | 1 << 30, 0, cs_2, 0, 0, 0, dp_status, 0, cs_8]
+    }
+
+    // Host router with DisplayPort, PCIe and USB 3 tunnels to 0-1 and a USB 3 tunnel from there
+    // on to 0-301. Links run at `speed` per lane.
+    fn domain(dp_in: &[u32], speed: &str) -> Topology {
+        let mut lane = adapter(0, 1, debugfs::Type::Lane);
+        lane.set_test_paths(&[hop(8, 16, 8), hop(9, 9, 8), hop(10, 5, 8)]);
+        let mut dp = adapter(0, 5, debugfs::Type::DisplayPortIn);
+        dp.set_test_registers(&ADP_CS, dp_in);
+        dp.set_test_paths(&[hop(8, 1, 11), hop(9, 1, 10)]);
+        let mut pcie = adapter(0, 9, debugfs::Type::PcieDown);
+        pcie.set_test_paths(&[hop(8, 1, 9)]);
+        let mut usb3 = adapter(0, 16, debugfs::Type::Usb3Down);
+        // Allocated 300/400 with Scale 1.
+        usb3.set_test_registers(&ADP_CS, &[1 << 31 | 1 << 30, 0, 300 | 400 << 12, 1]);
+        usb3.set_test_paths(&[hop(8, 1, 8)]);
+        let host = router(0, vec![lane, dp, pcie, usb3]);
+
+        let link = [
+            ("rx_speed", speed),
+            ("rx_lanes", "2"),
+            ("tx_speed", speed),
+            ("tx_lanes", "2"),
+        ];
+
+        let mut lane = upstream_lane(1, 1);
+        lane.set_test_paths(&[hop(8, 16, 8), hop(9, 9, 8), hop(10, 11, 9), hop(11, 11, 8)]);
+        let mut lane3 = adapter(1, 3, debugfs::Type::Lane);
+        lane3.set_test_paths(&[hop(8, 17, 8)]);
+        let mut pcie = adapter(1, 9, debugfs::Type::PcieUp);
+        pcie.set_test_paths(&[hop(8, 1, 9)]);
+        let mut dp = adapter(1, 11, debugfs::Type::DisplayPortOut);
+        dp.set_test_paths(&[hop(8, 1, 10)]);
+        let mut usb3_up = adapter(1, 16, debugfs::Type::Usb3Up);
+        usb3_up.set_test_paths(&[hop(8, 1, 8)]);
+        let mut usb3_down = adapter(1, 17, debugfs::Type::Usb3Down);
+        usb3_down.set_test_paths(&[hop(8, 3, 8)]);
+        let hub = router_with(1, &link, vec![lane, lane3, pcie, dp, usb3_up, usb3_down]);
+
+        let mut lane = upstream_lane(0x301, 1);
+        lane.set_test_paths(&[hop(8, 16, 8)]);
+        let mut usb3 = adapter(0x301, 16, debugfs::Type::Usb3Up);
+        usb3.set_test_paths(&[hop(8, 1, 8)]);
+        let device = router_with(0x301, &link, vec![lane, usb3]);
+
+        Topology::new(vec![host, hub, device])
+    }
+
+    #[test]
+    fn oversubscribed() {
+        let mut link = LinkBandwidth {
+            domain: 0,
+            route: 0,
+            adapter: 1,
+            child_route: 1,
+            up_capacity: Some(40000),
+            down_capacity: Some(40000),
+            consumers: vec![
+                reserved(Type::Usb3, 9000, 9000),
+                reserved(Type::DisplayPort, 0, 25920),
+                reserved(Type::Pcie, 1500, 1500),
+            ],
+        };
+
+        assert_eq!(link.upstream(), 10500);
+        assert_eq!(link.downstream(), 36420);
+        assert!(!link.is_oversubscribed());
+
+        link.consumers.push(reserved(Type::DisplayPort, 0, 8640));
+        assert_eq!(link.downstream(), 45060);
+        assert!(link.is_oversubscribed());
+
+        link.down_capacity = None;
+        assert!(!link.is_oversubscribed());
+    }
+
+    #[test]
+    fn tunnels() {
+        // Link Rate 3 (8.1 Gb/s), 4 lanes, bandwidth allocation mode disabled.
+        let topology = domain(&dp_in(0, 3 << 8 | 4, 0), "40.0 Gb/s");
+        let tunnels = Tunnel::discover_all(topology.devices());
+        assert_eq!(tunnels.len(), 4);
+        assert!(tunnels.iter().all(|t| t.is_complete()));
+
+        let host_link = &topology.links()[0];
+        let hub_link = &topology.links()[1];
+        let [dp, pcie, usb3, usb3_deep] = [0, 1, 2, 3].map(|i| &tunnels[i]);
+        assert_eq!(usb3_deep.src_device().route(), 1);
+
+        assert!(crosses(&topology, dp, host_link));
+        assert!(!crosses(&topology, dp, hub_link));
+        assert!(crosses(&topology, usb3_deep, hub_link));
+        assert!(!crosses(&topology, usb3_deep, host_link));
+
+        // Deeper USB 3 tunnels share the allocation of the tunnel from the host router.
+        assert_eq!(usb3_bandwidth(usb3, &tunnels), Some((2457, 3276)));
+        assert_eq!(usb3_bandwidth(usb3_deep, &tunnels), Some((2457, 3276)));
+        assert_eq!(usb3_bandwidth(usb3_deep, &tunnels[3..]), None);
+
+        assert_eq!(dp_bandwidth(dp), Some((25920, None)));
+
+        let pcie = consumer(pcie, &tunnels, host_link).unwrap();
+        assert_eq!((pcie.upstream(), pcie.downstream()), (1500, 1500));
+        let dp = consumer(dp, &tunnels, host_link).unwrap();
+        assert_eq!((dp.upstream(), dp.downstream()), (0, 25920));
+
+        let usage: Vec<_> = links(&topology, &tunnels)
+            .iter()
+            .map(|link| {
+                let consumers: Vec<_> = link
+                    .consumers()
+                    .iter()
+                    .map(|c| (c.kind(), c.route(), c.adapter()))
+                    .collect();
+                (
+                    link.route(),
+                    link.adapter(),
+                    link.upstream(),
+                    link.downstream(),
+                    consumers,
+                )
+            })
+            .collect();
+        assert_eq!(
+            usage,
+            [
+                (
+                    0,
+                    1,
+                    1500 + 2457,
+                    25920 + 1500 + 3276,
+                    vec![
+                        (Type::DisplayPort, 0, 5),
+                        (Type::Pcie, 0, 9),
+                        (Type::Usb3, 0, 16)
+                    ]
+                ),
+                (1, 3, 2457, 3276, vec![(Type::Usb3, 1, 17)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn link_speed() {
+        let topology = domain(&dp_in(0, 3 << 8 | 4, 0), "20.0 Gb/s");
+        let tunnels = Tunnel::discover_all(topology.devices());
+        let link = &topology.links()[0];
+
+        // PCIe reserves bandwidth only on USB4 v2 links.
+        let pcie = consumer(&tunnels[1], &tunnels, link).unwrap();
+        assert_eq!(pcie.kind(), Type::Pcie);
+        assert_eq!((pcie.upstream(), pcie.downstream()), (0, 0));
+        assert_eq!(links(&topology, &tunnels)[0].down_capacity(), Some(36000));
+
+        // Bandwidth allocation mode: GR=1 (500 Mb/s), CMMS, Estimated BW=70, Allocated BW=66
+        // and DPME. Fits the 40 Gb/s link but not without the guard band.
+        let cs_2 = 1 << 11 | 1 << 20 | 70 << 24;
+        let topology = domain(&dp_in(cs_2, 66 << 24 | 3 << 8 | 4, 1 << 30), "20.0 Gb/s");
+        let tunnels = Tunnel::discover_all(topology.devices());
+        let link = &links(&topology, &tunnels)[0];
+        assert_eq!(link.downstream(), 33000 + 3276);
+        assert!(link.is_oversubscribed());
+    }
+
+    #[test]
+    fn displayport() {
+        // UHBR 10 uses 128b/132b encoding.
+        let topology = domain(&dp_in(0, 4 << 8 | 4, 0), "40.0 Gb/s");
+        let tunnels = Tunnel::discover_all(topology.devices());
+        assert_eq!(dp_bandwidth(&tunnels[0]), Some((38787, None)));
+
+        // Bandwidth allocation mode: GR=1 (500 Mb/s), CMMS, Estimated BW=40, Allocated BW=30
+        // and DPME.
+        let cs_2 = 1 << 11 | 1 << 20 | 40 << 24;
+        let topology = domain(&dp_in(cs_2, 30 << 24 | 3 << 8 | 4, 1 << 30), "40.0 Gb/s");
+        let tunnels = Tunnel::discover_all(topology.devices());
+        assert_eq!(dp_bandwidth(&tunnels[0]), Some((15000, Some(20000))));
+
+        let dp = consumer(&tunnels[0], &tunnels, &topology.links()[0]).unwrap();
+        assert_eq!(dp.downstream(), 15000);
+        assert_eq!(dp.estimated(), Some(20000));
+    }
+}
@@ -0,0 +1,175 @@
+// Manage Thunderbolt/USB4 domain boot ACL
+//
+// Copyright (C) 2026, Intel Corporation
+
+use clap::{Parser, Subcommand};
+use nix::unistd::Uid;
+use std::{
+    io::{self, Error, ErrorKind},
+    process,
+};
+
+use tbtools::{Address, Device, util};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Manage Thunderbolt/USB4 domain boot ACL", long_about = None)]
+struct Args {
+    /// Domain number
+    #[arg(short, long, default_value_t = 0)]
+    domain: u8,
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Lists the boot ACL entries (default)
+    List,
+    /// Adds router to the first empty slot
+    Add {
+        /// Route string of a connected router to add
+        #[arg(value_parser = util::parse_route, short, long, conflicts_with = "uuid")]
+        route: Option<u64>,
+        /// Unique ID of the router to add
+        #[arg(required_unless_present = "route")]
+        uuid: Option<String>,
+    },
+    /// Removes router from the boot ACL
+    Remove {
+        /// Slot to empty
+        #[arg(short, long, conflicts_with = "uuid")]
+        index: Option<usize>,
+        /// Unique ID of the router to remove
+        #[arg(required_unless_present = "index")]
+        uuid: Option<String>,
+    },
+    /// Empties all slots
+    Clear,
+}
+
+fn list(domain: &Device, acl: &[Option<String>]) -> io::Result<()> {
+    let routers: Vec<_> = tbtools::find_devices(None)?
+        .into_iter()
+        .filter(|d| d.is_router() && d.domain_index() == domain.domain_index())
+        .collect();
+
+    println!(
+        "{}: {}/{} slots used",
+        domain.kernel_name(),
+        acl.iter().flatten().count(),
+        acl.len()
+    );
+
+    for (i, uuid) in acl.iter().enumerate() {
+        let Some(uuid) = uuid else {
+            println!("  {i}: <empty>");
+            continue;
+        };
+
+        print!("  {i}: {uuid}");
+        // Show the router too if it is connected.
+        if let Some(router) = routers.iter().find(|r| {
+            r.unique_id()
+                .is_some_and(|id| id.eq_ignore_ascii_case(uuid))
+        }) {
+            print!(" ({}", router.kernel_name());
+            if let (Some(vendor), Some(device)) = (router.vendor_name(), router.device_name()) {
+                print!(" {vendor} {device}");
+            }
+            print!(")");
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn router_uuid(domain: &Device, route: u64) -> io::Result<String> {
+    let address = Address::Router {
+        domain: domain.domain_index() as u8,
+        route,
+    };
+    tbtools::find_device(&address)?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such router found"))?
+        .unique_id()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "router does not have unique ID"))
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let address = Address::Domain {
+        domain: args.domain,
+    };
+    let mut domain = tbtools::find_device(&address)?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such domain found"))?;
+    let mut acl = domain
+        .boot_acl()
+        .ok_or_else(|| Error::new(ErrorKind::Unsupported, "domain does not support boot ACL"))?;
+
+    let slots = acl.len();
+
+    match &args.command {
+        None | Some(Commands::List) => return list(&domain, &acl),
+
+        Some(Commands::Add { route, uuid }) => {
+            let uuid = match route {
+                Some(route) => router_uuid(&domain, *route)?,
+                None => uuid.clone().unwrap(),
+            };
+            if acl.iter().flatten().any(|u| u.eq_ignore_ascii_case(&uuid)) {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{uuid} is already in the boot ACL"),
+                ));
+            }
+            let slot = acl.iter_mut().find(|u| u.is_none()).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::StorageFull,
+                    format!("all {slots} slots are in use"),
+                )
+            })?;
+            *slot = Some(uuid);
+        }
+
+        Some(Commands::Remove { index, uuid }) => {
+            let slot = match (index, uuid) {
+                (Some(index), _) => acl.get_mut(*index).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("index must be less than {slots}"),
+                    )
+                })?,
+                (None, Some(uuid)) => acl
+                    .iter_mut()
+                    .find(|u| u.as_ref().is_some_and(|u| u.eq_ignore_ascii_case(uuid)))
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::NotFound,
+                            format!("{uuid} is not in the boot ACL"),
+                        )
+                    })?,
+                (None, None) => unreachable!(),
+            };
+            *slot = None;
+        }
+
+        Some(Commands::Clear) => acl.fill(None),
+    }
+
+    domain.set_boot_acl(&acl)?;
+    list(&domain, &acl)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if !matches!(args.command, None | Some(Commands::List)) && !Uid::current().is_root() {
+        eprintln!("Error: changing boot ACL requires root permissions!");
+        process::exit(1);
+    }
+
+    if let Err(err) = run(&args) {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
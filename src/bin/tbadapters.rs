@@ -10,12 +10,17 @@ use ansi_term::{
 use clap::Parser;
 use csv::Writer;
 use nix::unistd::Uid;
+use serde::Serialize;
 use std::io::{self, ErrorKind, IsTerminal, Write};
+use std::path::PathBuf;
 use std::process;
 
 use tbtools::{
     self, Address, Device,
     debugfs::{self, Adapter, BitFields, State, Type},
+    mapping::Mappings,
+    snapshot,
+    usb::Port,
     util,
 };
 
@@ -35,6 +40,16 @@ struct Args {
     /// Output suitable for scripting
     #[arg(short = 'S', long)]
     script: bool,
+    /// Output as JSON
+    #[arg(long, conflicts_with = "script")]
+    json: bool,
+    /// Read devices and registers from snapshot FILE instead of the live system
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
+    /// Use router operations to map PCIe adapters missing from the DROM. These may interfere
+    /// with the kernel connection manager
+    #[arg(long)]
+    router_ops: bool,
 }
 
 fn dump_adapter_num(adapter_num: u8, mut record: Option<&mut Vec<String>>) {
@@ -132,16 +147,114 @@ fn dump_adapter_state(adapter: &Adapter, mut record: Option<&mut Vec<String>>) {
     }
 }
 
+/// Returns the Linux devices the adapters are connected to.
+fn read_mappings(device: &Device, adapters: &[&Adapter], args: &Args) -> io::Result<Mappings> {
+    // These are only available on the live system.
+    if args.snapshot.is_some() {
+        return Ok(Mappings::default());
+    }
+
+    // Reading the connectors opens every DP AUX device so only do it if needed.
+    let connectors =
+        device.is_host_router() && adapters.iter().any(|a| a.kind() == Type::DisplayPortIn);
+
+    // Resolving PCI bridges and USB ports requires following the tunnels from the host router
+    // so pull in all the routers in the domain.
+    let has_tunnels = device.adapters().is_some_and(|adapters| {
+        adapters.iter().any(|a| {
+            matches!(
+                a.kind(),
+                Type::PcieUp
+                    | Type::PcieDown
+                    | Type::Usb3Up
+                    | Type::Usb3Down
+                    | Type::Usb3GenTUp
+                    | Type::Usb3GenTDown
+            )
+        })
+    });
+    let mut routers: Vec<_> = if has_tunnels {
+        tbtools::find_devices(None)?
+            .into_iter()
+            .filter(|d| d.is_router() && d.domain_index() == device.domain_index())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for router in &mut routers {
+        router.read_adapters()?;
+        for adapter in router.adapters_mut().unwrap() {
+            if adapter.is_valid() {
+                adapter.read_paths().ok();
+            }
+        }
+        router.read_drom().ok();
+    }
+
+    Mappings::read(&routers, connectors, args.router_ops)
+}
+
+fn format_port(port: &Port) -> String {
+    let mut mapping = port.name();
+    // The first device is the one directly connected to the port.
+    if let Some(device) = port.devices().first()
+        && let Some(product) = device.product_name()
+    {
+        mapping.push_str(&format!(" ({product})"));
+    }
+    mapping
+}
+
+/// Adapter entry of the JSON output.
+#[derive(Serialize)]
+struct Record {
+    adapter: u8,
+    kind: Type,
+    state: State,
+    upstream: bool,
+    mapping: Option<String>,
+}
+
+fn mapping(adapter: &Adapter, mappings: &Mappings) -> String {
+    if let Some(connector) = mappings.connector(adapter) {
+        match connector.aux() {
+            Some(aux) => format!("{} (drm_dp_aux{aux})", connector.name()),
+            None => connector.name().to_string(),
+        }
+    } else if let Some(bridge) = mappings.bridge(adapter) {
+        bridge.description()
+    } else if let Some(port) = mappings.port(adapter) {
+        format_port(port)
+    } else {
+        String::new()
+    }
+}
+
+fn dump_mapping(adapter: &Adapter, mappings: &Mappings, mut record: Option<&mut Vec<String>>) {
+    let mapping = mapping(adapter, mappings);
+
+    if let Some(ref mut record) = record {
+        record.push(mapping);
+    } else if !mapping.is_empty() {
+        print!(" {mapping}");
+    }
+}
+
 fn dump_other(mut record: Option<&mut Vec<String>>) {
     if let Some(ref mut record) = record {
         record.push(String::from("Not implemented"));
         record.push(String::new());
+        record.push(String::new());
     } else {
         print!("Not implemented");
     }
 }
 
-fn dump_adapter<W: Write>(adapter: &Adapter, mut writer: Option<&mut Writer<W>>) -> io::Result<()> {
+fn dump_adapter<W: Write>(
+    adapter: &Adapter,
+    mappings: &Mappings,
+    mut writer: Option<&mut Writer<W>>,
+) -> io::Result<()> {
     let mut record: Option<Vec<String>> = if writer.is_some() {
         Some(Vec::new())
     } else {
@@ -153,6 +266,7 @@ fn dump_adapter<W: Write>(adapter: &Adapter, mut writer: Option<&mut Writer<W>>)
     if adapter.is_lane() || adapter.is_protocol() {
         dump_adapter_type(adapter, record.as_mut());
         dump_adapter_state(adapter, record.as_mut());
+        dump_mapping(adapter, mappings, record.as_mut());
     } else {
         dump_other(record.as_mut());
     }
@@ -169,7 +283,7 @@ fn dump_adapter<W: Write>(adapter: &Adapter, mut writer: Option<&mut Writer<W>>)
 fn dump_adapters(device: &mut Device, args: &Args) -> io::Result<()> {
     let mut writer = if args.script {
         let mut writer = Writer::from_writer(io::stdout());
-        writer.write_record(["adapter", "type", "state"])?;
+        writer.write_record(["adapter", "type", "state", "mapping"])?;
         Some(writer)
     } else {
         None
@@ -177,18 +291,43 @@ fn dump_adapters(device: &mut Device, args: &Args) -> io::Result<()> {
 
     device.read_adapters()?;
 
+    let mut adapters = Vec::new();
+
     if let Some(adapter_numbers) = &args.adapter {
         for adapter_num in adapter_numbers {
             if let Some(adapter) = device.adapter(*adapter_num) {
-                dump_adapter(adapter, writer.as_mut())?;
+                adapters.push(adapter);
             } else {
                 eprintln!("Warning: non-existing adapter: {}!", *adapter_num);
             }
         }
-    } else if let Some(adapters) = device.adapters() {
-        for adapter in adapters {
-            dump_adapter(adapter, writer.as_mut())?;
-        }
+    } else if let Some(all) = device.adapters() {
+        adapters.extend(all);
+    }
+
+    let mappings = read_mappings(device, &adapters, args)?;
+
+    if args.json {
+        let records: Vec<_> = adapters
+            .iter()
+            .map(|adapter| {
+                let mapping = mapping(adapter, &mappings);
+                Record {
+                    adapter: adapter.adapter(),
+                    kind: adapter.kind(),
+                    state: adapter.state(),
+                    upstream: adapter.is_upstream(),
+                    mapping: (!mapping.is_empty()).then_some(mapping),
+                }
+            })
+            .collect();
+        serde_json::to_writer_pretty(io::stdout(), &records)?;
+        println!();
+        return Ok(());
+    }
+
+    for adapter in adapters {
+        dump_adapter(adapter, &mappings, writer.as_mut())?;
     }
 
     Ok(())
@@ -215,14 +354,21 @@ fn dump(args: &Args) -> io::Result<()> {
 fn main() {
     let args = Args::parse();
 
-    if !Uid::current().is_root() {
-        eprintln!("Error: debugfs access requires root permissions");
-        process::exit(1);
-    }
+    if let Some(path) = &args.snapshot {
+        if let Err(err) = snapshot::replay_file(path) {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    } else {
+        if !Uid::current().is_root() {
+            eprintln!("Error: debugfs access requires root permissions");
+            process::exit(1);
+        }
 
-    if let Err(err) = debugfs::mount() {
-        eprintln!("Error: failed to mount debugfs: {err}");
-        process::exit(1);
+        if let Err(err) = debugfs::mount() {
+            eprintln!("Error: failed to mount debugfs: {err}");
+            process::exit(1);
+        }
     }
 
     if let Err(err) = dump(&args) {
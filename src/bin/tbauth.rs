@@ -3,15 +3,18 @@
 // Copyright (C) 2023, Intel Corporation
 // Author: Mika Westerberg <mika.westerberg@linux.intel.com>
 
-use std::fmt::{self, Write};
 use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Write as IoWrite};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
 use std::process;
 
 use clap::Parser;
-use rand::prelude::*;
 
-use tbtools::{Address, Device, Kind, SecurityLevel, util};
+use tbtools::{
+    Address, Device, Kind, SecurityLevel,
+    keystore::{self, KeyStore},
+    util,
+};
 
 #[derive(Parser, Debug)]
 #[command(version)]
@@ -32,6 +35,15 @@ struct Args {
     /// Challenge with key
     #[arg(short = 'C', long, group = "key")]
     challenge_key_path: Option<String>,
+    /// Challenge with the stored key, or enroll and store a new key if there is none
+    #[arg(short, long, group = "key")]
+    store: bool,
+    /// Remove the stored key of the device
+    #[arg(long, conflicts_with_all = ["key", "deauthorize"])]
+    forget: bool,
+    /// Directory of the key store (default: $TBTOOLS_KEY_DIR or /var/lib/tbtools/keys)
+    #[arg(long, value_name = "DIR")]
+    key_dir: Option<PathBuf>,
 }
 
 fn is_deauthorization_supported(device: &Device) -> bool {
@@ -45,17 +57,10 @@ fn is_secure_supported(device: &Device) -> bool {
     device.has_key()
 }
 
-fn gen_key() -> Result<String, fmt::Error> {
-    let mut data = [0u8; 32];
-
-    rand::rng().fill_bytes(&mut data);
-
-    let mut key = String::new();
-    for b in data {
-        write!(key, "{b:02x}")?;
-    }
-
-    Ok(key)
+fn key_store(args: &Args) -> KeyStore {
+    args.key_dir
+        .as_deref()
+        .map_or_else(KeyStore::open_default, KeyStore::new)
 }
 
 fn main() -> io::Result<()> {
@@ -88,21 +93,56 @@ fn main() -> io::Result<()> {
         return tbtools::authorize_device(&mut device, 0);
     }
 
-    let authorized: u32;
+    if args.forget {
+        let Some(unique_id) = device.unique_id() else {
+            eprintln!("Error: Device does not have unique ID!");
+            process::exit(1);
+        };
+        if !key_store(&args).remove(&unique_id)? {
+            eprintln!("Error: No key stored for {unique_id}");
+            process::exit(1);
+        }
+        return Ok(());
+    }
 
-    if let Some(path) = &args.add_key_path {
+    if args.store {
         if !is_secure_supported(&device) {
             eprintln!("Error: Domain security level is not 'secure'");
             process::exit(1);
         }
 
-        let key = match gen_key() {
+        let store = key_store(&args);
+        let challenge = device
+            .unique_id()
+            .is_some_and(|id| store.get(&id).is_ok_and(|key| key.is_some()));
+
+        match store.authorize(&mut device) {
+            Ok(authorization) => println!("{}: {authorization}", device.kernel_name()),
             Err(err) => {
-                eprintln!("Error: Key generation failed {err}");
+                eprintln!(
+                    "Error: Device {} authorization failed {}",
+                    device.kernel_name(),
+                    err
+                );
+                if challenge {
+                    eprintln!("Device may have lost the key, re-enroll with --forget first");
+                }
                 process::exit(1);
             }
-            Ok(key) => key,
-        };
+        }
+
+        return Ok(());
+    }
+
+    let authorized: u32;
+
+    if let Some(path) = &args.add_key_path {
+        if !is_secure_supported(&device) {
+            eprintln!("Error: Domain security level is not 'secure'");
+            process::exit(1);
+        }
+
+        let key = keystore::generate_key();
 
         device.set_key(&key)?;
 
@@ -0,0 +1,227 @@
+// Authorize Thunderbolt/USB4 devices automatically based on policy
+//
+// Copyright (C) 2026, Intel Corporation
+
+use clap::Parser;
+use nix::unistd::Uid;
+use std::{
+    collections::HashMap,
+    io::{self, Error, ErrorKind},
+    path::PathBuf,
+    process,
+};
+
+use tbtools::{
+    Address, Device, Kind,
+    keystore::KeyStore,
+    monitor::{Builder, ChangeEvent, Event},
+    policy::{self, Action, Policy},
+};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Authorize Thunderbolt/USB4 devices automatically based on policy", long_about = None)]
+struct Args {
+    /// Domain number. If not given all domains are handled
+    #[arg(short, long)]
+    domain: Option<u32>,
+    /// Policy file
+    #[arg(short, long, value_name = "FILE", default_value = policy::DEFAULT_PATH)]
+    policy: PathBuf,
+    /// Directory of the key store (default: $TBTOOLS_KEY_DIR or /var/lib/tbtools/keys)
+    #[arg(long, value_name = "DIR")]
+    key_dir: Option<PathBuf>,
+    /// Only log the decisions, do not authorize anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+    /// Handle routers that are already connected and exit
+    #[arg(short, long)]
+    once: bool,
+}
+
+struct Daemon {
+    policy: Policy,
+    store: KeyStore,
+    domain: Option<u32>,
+    dry_run: bool,
+    /// Unique IDs of the routers already decided keyed by the kernel name, which is still known
+    /// when the router is unplugged.
+    decided: HashMap<String, String>,
+}
+
+fn describe(device: &Device) -> String {
+    let mut s = format!(
+        "{} {:04x}:{:04x}",
+        device.kernel_name(),
+        device.vendor(),
+        device.device()
+    );
+    if let (Some(vendor), Some(name)) = (device.vendor_name(), device.device_name()) {
+        s.push_str(&format!(" {vendor} {name}"));
+    }
+    if let Some(unique_id) = device.unique_id() {
+        s.push_str(&format!(" ({unique_id})"));
+    }
+    s
+}
+
+impl Daemon {
+    fn handle(&mut self, device: &mut Device) {
+        if !device.is_router()
+            || device.authorized().unwrap_or(true)
+            || self.domain.is_some_and(|d| d != device.domain_index())
+        {
+            return;
+        }
+
+        let unique_id = device.unique_id();
+        if unique_id
+            .as_ref()
+            .is_some_and(|id| self.decided.values().any(|d| d == id))
+        {
+            return;
+        }
+
+        let name = describe(device);
+
+        // Parents are handled first so the policy is never bypassed by authorizing them here.
+        if device
+            .parent()
+            .is_some_and(|parent| parent.is_router() && !parent.authorized().unwrap_or(false))
+        {
+            println!("{name}: parent is not authorized, skipping");
+            return;
+        }
+
+        // Decided even if the action below fails so that it is not retried on every event.
+        if let Some(unique_id) = unique_id {
+            self.decided.insert(device.kernel_name(), unique_id);
+        }
+
+        let Some(rule) = self.policy.evaluate(device) else {
+            println!("{name}: no matching rule, leaving unauthorized");
+            return;
+        };
+        let action = rule.action();
+
+        println!("{name}: {action} (rule at line {})", rule.line());
+        if self.dry_run {
+            return;
+        }
+
+        let result = match action {
+            Action::Authorize => device.authorize(1),
+            Action::Challenge => self
+                .store
+                .authorize(device)
+                .map(|authorization| println!("{name}: {authorization}")),
+            Action::Reject => Ok(()),
+        };
+
+        if let Err(err) = result {
+            eprintln!("{name}: {action} failed: {err}");
+        }
+    }
+
+    fn coldplug(&mut self) -> io::Result<()> {
+        let mut routers: Vec<_> = tbtools::find_devices(None)?
+            .into_iter()
+            .filter(|d| d.is_router())
+            .collect();
+        routers.sort_by_key(|r| (r.domain_index(), r.depth()));
+
+        for router in &routers {
+            let address = Address::Router {
+                domain: router.domain_index() as u8,
+                route: router.route(),
+            };
+            // Re-read so that the parents authorized in this loop are taken into account.
+            match tbtools::find_device(&address) {
+                Ok(Some(mut router)) => self.handle(&mut router),
+                Ok(None) => (),
+                Err(err) => eprintln!("{}: {err}", router.kernel_name()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        let mut monitor = Builder::new()?.kind(Kind::Router)?.build()?;
+
+        self.coldplug()?;
+
+        loop {
+            if !monitor.poll(None)? {
+                continue;
+            }
+            for event in monitor.iter_mut() {
+                match event {
+                    Event::Add(mut device) => self.handle(&mut device),
+                    // Decide again if the router is plugged back.
+                    Event::Remove(device) => {
+                        self.decided.remove(&device.kernel_name());
+                    }
+                    // Routers below a router that just got authorized can be handled now.
+                    Event::Change(_, Some(ChangeEvent::Router { authorized }))
+                        if authorized > 0 =>
+                    {
+                        if let Err(err) = self.coldplug() {
+                            eprintln!("Error: {err}");
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let policy = Policy::load(&args.policy)?;
+    if policy.rules().is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: no rules", args.policy.display()),
+        ));
+    }
+
+    let store = args
+        .key_dir
+        .as_deref()
+        .map_or_else(KeyStore::open_default, KeyStore::new);
+
+    let mut daemon = Daemon {
+        policy,
+        store,
+        domain: args.domain,
+        dry_run: args.dry_run,
+        decided: HashMap::new(),
+    };
+
+    println!(
+        "Loaded {} rules from {}",
+        daemon.policy.rules().len(),
+        args.policy.display()
+    );
+
+    if args.once {
+        daemon.coldplug()
+    } else {
+        daemon.run()
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if !args.dry_run && !Uid::current().is_root() {
+        eprintln!("Error: authorizing devices requires root permissions!");
+        process::exit(1);
+    }
+
+    if let Err(err) = run(&args) {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,303 @@
+// Compare Thunderbolt/USB4 config spaces between two captures
+//
+// Copyright (C) 2026, Intel Corporation
+
+use ansi_term::Colour::{Cyan, Green, Red, Yellow};
+use clap::Parser;
+use nix::unistd::Uid;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process,
+};
+
+use tbtools::{
+    self, Device,
+    debugfs::{self, BitField, Diff, Name, Register, RegisterChange},
+    snapshot,
+};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Compare Thunderbolt/USB4 config spaces between two captures", long_about = None)]
+struct Args {
+    /// Only compare devices in this domain
+    #[arg(short, long)]
+    domain: Option<u32>,
+    /// Do not compare adapter counters
+    #[arg(short = 'C', long)]
+    no_counters: bool,
+    /// Snapshot FILE taken before
+    old: PathBuf,
+    /// Snapshot FILE taken after (live system if not given)
+    new: Option<PathBuf>,
+}
+
+/// Config spaces of a single adapter.
+#[derive(Default)]
+struct AdapterRegs {
+    regs: Vec<Register>,
+    paths: Vec<Register>,
+    counters: Vec<Register>,
+}
+
+/// Config spaces of a single router.
+struct RouterRegs {
+    name: String,
+    regs: Vec<Register>,
+    adapters: BTreeMap<u8, AdapterRegs>,
+}
+
+/// All routers keyed by domain and route.
+type Capture = BTreeMap<(u32, u64), RouterRegs>;
+
+fn read_router(device: &mut Device, args: &Args) -> io::Result<RouterRegs> {
+    device.read_adapters()?;
+
+    let regs = device.registers().cloned().unwrap_or_default();
+    let mut adapters = BTreeMap::new();
+
+    for adapter in device.adapters_mut().unwrap() {
+        // Not all adapters have path or counters config spaces so ignore errors here.
+        let paths = adapter
+            .read_paths()
+            .ok()
+            .and_then(|_| adapter.path_registers().cloned())
+            .unwrap_or_default();
+        let counters = if args.no_counters {
+            Vec::new()
+        } else {
+            adapter
+                .read_counters()
+                .ok()
+                .and_then(|_| adapter.counter_registers().cloned())
+                .unwrap_or_default()
+        };
+
+        adapters.insert(
+            adapter.adapter(),
+            AdapterRegs {
+                regs: adapter.registers().cloned().unwrap_or_default(),
+                paths,
+                counters,
+            },
+        );
+    }
+
+    Ok(RouterRegs {
+        name: format!(
+            "Domain {} Route {:x}",
+            device.domain_index(),
+            device.route()
+        ),
+        regs,
+        adapters,
+    })
+}
+
+fn capture(args: &Args) -> io::Result<Capture> {
+    let mut capture = Capture::new();
+
+    for mut device in tbtools::find_devices(None)? {
+        if !device.is_router() {
+            continue;
+        }
+        if let Some(domain) = args.domain
+            && device.domain_index() != domain
+        {
+            continue;
+        }
+
+        let router = read_router(&mut device, args)?;
+        capture.insert((device.domain_index(), device.route()), router);
+    }
+
+    Ok(capture)
+}
+
+fn replay(path: &Path) {
+    if let Err(err) = snapshot::replay_file(path) {
+        eprintln!("Error: {err}");
+        process::exit(2);
+    }
+}
+
+fn color_before(value: &str) -> String {
+    if io::stdout().is_terminal() {
+        Red.paint(value).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn color_after(value: &str) -> String {
+    if io::stdout().is_terminal() {
+        Green.paint(value).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn color_name(name: &str) -> String {
+    if io::stdout().is_terminal() {
+        Yellow.bold().paint(name).to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn color_header(header: &str) -> String {
+    if io::stdout().is_terminal() {
+        Cyan.bold().paint(header).to_string()
+    } else {
+        header.to_string()
+    }
+}
+
+fn format_field_value(field: &BitField, value: u32) -> String {
+    match field.value_name(value) {
+        Some(name) => format!("{value:#x} ({name})"),
+        None => format!("{value:#x}"),
+    }
+}
+
+fn format_value(reg: Option<&Register>) -> String {
+    match reg {
+        Some(reg) => format!("0x{:08x}", reg.value()),
+        None => String::from("----------"),
+    }
+}
+
+fn dump_changes(space: &str, changes: &[RegisterChange]) {
+    for change in changes {
+        let reg = change.after().or(change.before()).unwrap();
+
+        println!(
+            "  {space} 0x{:04x} {:<15} {} → {}",
+            change.offset(),
+            color_name(reg.name().unwrap_or("")),
+            color_before(&format_value(change.before())),
+            color_after(&format_value(change.after())),
+        );
+
+        for (field, before, after) in change.fields() {
+            println!(
+                "    [{:>02}:{:>02}] {}: {} → {}",
+                field.range().start(),
+                field.range().end(),
+                field.name(),
+                color_before(&format_field_value(field, before)),
+                color_after(&format_field_value(field, after)),
+            );
+        }
+    }
+}
+
+/// Dumps the register changes of a router. Returns `true` if there were any.
+fn diff_router(old: &RouterRegs, new: &RouterRegs) -> bool {
+    let mut changes = vec![(String::from("Router"), old.regs.diff(&new.regs))];
+
+    let empty = AdapterRegs::default();
+    let adapters: BTreeSet<_> = old.adapters.keys().chain(new.adapters.keys()).collect();
+
+    for adapter in adapters {
+        let before = old.adapters.get(adapter).unwrap_or(&empty);
+        let after = new.adapters.get(adapter).unwrap_or(&empty);
+
+        changes.push((format!("Adapter {adapter}"), before.regs.diff(&after.regs)));
+        changes.push((
+            format!("Adapter {adapter} Path"),
+            before.paths.diff(&after.paths),
+        ));
+        changes.push((
+            format!("Adapter {adapter} Counters"),
+            before.counters.diff(&after.counters),
+        ));
+    }
+
+    if changes.iter().all(|(_, c)| c.is_empty()) {
+        return false;
+    }
+
+    println!("{}", color_header(&new.name));
+    for (space, c) in &changes {
+        dump_changes(space, c);
+    }
+
+    true
+}
+
+/// Dumps the differences between `old` and `new`. Returns `true` if there were any.
+fn diff(old: &Capture, new: &Capture) -> bool {
+    let mut changed = false;
+
+    for (key, router) in old {
+        match new.get(key) {
+            Some(new_router) => changed |= diff_router(router, new_router),
+            None => {
+                println!(
+                    "{}: {}",
+                    color_header(&router.name),
+                    color_before("removed")
+                );
+                changed = true;
+            }
+        }
+    }
+
+    for (key, router) in new {
+        if !old.contains_key(key) {
+            println!("{}: {}", color_header(&router.name), color_after("added"));
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn main() {
+    let args = Args::parse();
+
+    // Live system is read first because replaying a snapshot replaces it.
+    let new = match &args.new {
+        Some(_) => None,
+        None => {
+            if !Uid::current().is_root() {
+                eprintln!("Error: debugfs access requires root permissions");
+                process::exit(2);
+            }
+
+            if let Err(err) = debugfs::mount() {
+                eprintln!("Error: failed to mount debugfs: {err}");
+                process::exit(2);
+            }
+
+            Some(capture(&args).unwrap_or_else(|err| {
+                eprintln!("Error: {err}");
+                process::exit(2);
+            }))
+        }
+    };
+
+    replay(&args.old);
+    let old = capture(&args).unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        process::exit(2);
+    });
+
+    let new = new.unwrap_or_else(|| {
+        replay(args.new.as_ref().unwrap());
+        capture(&args).unwrap_or_else(|err| {
+            eprintln!("Error: {err}");
+            process::exit(2);
+        })
+    });
+
+    // Exit status follows diff(1): 0 if no differences, 1 if there are and 2 on trouble.
+    if diff(&old, &new) {
+        process::exit(1);
+    }
+
+    println!("No changes");
+}
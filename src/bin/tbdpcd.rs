@@ -0,0 +1,177 @@
+// Dump and control USB4 DisplayPort DPCD registers
+//
+// Copyright (C) 2026, Intel Corporation
+
+use ansi_term::Colour::{Cyan, Yellow};
+use clap::{ArgGroup, Parser};
+use nix::unistd::Uid;
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process,
+};
+
+use tbtools::{
+    debugfs::{BitFields, Name, Register},
+    dpcd::{self, Dpcd, RequestStatus},
+};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Dump and control USB4 DisplayPort DPCD registers", long_about = None)]
+#[command(group(ArgGroup::new("source").required(true).args(["aux", "file"])))]
+#[command(group(ArgGroup::new("action").args(["bandwidth", "enable_bw", "disable_bw", "request_bw"])))]
+struct Args {
+    /// DisplayPort AUX device number (/dev/drm_dp_auxN)
+    #[arg(short, long)]
+    aux: Option<u32>,
+    /// Read DPCD from FILE instead of AUX device
+    #[arg(short, long, value_name = "FILE")]
+    file: Option<PathBuf>,
+    /// Verbose output (use multiple times to get more detailed output)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Show bandwidth allocation mode status
+    #[arg(short, long)]
+    bandwidth: bool,
+    /// Enable bandwidth allocation mode
+    #[arg(short, long)]
+    enable_bw: bool,
+    /// Disable bandwidth allocation mode
+    #[arg(short = 'D', long)]
+    disable_bw: bool,
+    /// Request bandwidth in Mb/s
+    #[arg(short, long, value_name = "MBPS")]
+    request_bw: Option<u32>,
+}
+
+fn color_field_value(value: &str) -> String {
+    if io::stdout().is_terminal() {
+        Cyan.paint(format!("{value:>10}")).to_string()
+    } else {
+        format!("{value:>10}")
+    }
+}
+
+fn color_field_short_name(short_name: &str) -> String {
+    if io::stdout().is_terminal() {
+        Yellow.bold().paint(short_name).to_string()
+    } else {
+        String::from(short_name)
+    }
+}
+
+fn dump_register(reg: &Register, args: &Args) {
+    print!(
+        "0x{:05x} 0x{:02x}",
+        dpcd::USB4_DPCD_BASE + u32::from(reg.offset()),
+        reg.value()
+    );
+    if let Some(name) = reg.name() {
+        print!(" {name}");
+    }
+    println!();
+
+    if args.verbose > 0
+        && let Some(fields) = reg.fields()
+    {
+        for field in fields {
+            let v = reg.field(field.name());
+            let value_name = field
+                .value_name(v)
+                .map_or(String::new(), |name| format!(" → {name}"));
+            let short_name = field.short_name().map_or(String::new(), |short_name| {
+                format!(" ({})", color_field_short_name(short_name))
+            });
+            println!(
+                "  [{:>02}:{:>02}] {} {}{}{}",
+                field.range().start(),
+                field.range().end(),
+                color_field_value(&format!("{v:#x}")),
+                field.name(),
+                short_name,
+                value_name,
+            );
+        }
+    }
+}
+
+fn dump_registers(dpcd: &Dpcd, args: &Args) -> io::Result<()> {
+    for reg in dpcd.read_registers()? {
+        // Only dump known registers unless asked otherwise.
+        if reg.name().is_some() || args.verbose > 1 {
+            dump_register(&reg, args);
+        }
+    }
+    Ok(())
+}
+
+fn dump_bandwidth(dpcd: &Dpcd) -> io::Result<()> {
+    let yesno = |b| if b { "yes" } else { "no" };
+
+    println!(
+        "BW allocation mode supported: {}",
+        yesno(dpcd.bw_mode_supported()?)
+    );
+    println!(
+        "BW allocation mode enabled: {}",
+        yesno(dpcd.bw_mode_enabled()?)
+    );
+    println!("Group ID: {}", dpcd.group_id()?);
+    println!("Granularity: {} Mb/s", dpcd.granularity()?);
+    println!("Estimated: {} Mb/s", dpcd.estimated_bw()?);
+    println!("Allocated: {} Mb/s", dpcd.allocated_bw()?);
+    println!("Requested: {} Mb/s", dpcd.requested_bw()?);
+
+    let status = match dpcd.request_status()? {
+        RequestStatus::None => "none",
+        RequestStatus::Succeeded => "succeeded",
+        RequestStatus::Failed => "failed",
+    };
+    println!("Last request: {status}");
+
+    Ok(())
+}
+
+fn run(dpcd: &Dpcd, args: &Args) -> io::Result<()> {
+    if args.enable_bw {
+        dpcd.set_bw_mode(true)?;
+        println!("BW allocation mode enabled");
+    } else if args.disable_bw {
+        dpcd.set_bw_mode(false)?;
+        println!("BW allocation mode disabled");
+    } else if let Some(bw) = args.request_bw {
+        dpcd.request_bw(bw)?;
+        println!("Requested {} Mb/s", dpcd.requested_bw()?);
+    } else if args.bandwidth {
+        dump_bandwidth(dpcd)?;
+    } else {
+        dump_registers(dpcd, args)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let dpcd = if let Some(aux) = args.aux {
+        if !Uid::current().is_root() {
+            eprintln!("Error: AUX device access requires root permissions!");
+            process::exit(1);
+        }
+        Dpcd::with_aux(aux)
+    } else {
+        Dpcd::open(args.file.as_ref().unwrap())
+    };
+
+    let dpcd = dpcd.unwrap_or_else(|err| {
+        eprintln!("Error: failed to open DPCD: {err}");
+        process::exit(1);
+    });
+
+    if let Err(err) = run(&dpcd, &args) {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
@@ -8,6 +8,7 @@ use clap::Parser;
 use nix::unistd::Uid;
 use std::{
     io::{self, ErrorKind, IsTerminal},
+    path::PathBuf,
     process,
 };
 
@@ -15,7 +16,7 @@ use tbtools::{
     self, Address, Device, Version,
     debugfs::{self, BitFields, Name, Register},
     drom::{Drom, DromEntry, RankType, SingleDataPathPreference, TmuMode, TmuRate},
-    usb4, util,
+    snapshot, usb4, util,
 };
 
 #[derive(Parser, Debug)]
@@ -36,6 +37,12 @@ struct Args {
     /// Select counters config space of an adapter
     #[arg(short, long)]
     counters: bool,
+    /// Select sideband registers of a USB4 port or retimer
+    #[arg(short, long)]
+    sideband: bool,
+    /// Retimer index if accessing retimer sideband registers
+    #[arg(short, long, requires_all = ["adapter", "sideband"], value_parser = clap::value_parser!(u8).range(1..6))]
+    index: Option<u8>,
     /// Verbose output (use multiple times to get more detailed output)
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
@@ -51,6 +58,12 @@ struct Args {
     /// Number of double words to read
     #[arg(short = 'N', long)]
     nregs: Option<usize>,
+    /// Output registers (with decoded fields) or DROM as JSON
+    #[arg(long)]
+    json: bool,
+    /// Read devices and registers from snapshot FILE instead of the live system
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
     /// Double word offset or name of a register
     offset: Option<String>,
 }
@@ -80,6 +93,19 @@ fn offset(regs: &[Register], args: &Args) -> u16 {
 }
 
 fn dump_value(reg: &Register, args: &Args) {
+    // Sideband registers may be longer than double word so dump them byte by byte.
+    if let Some(data) = reg.data()
+        && data.len() > 4
+    {
+        let bytes: Vec<_> = data.iter().map(|b| format!("0x{b:02x}")).collect();
+        print!("{}", bytes.join(" "));
+
+        if args.verbose > 1 {
+            print!(" {}", util::bytes_to_ascii(data));
+        }
+        return;
+    }
+
     let value = reg.value();
     print!("0x{value:08x}");
 
@@ -161,9 +187,9 @@ fn color_adapter_num(adapter_num: u8) -> String {
     }
 }
 
-fn dump_regs(regs: &Vec<Register>, args: &Args) {
+fn select_regs<'a>(regs: &'a [Register], args: &Args) -> Vec<&'a Register> {
     let offset = offset(regs, args);
-    let mut i = 0;
+    let mut selected = Vec::new();
 
     for reg in regs {
         if let Some(cap_id) = args.cap_id {
@@ -188,13 +214,28 @@ fn dump_regs(regs: &Vec<Register>, args: &Args) {
             continue;
         }
 
-        if let Some(nregs) = args.nregs {
-            if i >= nregs {
-                return;
-            }
-            i += 1;
+        if let Some(nregs) = args.nregs
+            && selected.len() >= nregs
+        {
+            break;
         }
 
+        selected.push(reg);
+    }
+
+    selected
+}
+
+fn dump_regs(regs: &[Register], args: &Args) -> io::Result<()> {
+    let regs = select_regs(regs, args);
+
+    if args.json {
+        serde_json::to_writer_pretty(io::stdout(), &regs)?;
+        println!();
+        return Ok(());
+    }
+
+    for reg in regs {
         if args.verbose > 0 {
             print!("0x{:04x} ", reg.offset());
         }
@@ -237,6 +278,8 @@ fn dump_regs(regs: &Vec<Register>, args: &Args) {
             }
         }
     }
+
+    Ok(())
 }
 
 fn dump_bytes(bytes: &[u8], offset: usize, args: &Args) {
@@ -538,10 +581,15 @@ fn dump_router(device: &mut Device, args: &Args) -> io::Result<()> {
         device.read_drom()?;
 
         if let Some(drom) = device.drom() {
+            if args.json {
+                serde_json::to_writer_pretty(io::stdout(), drom)?;
+                println!();
+                return Ok(());
+            }
             dump_drom(drom, args);
         }
     } else if let Some(regs) = device.registers() {
-        dump_regs(regs, args);
+        dump_regs(regs, args)?;
     }
 
     Ok(())
@@ -555,23 +603,57 @@ fn dump_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()>
             adapter.read_paths()?;
 
             if let Some(regs) = adapter.path_registers() {
-                dump_regs(regs, args);
+                dump_regs(regs, args)?;
             }
         } else if args.counters {
             adapter.read_counters()?;
 
             if let Some(regs) = adapter.counter_registers() {
-                dump_regs(regs, args);
+                dump_regs(regs, args)?;
+            }
+        } else if args.sideband {
+            adapter.read_sideband()?;
+
+            if let Some(regs) = adapter.sideband_registers() {
+                dump_regs(regs, args)?;
             }
         } else if let Some(regs) = adapter.registers() {
-            dump_regs(regs, args);
+            dump_regs(regs, args)?;
         }
     }
 
     Ok(())
 }
 
+fn dump_retimer(args: &Args) -> io::Result<()> {
+    let address = Address::Retimer {
+        domain: args.domain,
+        route: args.route,
+        adapter: args.adapter.unwrap(),
+        index: args.index.unwrap(),
+    };
+    let mut device = match tbtools::find_device(&address)? {
+        Some(device) => device,
+        None => {
+            eprintln!("Error: No such retimer found!");
+            process::exit(1);
+        }
+    };
+
+    device.read_sideband()?;
+
+    if let Some(regs) = device.sideband_registers() {
+        dump_regs(regs, args)?;
+    }
+
+    Ok(())
+}
+
 fn dump(args: &Args) -> io::Result<()> {
+    if args.index.is_some() {
+        return dump_retimer(args);
+    }
+
     let address = Address::Router {
         domain: args.domain,
         route: args.route,
@@ -593,6 +675,9 @@ fn dump(args: &Args) -> io::Result<()> {
             process::exit(1);
         }
         dump_adapter(&mut device, adapter, args)?
+    } else if args.sideband {
+        eprintln!("Error: Only USB4 ports and retimers have sideband registers!");
+        process::exit(1);
     } else {
         dump_router(&mut device, args)?
     }
@@ -603,15 +688,22 @@ fn dump(args: &Args) -> io::Result<()> {
 fn main() {
     let args = Args::parse();
 
-    if !Uid::current().is_root() {
-        eprintln!("Error: debugfs access requires root permissions");
-        process::exit(1);
-    }
+    if let Some(path) = &args.snapshot {
+        if let Err(err) = snapshot::replay_file(path) {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    } else {
+        if !Uid::current().is_root() {
+            eprintln!("Error: debugfs access requires root permissions");
+            process::exit(1);
+        }
 
-    debugfs::mount().unwrap_or_else(|e| {
-        eprintln!("Error: failed to mount debugfs: {e}");
-        process::exit(1);
-    });
+        debugfs::mount().unwrap_or_else(|e| {
+            eprintln!("Error: failed to mount debugfs: {e}");
+            process::exit(1);
+        });
+    }
 
     dump(&args).unwrap_or_else(|e| {
         eprintln!("Error: {e}");
@@ -6,17 +6,19 @@
 use std::{
     io::{self, ErrorKind},
     ops::RangeInclusive,
+    path::PathBuf,
     process,
 };
 
 use clap::Parser;
 use csv::Writer;
 use nix::unistd::Uid;
+use serde::Serialize;
 
 use tbtools::{
     Address, Device,
     debugfs::{self, BitFields, Name, Register},
-    util,
+    snapshot, util,
 };
 
 #[derive(Parser, Debug)]
@@ -38,6 +40,12 @@ struct Args {
     /// Select counters config space of an adapter
     #[arg(short, long)]
     counters: bool,
+    /// Select sideband registers of a USB4 port or retimer
+    #[arg(short, long)]
+    sideband: bool,
+    /// Retimer index if accessing retimer sideband registers
+    #[arg(short, long, requires_all = ["adapter", "sideband"], value_parser = clap::value_parser!(u8).range(1..6))]
+    index: Option<u8>,
     /// Output in binary instead of hex
     #[arg(short = 'B', long, group = "output")]
     binary: bool,
@@ -53,18 +61,41 @@ struct Args {
     /// Verbose output (only works with --query)
     #[arg(short, long)]
     verbose: bool,
+    /// Output as JSON
+    #[arg(long, conflicts_with_all = ["binary", "decimal", "script"])]
+    json: bool,
+    /// Read devices and registers from snapshot FILE instead of the live system
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
     /// One or more registers to read in format offset or name[.field]
     regs: Vec<String>,
 }
 
-fn dump_value(value: u32, args: &Args) {
-    if args.binary {
-        println!("{value:#b}");
-    } else if args.decimal {
-        println!("{value}");
-    } else {
-        println!("{value:#x}");
+/// Value read for a register or field given on the command line.
+#[derive(Serialize)]
+struct Value<'a> {
+    name: &'a str,
+    value: u32,
+}
+
+fn dump_values(values: &[Value], args: &Args) -> io::Result<()> {
+    if args.json {
+        serde_json::to_writer_pretty(io::stdout(), values)?;
+        println!();
+        return Ok(());
+    }
+
+    for Value { value, .. } in values {
+        if args.binary {
+            println!("{value:#b}");
+        } else if args.decimal {
+            println!("{value}");
+        } else {
+            println!("{value:#x}");
+        }
     }
+
+    Ok(())
 }
 
 fn query_register(registers: &[Register], args: &Args) -> io::Result<()> {
@@ -75,6 +106,19 @@ fn query_register(registers: &[Register], args: &Args) -> io::Result<()> {
         range: Option<RangeInclusive<u8>>,
     }
 
+    #[derive(Serialize)]
+    struct Record<'a> {
+        domain: u8,
+        route: u64,
+        adapter: Option<u8>,
+        index: Option<u8>,
+        name: &'a str,
+        field: Option<&'a str>,
+        offset: Option<u16>,
+        start_bit: Option<u8>,
+        end_bit: Option<u8>,
+    }
+
     let mut names = Vec::new();
 
     if args.regs.is_empty() {
@@ -133,6 +177,26 @@ fn query_register(registers: &[Register], args: &Args) -> io::Result<()> {
         }
     }
 
+    if args.json {
+        let records: Vec<_> = names
+            .iter()
+            .map(|n| Record {
+                domain: args.domain,
+                route: args.route,
+                adapter: args.adapter,
+                index: args.index,
+                name: &n.name,
+                field: n.field.as_deref(),
+                offset: n.offset,
+                start_bit: n.range.as_ref().map(|r| *r.start()),
+                end_bit: n.range.as_ref().map(|r| *r.end()),
+            })
+            .collect();
+        serde_json::to_writer_pretty(io::stdout(), &records)?;
+        println!();
+        return Ok(());
+    }
+
     if args.script {
         let mut writer = Writer::from_writer(io::stdout());
         let mut headers = vec!["domain", "route", "adapter", "index", "name", "field"];
@@ -155,8 +219,11 @@ fn query_register(registers: &[Register], args: &Args) -> io::Result<()> {
             } else {
                 record.push(String::new());
             }
-            // TODO: Add index with retimer support.
-            record.push(String::new());
+            if let Some(index) = &args.index {
+                record.push(format!("{index}"));
+            } else {
+                record.push(String::new());
+            }
             record.push(name.name.clone());
 
             if let Some(field) = &name.field {
@@ -209,28 +276,35 @@ fn query_router(device: &Device, args: &Args) -> io::Result<()> {
     Ok(())
 }
 
-fn read_router(device: &mut Device, args: &Args) -> io::Result<()> {
-    for reg in &args.regs {
-        match util::parse_hex::<u16>(reg) {
+fn read_registers<'a>(registers: &[Register], args: &'a Args) -> Vec<Value<'a>> {
+    let mut values = Vec::new();
+
+    for spec in &args.regs {
+        let mut push = |value| values.push(Value { name: spec, value });
+
+        match util::parse_hex::<u16>(spec) {
             Some(offset) => {
-                if let Some(reg) = device.register_by_offset_mut(offset) {
-                    dump_value(reg.value(), args);
+                if let Some(reg) = registers.iter().find(|r| r.offset() == offset) {
+                    push(reg.value());
                 } else {
                     eprintln!("Warning: invalid offset {offset}!");
                 }
             }
             None => {
-                let name: Vec<_> = reg.split('.').collect();
+                let name: Vec<_> = spec.split('.').collect();
 
-                if let Some(reg) = device.register_by_name_mut(name[0]) {
+                if let Some(reg) = registers.iter().find(|r| {
+                    r.name()
+                        .is_some_and(|n| n.to_lowercase() == name[0].to_lowercase())
+                }) {
                     if name.len() > 1 {
                         if reg.has_field(name[1]) {
-                            dump_value(reg.field(name[1]), args);
+                            push(reg.field(name[1]));
                         } else {
                             eprintln!("Warning: field name {} not found!", name[1]);
                         }
                     } else {
-                        dump_value(reg.value(), args);
+                        push(reg.value());
                     }
                 } else {
                     eprintln!("Warning: register name {} not found!", name[0]);
@@ -239,6 +313,13 @@ fn read_router(device: &mut Device, args: &Args) -> io::Result<()> {
         }
     }
 
+    values
+}
+
+fn read_router(device: &Device, args: &Args) -> io::Result<()> {
+    if let Some(registers) = device.registers() {
+        dump_values(&read_registers(registers, args), args)?;
+    }
     Ok(())
 }
 
@@ -252,6 +333,9 @@ fn query_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()
         } else if args.counters {
             adapter.read_counters()?;
             adapter.counter_registers()
+        } else if args.sideband {
+            adapter.read_sideband()?;
+            adapter.sideband_registers()
         } else {
             adapter.registers()
         }
@@ -266,14 +350,26 @@ fn read_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()>
     device.read_adapters()?;
 
     if let Some(adapter) = device.adapter_mut(adapter) {
+        if args.sideband {
+            adapter.read_sideband()?;
+            if let Some(registers) = adapter.sideband_registers() {
+                dump_values(&read_registers(registers, args), args)?;
+            }
+            return Ok(());
+        }
+
         if args.path {
             adapter.read_paths()?;
         } else if args.counters {
             adapter.read_counters()?;
         }
 
-        for reg in &args.regs {
-            match util::parse_hex::<u16>(reg) {
+        let mut values = Vec::new();
+
+        for spec in &args.regs {
+            let mut push = |value| values.push(Value { name: spec, value });
+
+            match util::parse_hex::<u16>(spec) {
                 Some(offset) => {
                     let reg = if args.path {
                         adapter.path_register_by_offset_mut(offset)
@@ -284,7 +380,7 @@ fn read_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()>
                     };
 
                     if let Some(reg) = reg {
-                        dump_value(reg.value(), args);
+                        push(reg.value());
                     } else {
                         eprintln!("Warning: invalid offset {offset}!");
                     }
@@ -294,17 +390,17 @@ fn read_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()>
                     if args.path || args.counters {
                         eprintln!("Warning: path and counters registers do not have names!");
                     } else {
-                        let name: Vec<_> = reg.split('.').collect();
+                        let name: Vec<_> = spec.split('.').collect();
 
                         if let Some(reg) = adapter.register_by_name_mut(name[0]) {
                             if name.len() > 1 {
                                 if reg.has_field(name[1]) {
-                                    dump_value(reg.field(name[1]), args);
+                                    push(reg.field(name[1]));
                                 } else {
                                     eprintln!("Warning: field name {} not found!", name[1]);
                                 }
                             } else {
-                                dump_value(reg.value(), args);
+                                push(reg.value());
                             }
                         } else {
                             eprintln!("Warning: register name {} not found!", name[0]);
@@ -313,6 +409,8 @@ fn read_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()>
                 }
             }
         }
+
+        dump_values(&values, args)?;
     } else {
         eprintln!("Error: adapter {adapter} not found!");
         process::exit(1);
@@ -321,7 +419,39 @@ fn read_adapter(device: &mut Device, adapter: u8, args: &Args) -> io::Result<()>
     Ok(())
 }
 
+fn read_retimer(args: &Args) -> io::Result<()> {
+    let address = Address::Retimer {
+        domain: args.domain,
+        route: args.route,
+        adapter: args.adapter.unwrap(),
+        index: args.index.unwrap(),
+    };
+    let mut device = match tbtools::find_device(&address)? {
+        Some(device) => device,
+        None => {
+            eprintln!("Error: No such retimer found!");
+            process::exit(1);
+        }
+    };
+
+    device.read_sideband()?;
+
+    if let Some(registers) = device.sideband_registers() {
+        if args.query {
+            query_register(registers, args)?;
+        } else {
+            dump_values(&read_registers(registers, args), args)?;
+        }
+    }
+
+    Ok(())
+}
+
 fn read(args: &Args) -> io::Result<()> {
+    if args.index.is_some() {
+        return read_retimer(args);
+    }
+
     let address = Address::Router {
         domain: args.domain,
         route: args.route,
@@ -342,10 +472,13 @@ fn read(args: &Args) -> io::Result<()> {
         } else {
             read_adapter(&mut device, adapter, args)?
         }
+    } else if args.sideband {
+        eprintln!("Error: Only USB4 ports and retimers have sideband registers!");
+        process::exit(1);
     } else if args.query {
         query_router(&device, args)?;
     } else {
-        read_router(&mut device, args)?;
+        read_router(&device, args)?;
     }
 
     Ok(())
@@ -354,14 +487,21 @@ fn read(args: &Args) -> io::Result<()> {
 fn main() {
     let args = Args::parse();
 
-    if !Uid::current().is_root() {
-        eprintln!("Error: debugfs access requires root permissions!");
-        process::exit(1);
-    }
+    if let Some(path) = &args.snapshot {
+        if let Err(err) = snapshot::replay_file(path) {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    } else {
+        if !Uid::current().is_root() {
+            eprintln!("Error: debugfs access requires root permissions!");
+            process::exit(1);
+        }
 
-    if let Err(err) = debugfs::mount() {
-        eprintln!("Error: failed to mount debugfs: {err}");
-        process::exit(1);
+        if let Err(err) = debugfs::mount() {
+            eprintln!("Error: failed to mount debugfs: {err}");
+            process::exit(1);
+        }
     }
 
     if let Err(err) = read(&args) {
@@ -7,8 +7,17 @@ use ansi_term::Colour::{Cyan, Green, Yellow};
 use ansi_term::Style;
 use clap::Parser;
 use csv::Writer;
-use std::io::{self, IsTerminal};
-use tbtools::{self, Device, Kind, SecurityLevel};
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process,
+};
+use tbtools::{
+    self, Device, Kind, SecurityLevel,
+    graph::{Format, Graph},
+    snapshot,
+    topology::Topology,
+};
 
 #[derive(Parser, Debug)]
 #[command(version)]
@@ -23,9 +32,21 @@ struct Args {
     /// List devices in tree format
     #[arg(short, long, group = "output")]
     tree: bool,
+    /// Output topology as Graphviz DOT graph
+    #[arg(long, group = "output")]
+    dot: bool,
+    /// Output topology as Mermaid flowchart
+    #[arg(long, group = "output")]
+    mermaid: bool,
+    /// Output devices as JSON
+    #[arg(long, group = "output")]
+    json: bool,
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
+    /// Read devices from snapshot FILE instead of the live system
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
 }
 
 fn indent(args: &Args, device: &Device) -> String {
@@ -380,9 +401,35 @@ fn print_service(args: &Args, mut record: Option<&mut Vec<String>>, svc: &Device
 fn main() -> io::Result<()> {
     let args = Args::parse();
 
-    let devices = tbtools::find_devices(None)?;
+    if let Some(path) = &args.snapshot
+        && let Err(err) = snapshot::replay_file(path)
+    {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+
+    let topology = Topology::read()?;
+
+    if args.dot || args.mermaid {
+        let mut graph = Graph::new();
+        graph.add_topology(&topology);
+        let format = if args.dot {
+            Format::Dot
+        } else {
+            Format::Mermaid
+        };
+        print!("{}", graph.render(format));
+        return Ok(());
+    }
+
+    // In tree format children are listed right after their parent.
+    let devices = if args.tree {
+        topology.walk().into_iter().map(|(_, d)| d).collect()
+    } else {
+        topology.devices().iter().collect::<Vec<_>>()
+    };
     let devices: Vec<_> = devices
-        .iter()
+        .into_iter()
         .filter(|d| match d.kind() {
             Kind::Router => true,
             Kind::Retimer | Kind::Domain | Kind::Xdomain | Kind::Service => args.all,
@@ -390,6 +437,12 @@ fn main() -> io::Result<()> {
         })
         .collect();
 
+    if args.json {
+        serde_json::to_writer_pretty(io::stdout(), &devices)?;
+        println!();
+        return Ok(());
+    }
+
     if !args.script && devices.is_empty() {
         println!("No Thunderbolt/USB4 devices found");
         return Ok(());
@@ -27,6 +27,7 @@ use tbtools::{
     debugfs::{Adapter, BitField, BitFields, Name, PathEntry, Register, Type},
     drom::{DromEntry, RankType, SingleDataPathPreference, TmuMode, TmuRate},
     monitor::{self, ChangeEvent},
+    snapshot,
     trace::{self, Entry},
     util, {self, ConfigSpace},
 };
@@ -56,6 +57,7 @@ const DIALOG_EVENTS: &str = "dialog.events";
 const VIEW_ADAPTERS: &str = "view.adapters";
 const VIEW_PATHS: &str = "view.paths";
 const VIEW_REGISTERS: &str = "view.registers";
+const VIEW_REGISTERS_RETIMERS: &str = "view.registers.retimers";
 const VIEW_REGISTERS_HEX_EDIT: &str = "view.registers.hex_edit";
 const VIEW_REGISTERS_BIN_EDIT: &str = "view.registers.bin_edit";
 const VIEW_REGISTERS_FIELDS: &str = "view.registers.fields";
@@ -662,6 +664,7 @@ fn list_header(space: &ConfigSpace) -> String {
         }
         ConfigSpace::Path => String::from("Offset Relative HopID Value    Name"),
         ConfigSpace::Counters => String::from("Offset Relative CounterID Value"),
+        ConfigSpace::Sideband => String::from("Offset Size Value    Name"),
     }
 }
 
@@ -690,6 +693,11 @@ fn list_entry(space: &ConfigSpace, reg: &Register) -> SpannedString<Style> {
         ConfigSpace::Counters => {
             format!("{offset:<6} {relative_offset:<8} {cap_id:<9} {value}",)
         }
+        ConfigSpace::Sideband => {
+            let size = reg.data().map_or(0, |d| d.len());
+            let offset = format!("{:02x}", reg.offset());
+            format!("{offset:<6} {size:<4} {value}{name}")
+        }
     };
 
     if reg.is_changed() {
@@ -713,8 +721,16 @@ fn selected_adapter<'a>(siv: &mut Cursive, device: &'a mut Device) -> &'a mut Ad
     device.adapter_mut(*adapter).unwrap()
 }
 
+// Returns the retimer whose sideband registers are accessed instead of the USB4 port ones.
+fn selected_retimer(retimers: &mut SelectView<Option<Device>>) -> Option<&mut Device> {
+    let index = retimers.selected_id()?;
+    retimers.get_item_mut(index)?.1.as_mut()
+}
+
 fn read_registers(siv: &mut Cursive) {
     let devices: &mut SelectView<Device> = &mut siv.find_name(DEVICES).unwrap();
+    let retimers: &mut SelectView<Option<Device>> =
+        &mut siv.find_name(VIEW_REGISTERS_RETIMERS).unwrap();
 
     if let Some(index) = devices.selected_id() {
         let device = devices.get_item_mut(index).unwrap().1;
@@ -780,6 +796,34 @@ fn read_registers(siv: &mut Cursive) {
 
                 adapter.counter_registers()
             }
+
+            ConfigSpace::Sideband => {
+                if let Some(retimer) = selected_retimer(retimers) {
+                    if let Err(err) = retimer.read_sideband() {
+                        siv.add_layer(ThemedView::new(
+                            theme::dialog(),
+                            Layer::new(Dialog::info(format!(
+                                "Failed to read retimer sideband registers: {err}"
+                            ))),
+                        ));
+                        return;
+                    }
+
+                    retimer.sideband_registers()
+                } else {
+                    if let Err(err) = adapter.read_sideband() {
+                        siv.add_layer(ThemedView::new(
+                            theme::dialog(),
+                            Layer::new(Dialog::info(format!(
+                                "Failed to read port sideband registers: {err}"
+                            ))),
+                        ));
+                        return;
+                    }
+
+                    adapter.sideband_registers()
+                }
+            }
         };
 
         let headers: &mut TextView = &mut siv.find_name("headers").unwrap();
@@ -860,8 +904,11 @@ fn write_changed(siv: &mut Cursive) -> bool {
     let devices: &mut SelectView<Device> = &mut siv.find_name(DEVICES).unwrap();
     let index = devices.selected_id().unwrap();
     let device = devices.get_item_mut(index).unwrap().1;
+    let retimers: &mut SelectView<Option<Device>> =
+        &mut siv.find_name(VIEW_REGISTERS_RETIMERS).unwrap();
+    let space = selected_space(siv);
 
-    if selected_space(siv) == ConfigSpace::Router {
+    if space == ConfigSpace::Router {
         if let Err(err) = device.write_changed() {
             siv.add_layer(ThemedView::new(
                 theme::dialog(),
@@ -869,6 +916,18 @@ fn write_changed(siv: &mut Cursive) -> bool {
             ));
             return false;
         }
+    } else if space == ConfigSpace::Sideband
+        && let Some(retimer) = selected_retimer(retimers)
+    {
+        if let Err(err) = retimer.write_changed() {
+            siv.add_layer(ThemedView::new(
+                theme::dialog(),
+                Layer::new(Dialog::info(format!(
+                    "Failed to write retimer registers: {err}",
+                ))),
+            ));
+            return false;
+        }
     } else {
         let adapter = selected_adapter(siv, device);
 
@@ -1011,6 +1070,8 @@ fn update_register(siv: &mut Cursive) {
         let devices: &mut SelectView<Device> = &mut siv.find_name(DEVICES).unwrap();
         let index = devices.selected_id().unwrap();
         let device = devices.get_item_mut(index).unwrap().1;
+        let retimers: &mut SelectView<Option<Device>> =
+            &mut siv.find_name(VIEW_REGISTERS_RETIMERS).unwrap();
         let offset = reg.offset();
         let space = selected_space(siv);
 
@@ -1031,6 +1092,15 @@ fn update_register(siv: &mut Cursive) {
                 let adapter = selected_adapter(siv, device);
                 adapter.counter_register_by_offset_mut(offset)
             }
+
+            ConfigSpace::Sideband => {
+                if let Some(retimer) = selected_retimer(retimers) {
+                    retimer.sideband_register_by_offset_mut(offset)
+                } else {
+                    let adapter = selected_adapter(siv, device);
+                    adapter.sideband_register_by_offset_mut(offset)
+                }
+            }
         };
 
         siv.call_on_name(VIEW_REGISTERS_HEX_EDIT, |e: &mut NumberEditView| {
@@ -1241,12 +1311,24 @@ fn build_registers(siv: &mut Cursive) {
                     v.unhide();
                 }
             });
+
+            // Retimers only have the sideband registers.
+            let has_retimers = s
+                .call_on_name(
+                    VIEW_REGISTERS_RETIMERS,
+                    |v: &mut SelectView<Option<Device>>| v.len() > 1,
+                )
+                .unwrap_or(false);
+            s.call_on_name("retimers_visible", |v: &mut HideableView<ListView>| {
+                v.set_visible(*space == ConfigSpace::Sideband && has_retimers);
+            });
         })
         .item("None", ConfigSpace::Unknown)
         .item("Router", ConfigSpace::Router)
         .item("Adapter", ConfigSpace::Adapter)
         .item("Path", ConfigSpace::Path)
-        .item("Counters", ConfigSpace::Counters);
+        .item("Counters", ConfigSpace::Counters)
+        .item("Sideband", ConfigSpace::Sideband);
 
     let mut adapters = SelectView::new()
         .popup()
@@ -1269,6 +1351,24 @@ fn build_registers(siv: &mut Cursive) {
         }
     }
 
+    let mut retimers = SelectView::new()
+        .popup()
+        .on_submit(|s, _| read_registers(s))
+        .item("None", None);
+
+    if let Ok(all_devices) = tbtools::find_devices(None) {
+        for retimer in all_devices.into_iter().filter(|d| {
+            d.is_retimer()
+                && d.domain_index() == device.domain_index()
+                && d.route() == device.route()
+        }) {
+            retimers.add_item(
+                format!("{} / Index {}", retimer.adapter_num(), retimer.index()),
+                Some(retimer),
+            );
+        }
+    }
+
     let headers = TextView::new("").with_name("headers");
 
     let registers = OnEventView::new(
@@ -1310,6 +1410,14 @@ fn build_registers(siv: &mut Cursive) {
                                 .hidden()
                                 .with_name("adapters_visible"),
                             )
+                            .child(
+                                HideableView::new(ListView::new().child(
+                                    "Retimer",
+                                    retimers.with_name(VIEW_REGISTERS_RETIMERS).max_width(25),
+                                ))
+                                .hidden()
+                                .with_name("retimers_visible"),
+                            )
                             .child(DummyView)
                             .child(headers)
                             .child(ScrollView::new(registers).max_height(15)),
@@ -1579,6 +1687,14 @@ fn update_title(siv: &mut Cursive) {
 }
 
 fn trace_supported(siv: &mut Cursive) -> bool {
+    if snapshot::is_active() {
+        siv.add_layer(ThemedView::new(
+            theme::dialog(),
+            Layer::new(Dialog::info("Tracing is not available with a snapshot")),
+        ));
+        return false;
+    }
+
     if let Err(err) = trace::mount() {
         siv.add_layer(ThemedView::new(
             theme::dialog(),
@@ -3177,14 +3293,21 @@ fn start_monitor(siv: &mut Cursive) {
     let sink = siv.cb_sink().clone();
 
     thread::spawn(move || {
-        let mut monitor = monitor::Builder::new()
-            .unwrap()
-            .kind(Kind::Router)
-            .unwrap()
-            .kind(Kind::Domain)
-            .unwrap()
-            .build()
-            .unwrap();
+        // Snapshot does not change so there is nothing to monitor.
+        let monitor = if snapshot::is_active() {
+            None
+        } else {
+            Some(
+                monitor::Builder::new()
+                    .unwrap()
+                    .kind(Kind::Router)
+                    .unwrap()
+                    .kind(Kind::Domain)
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+        };
 
         // Get the initial list of devices
         let mut n = 0;
@@ -3214,6 +3337,10 @@ fn start_monitor(siv: &mut Cursive) {
             .unwrap();
         }
 
+        let Some(mut monitor) = monitor else {
+            return;
+        };
+
         loop {
             match monitor.poll(None) {
                 Err(_) => {
@@ -3,23 +3,42 @@
 // Copyright (C) 2023, Intel Corporation
 // Author: Mika Westerberg <mika.westerberg@linux.intel.com>
 
+use clap::Parser;
 use nix::unistd::Uid;
-use std::process;
-use tbtools::debugfs;
+use std::{path::PathBuf, process};
+use tbtools::{debugfs, snapshot};
 
 mod app;
 mod theme;
 mod views;
 
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Thunderbolt/USB4 live device manager", long_about = None)]
+struct Args {
+    /// Browse devices and registers of snapshot FILE instead of the live system
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
+}
+
 fn main() {
-    if !Uid::current().is_root() {
-        eprintln!("Error: debugfs access requires root permissions");
-        process::exit(1);
-    }
+    let args = Args::parse();
+
+    if let Some(path) = &args.snapshot {
+        if let Err(err) = snapshot::replay_file(path) {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    } else {
+        if !Uid::current().is_root() {
+            eprintln!("Error: debugfs access requires root permissions");
+            process::exit(1);
+        }
 
-    if let Err(err) = debugfs::mount() {
-        eprintln!("Error: failed to mount debugfs: {err}");
-        process::exit(1);
+        if let Err(err) = debugfs::mount() {
+            eprintln!("Error: failed to mount debugfs: {err}");
+            process::exit(1);
+        }
     }
 
     app::run();
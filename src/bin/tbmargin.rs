@@ -9,6 +9,7 @@ use std::process;
 use ansi_term::Colour::{Green, Red};
 use clap::{self, Parser};
 use nix::unistd::Uid;
+use serde::Serialize;
 
 use tbtools::{
     Address, debugfs,
@@ -39,6 +40,23 @@ struct Args {
     /// Show capabilities only, do not run margining
     #[arg(short, long, default_value_t = false)]
     caps: bool,
+    /// Output capabilities and results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Results of a single margining run.
+#[derive(Serialize)]
+struct Run {
+    mode: Mode,
+    margin: Option<Margin>,
+    results: Results,
+}
+
+#[derive(Serialize)]
+struct Output {
+    caps: Caps,
+    runs: Vec<Run>,
 }
 
 fn color_result(res: &ResultValue) -> String {
@@ -229,11 +247,14 @@ fn show_caps(caps: &Caps) {
 
 fn run_margining(args: &Args, margining: &mut Margining) -> Result<()> {
     let caps = margining.caps();
+    let mut runs = Vec::new();
 
-    show_caps(&caps);
+    if !args.json {
+        show_caps(&caps);
+    }
 
     if args.caps {
-        return Ok(());
+        return dump_json(args, caps, runs);
     }
 
     // Try with the hardware mode but if not supported then software.
@@ -255,12 +276,17 @@ fn run_margining(args: &Args, margining: &mut Margining) -> Result<()> {
         vec![Lanes::Lane0, Lanes::Lane1]
     };
 
-    println!();
+    if !args.json {
+        println!();
+    }
 
     for (index, test) in tests.iter().enumerate() {
+        if !args.json {
+            println!("Running {} {test} margining", margining.mode());
+        }
+
         let margins: Vec<Margin> = match test {
             Test::Voltage => {
-                println!("Running {} voltage margining", margining.mode());
                 if caps.independent_voltage_margins == IndependentVoltage::Gen23Either {
                     vec![Margin::Low, Margin::High]
                 } else {
@@ -268,7 +294,6 @@ fn run_margining(args: &Args, margining: &mut Margining) -> Result<()> {
                 }
             }
             Test::Time => {
-                println!("Running {} time margining", margining.mode());
                 if caps
                     .time
                     .is_some_and(|time| time.independent_margins == IndependentTiming::Gen23Either)
@@ -288,18 +313,40 @@ fn run_margining(args: &Args, margining: &mut Margining) -> Result<()> {
             if !margins.is_empty() {
                 for margin in &margins {
                     margining.set_margin(margin);
-                    show_results(lane, test, &margining.mode(), &margining.run()?);
+                    runs.push(Run {
+                        mode: margining.mode(),
+                        margin: Some(margin.clone()),
+                        results: margining.run()?,
+                    });
                 }
             } else {
-                show_results(lane, test, &margining.mode(), &margining.run()?);
+                runs.push(Run {
+                    mode: margining.mode(),
+                    margin: None,
+                    results: margining.run()?,
+                });
+            }
+
+            if !args.json {
+                for run in runs.drain(..) {
+                    show_results(lane, test, &run.mode, &run.results);
+                }
             }
         }
 
-        if index < tests.len() - 1 {
+        if !args.json && index < tests.len() - 1 {
             println!();
         }
     }
 
+    dump_json(args, caps, runs)
+}
+
+fn dump_json(args: &Args, caps: Caps, runs: Vec<Run>) -> Result<()> {
+    if args.json {
+        serde_json::to_writer_pretty(io::stdout(), &Output { caps, runs })?;
+        println!();
+    }
     Ok(())
 }
 
@@ -0,0 +1,218 @@
+// Update NVM firmware of Thunderbolt/USB4 routers and retimers
+//
+// Copyright (C) 2026, Intel Corporation
+
+use clap::Parser;
+use nix::unistd::Uid;
+use std::{
+    fs,
+    io::{self, Error, ErrorKind},
+    path::PathBuf,
+    process,
+    time::Duration,
+};
+
+use tbtools::{
+    Address, Device, debugfs,
+    nvm::{Authenticate, Image, Nvm},
+    util,
+};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Update NVM firmware of Thunderbolt/USB4 routers and retimers", long_about = None)]
+struct Args {
+    /// Domain number
+    #[arg(short, long, default_value_t = 0)]
+    domain: u8,
+    /// Route string of the router
+    #[arg(value_parser = util::parse_route, short, long)]
+    route: u64,
+    /// Lane 0 adapter the retimer is on
+    #[arg(short, long, requires = "index", value_parser = clap::value_parser!(u8).range(1..64))]
+    adapter: Option<u8>,
+    /// Index of the retimer
+    #[arg(short, long, requires = "adapter")]
+    index: Option<u8>,
+    /// Authenticate the image when the device is disconnected
+    #[arg(short = 'D', long, group = "mode", requires = "image")]
+    on_disconnect: bool,
+    /// Validate and flush the image but do not authenticate it
+    #[arg(short = 'F', long, group = "mode", requires = "image")]
+    flush_only: bool,
+    /// Time in seconds to wait for the router to come back after authentication
+    #[arg(short, long, default_value_t = 60)]
+    timeout: u64,
+    /// Write the image even if it does not match the device
+    #[arg(short, long, requires = "image")]
+    force: bool,
+    /// Only show and validate the image, do not write it
+    #[arg(short = 'n', long, requires = "image")]
+    dry_run: bool,
+    /// Clear status of the last authentication
+    #[arg(short, long, conflicts_with = "image")]
+    clear: bool,
+    /// NVM image to write. If not given, shows the current NVM information
+    image: Option<PathBuf>,
+}
+
+fn show(nvm: &Nvm) -> io::Result<()> {
+    let device = nvm.device();
+
+    println!("{}", device.kernel_name());
+    if let Some(version) = device.nvm_version() {
+        println!("  NVM version: {:x}.{:x}", version.major, version.minor);
+    }
+    println!("  NVM size: {} kB", nvm.size()? / 1024);
+    println!("  Last authentication status: {:#x}", nvm.status()?);
+    println!(
+        "  Authenticate on disconnect: {}",
+        if nvm.supports_authenticate_on_disconnect() {
+            "supported"
+        } else {
+            "not supported"
+        }
+    );
+
+    Ok(())
+}
+
+fn show_image(image: &Image, device: &Device) {
+    let version = image.version();
+
+    print!(
+        "{}: image version {:x}.{:x}, device ID {:#06x}",
+        device.kernel_name(),
+        version.major,
+        version.minor,
+        image.device_id()
+    );
+    if let Some(drom) = image.drom() {
+        if let Some(vendor) = drom.vendor_id() {
+            print!(", vendor ID {vendor:#06x}");
+        }
+        if let Some(model) = drom.model_id() {
+            print!(", model ID {model:#06x}");
+        }
+    }
+    println!();
+
+    if let Some(current) = device.nvm_version()
+        && (version.major, version.minor) <= (current.major, current.minor)
+    {
+        eprintln!(
+            "Warning: image is not newer than the current NVM version {:x}.{:x}",
+            current.major, current.minor
+        );
+    }
+}
+
+fn update(args: &Args) -> io::Result<()> {
+    let address = match (args.adapter, args.index) {
+        (Some(adapter), Some(index)) => Address::Retimer {
+            domain: args.domain,
+            route: args.route,
+            adapter,
+            index,
+        },
+        _ => Address::Router {
+            domain: args.domain,
+            route: args.route,
+        },
+    };
+    let mut device = tbtools::find_device(&address)?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such device found"))?;
+
+    if args.clear {
+        return Nvm::new(&device)?.clear_status();
+    }
+
+    let Some(path) = &args.image else {
+        return show(&Nvm::new(&device)?);
+    };
+
+    let mode = if args.on_disconnect {
+        Authenticate::OnDisconnect
+    } else if args.flush_only {
+        Authenticate::FlushOnly
+    } else {
+        Authenticate::Now
+    };
+
+    let image = Image::parse(&fs::read(path)?)?;
+
+    // Router registers allow checking the hardware device ID too.
+    if device.is_router() && Uid::current().is_root() && debugfs::mount().is_ok() {
+        device.read_registers().ok();
+    }
+
+    show_image(&image, &device);
+
+    let valid = image.validate(&device);
+
+    // --force only allows writing a mismatched image, the check itself still fails.
+    if args.dry_run {
+        return match valid {
+            Ok(()) => {
+                println!("{}: image is compatible", device.kernel_name());
+                Ok(())
+            }
+            Err(err) => Err(Error::new(
+                err.kind(),
+                format!("image is NOT compatible: {err}"),
+            )),
+        };
+    }
+
+    if let Err(err) = valid {
+        if !args.force {
+            return Err(err);
+        }
+        eprintln!("Warning: {err}");
+    }
+
+    // NVM support is only needed for writing so images can be checked against any device.
+    let mut nvm = Nvm::new(&device)?;
+
+    println!(
+        "{}: writing {} bytes from {}",
+        device.kernel_name(),
+        image.bytes().len(),
+        path.display()
+    );
+    nvm.write(image.bytes())?;
+
+    println!("{}: {mode}", device.kernel_name());
+    nvm.authenticate(mode, Duration::from_secs(args.timeout))?;
+
+    match mode {
+        Authenticate::Now => {
+            print!("{}: done", device.kernel_name());
+            if let Some(version) = nvm.device().nvm_version() {
+                print!(", NVM version {:x}.{:x}", version.major, version.minor);
+            }
+            println!();
+        }
+        Authenticate::OnDisconnect => println!(
+            "{}: image will be authenticated when the device is disconnected",
+            device.kernel_name()
+        ),
+        Authenticate::FlushOnly => println!("{}: image flushed", device.kernel_name()),
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if ((args.image.is_some() && !args.dry_run) || args.clear) && !Uid::current().is_root() {
+        eprintln!("Error: NVM update requires root permissions!");
+        process::exit(1);
+    }
+
+    if let Err(err) = update(&args) {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
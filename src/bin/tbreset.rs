@@ -0,0 +1,84 @@
+// Trigger Downstream Port Reset on a USB4 port
+//
+// Copyright (C) 2026, Intel Corporation
+
+use clap::Parser;
+use nix::unistd::Uid;
+use std::{
+    io::{self, Error, ErrorKind},
+    process,
+    time::Duration,
+};
+
+use tbtools::{Address, debugfs, util};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Trigger Downstream Port Reset on a USB4 port", long_about = None)]
+struct Args {
+    /// Domain number
+    #[arg(short, long, default_value_t = 0)]
+    domain: u8,
+    /// Route string of the router
+    #[arg(value_parser = util::parse_route, short, long)]
+    route: u64,
+    /// Lane 0 adapter of the port
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..64))]
+    adapter: u8,
+    /// Time in seconds to wait for the connected router to come back
+    #[arg(short, long, default_value_t = 10)]
+    timeout: u64,
+}
+
+fn reset(args: &Args) -> io::Result<()> {
+    let address = Address::Router {
+        domain: args.domain,
+        route: args.route,
+    };
+    let mut device = tbtools::find_device(&address)?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such device found"))?;
+
+    device.read_adapters()?;
+
+    let adapter = device
+        .adapter_mut(args.adapter)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such adapter found"))?;
+    let reset = adapter.downstream_port_reset(Duration::from_secs(args.timeout))?;
+
+    print!(
+        "Domain {} Route {:x} Adapter {}: reset done",
+        args.domain, args.route, args.adapter
+    );
+    match (reset.link_down(), reset.enumerated()) {
+        (Some(link_down), Some(enumerated)) => println!(
+            ", router removed in {} ms, enumerated in {} ms",
+            link_down.as_millis(),
+            enumerated.as_millis()
+        ),
+        _ => println!(", nothing connected"),
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if !Uid::current().is_root() {
+        eprintln!("Error: debugfs access requires root permissions!");
+        process::exit(1);
+    }
+
+    if let Err(err) = debugfs::mount() {
+        eprintln!("Error: failed to mount debugfs: {err}");
+        process::exit(1);
+    }
+
+    if let Err(err) = reset(&args) {
+        eprintln!("Error: {err}");
+        if err.kind() == ErrorKind::PermissionDenied {
+            eprintln!("You may need to set CONFIG_USB4_DEBUGFS_WRITE=y in your kernel .config.");
+        }
+        process::exit(1);
+    }
+}
@@ -0,0 +1,275 @@
+// Run USB4 router operations
+//
+// Copyright (C) 2026, Intel Corporation
+
+use clap::{Parser, Subcommand, builder::RangedU64ValueParser};
+use nix::unistd::Uid;
+use num_traits::Num;
+use std::{
+    fs,
+    io::{self, Error, ErrorKind, IsTerminal},
+    path::PathBuf,
+    process,
+    str::FromStr,
+    time::Duration,
+};
+
+use tbtools::{
+    Address, Device, debugfs,
+    nvm::Nvm,
+    router_op::{self, Opcode, Response, RouterOperation},
+    util,
+};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Run USB4 router operations", long_about = None)]
+struct Args {
+    /// Domain number
+    #[arg(short, long, default_value_t = 0)]
+    domain: u8,
+    /// Route string of the router
+    #[arg(value_parser = util::parse_route, short, long)]
+    route: u64,
+    /// Time in milliseconds to wait for the operation to complete
+    #[arg(short, long)]
+    timeout: Option<u64>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Reads router NVM
+    NvmRead {
+        /// Byte address in the NVM
+        #[arg(short = 'A', long, value_parser = parse_number, default_value = "0")]
+        address: u32,
+        /// Number of bytes to read
+        #[arg(short = 'N', long, default_value_t = 64)]
+        len: usize,
+    },
+    /// Reads the whole active router NVM into a file
+    NvmDump {
+        /// Number of bytes to read. Defaults to the size of the active NVM
+        #[arg(short = 'N', long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        len: Option<usize>,
+        /// File where the NVM is written
+        output: PathBuf,
+    },
+    /// Shows Intel router NVM image version
+    NvmVersion,
+    /// Shows router NVM sector size
+    NvmSectorSize,
+    /// Reads router DROM
+    DromRead {
+        /// Byte address in the DROM
+        #[arg(short = 'A', long, value_parser = parse_number, default_value = "0")]
+        address: u32,
+        /// Number of bytes to read
+        #[arg(short = 'N', long, default_value_t = 64)]
+        len: usize,
+    },
+    /// Shows PCIe downstream port mapping
+    PcieDownstreamMapping,
+    /// Queries DisplayPort resource availability of a DP IN adapter
+    QueryDpResource {
+        /// DP IN adapter number
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..64))]
+        adapter: u8,
+    },
+    /// Shows router capabilities
+    Capabilities,
+    /// Runs any router operation and shows the raw results
+    Run {
+        /// Operation opcode
+        #[arg(value_parser = parse_hex::<u16>)]
+        opcode: u16,
+        /// Input metadata
+        #[arg(short, long, value_parser = parse_hex::<u32>)]
+        metadata: Option<u32>,
+        /// Input data double words
+        #[arg(value_parser = parse_hex::<u32>)]
+        data: Vec<u32>,
+    },
+}
+
+fn parse_hex<T: Num + FromStr>(s: &str) -> Result<T, String> {
+    util::parse_hex(s).ok_or_else(|| String::from("Invalid hex number"))
+}
+
+fn parse_number(s: &str) -> Result<u32, String> {
+    util::parse_number(s).ok_or_else(|| String::from("Invalid number"))
+}
+
+/// Offset of the NVM version in Intel router NVM images.
+const INTEL_NVM_VERSION: u32 = 0x08;
+
+fn dump_bytes(bytes: &[u8], address: u32) {
+    for (i, chunk) in bytes.chunks(8).enumerate() {
+        print!("0x{:06x}", address as usize + i * 8);
+        for byte in chunk {
+            print!(" 0x{byte:02x}");
+        }
+        println!();
+    }
+}
+
+fn format_rid(rid: u16) -> String {
+    format!("{:02x}:{:02x}.{:x}", rid >> 8, (rid >> 3) & 0x1f, rid & 0x7)
+}
+
+fn dump_response(opcode: Opcode, response: &Response) {
+    println!("Operation: {opcode}");
+    println!("Status: {:#x}", response.status());
+    println!("Metadata: 0x{:08x}", response.metadata());
+    println!("Data:");
+    for (i, dword) in response.data().iter().enumerate() {
+        println!("  0x{i:02x} 0x{dword:08x}");
+    }
+}
+
+fn nvm_dump(
+    op: &RouterOperation,
+    device: &Device,
+    len: Option<usize>,
+    output: &PathBuf,
+) -> io::Result<()> {
+    let len = match len {
+        Some(len) => len,
+        None => Nvm::new(device)
+            .ok()
+            .and_then(|nvm| nvm.active_size())
+            .filter(|size| *size > 0)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    "size of the active NVM not known, pass it with --len",
+                )
+            })? as usize,
+    };
+    let terminal = io::stderr().is_terminal();
+
+    let bytes = op.nvm_read_all(len, |read| {
+        if terminal {
+            eprint!("\rReading NVM: {read}/{len} bytes ({}%)", read * 100 / len);
+        }
+    })?;
+    if terminal {
+        eprintln!();
+    }
+
+    fs::write(output, &bytes)?;
+    println!("Wrote {} bytes to {}", bytes.len(), output.display());
+
+    Ok(())
+}
+
+fn run(op: &RouterOperation, device: &Device, command: &Commands) -> io::Result<()> {
+    match command {
+        Commands::NvmRead { address, len } => dump_bytes(&op.nvm_read(*address, *len)?, *address),
+
+        Commands::NvmDump { len, output } => nvm_dump(op, device, *len, output)?,
+
+        Commands::NvmVersion => {
+            let bytes = op.nvm_read(INTEL_NVM_VERSION, 4)?;
+            println!("NVM version: {:x}.{:x}", bytes[2], bytes[1]);
+        }
+
+        Commands::NvmSectorSize => println!("NVM sector size: {}", op.nvm_sector_size()?),
+
+        Commands::DromRead { address, len } => dump_bytes(&op.drom_read(*address, *len)?, *address),
+
+        Commands::PcieDownstreamMapping => {
+            for entry in op.pcie_downstream_mapping()? {
+                let kind = match entry.adapter() {
+                    Some(adapter) => format!("Tunneled {adapter}"),
+                    None => String::from("Native"),
+                };
+                println!(
+                    "{}: {kind} FPB: {} Non-FPB: {}",
+                    entry.index(),
+                    format_rid(entry.fpb_rid()),
+                    format_rid(entry.non_fpb_rid()),
+                );
+            }
+        }
+
+        Commands::QueryDpResource { adapter } => {
+            let available = op.query_dp_resource(*adapter)?;
+            println!(
+                "DP IN adapter {adapter}: {}",
+                if available {
+                    "available"
+                } else {
+                    "not available"
+                }
+            );
+        }
+
+        Commands::Capabilities => dump_response(Opcode::GetCapabilities, &op.capabilities()?),
+
+        Commands::Run {
+            opcode,
+            metadata,
+            data,
+        } => {
+            let opcode = Opcode::from(*opcode);
+            dump_response(opcode, &op.run(opcode, *metadata, data)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if !Uid::current().is_root() {
+        eprintln!("Error: debugfs access requires root permissions!");
+        process::exit(1);
+    }
+
+    if let Err(err) = debugfs::mount() {
+        eprintln!("Error: failed to mount debugfs: {err}");
+        process::exit(1);
+    }
+
+    let address = Address::Router {
+        domain: args.domain,
+        route: args.route,
+    };
+    let device = match tbtools::find_device(&address) {
+        Ok(Some(device)) => device,
+        Ok(None) => {
+            eprintln!("Error: No such device found!");
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    };
+
+    if !device.registers_writable() {
+        eprintln!(
+            "Error: Device registers are not writeable! You may need to set
+CONFIG_USB4_DEBUGFS_WRITE=y in your kernel .config."
+        );
+        process::exit(1);
+    }
+
+    let mut op = RouterOperation::new(&device).unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    });
+    op.set_timeout(
+        args.timeout
+            .map_or(router_op::DEFAULT_TIMEOUT, Duration::from_millis),
+    );
+
+    if let Err(err) = run(&op, &device, &args.command) {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
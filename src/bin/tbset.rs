@@ -34,6 +34,12 @@ struct Args {
     /// Select counters config space of an adapter
     #[arg(short, long)]
     counters: bool,
+    /// Select sideband registers of a USB4 port or retimer
+    #[arg(short, long)]
+    sideband: bool,
+    /// Retimer index if accessing retimer sideband registers
+    #[arg(short, long, requires_all = ["adapter", "sideband"], value_parser = clap::value_parser!(u8).range(1..6))]
+    index: Option<u8>,
     /// One or more values to write in format offset=value or name[.field]=value
     values: Vec<String>,
 }
@@ -73,6 +79,41 @@ fn write_router(device: &mut Device, values: &Vec<(String, u32)>) -> io::Result<
     device.write_changed()
 }
 
+fn write_retimer(device: &mut Device, values: &Vec<(String, u32)>) -> io::Result<()> {
+    device.read_sideband()?;
+
+    for value in values {
+        match util::parse_hex::<u16>(&value.0) {
+            Some(offset) => {
+                if let Some(reg) = device.sideband_register_by_offset_mut(offset) {
+                    reg.set_value(value.1);
+                } else {
+                    eprintln!("Warning: invalid offset {offset}!");
+                }
+            }
+            None => {
+                let name: Vec<_> = value.0.split('.').collect();
+
+                if let Some(reg) = device.sideband_register_by_name_mut(name[0]) {
+                    if name.len() > 1 {
+                        if reg.has_field(name[1]) {
+                            reg.set_field(name[1], value.1);
+                        } else {
+                            eprintln!("Warning: field name {} not found!", name[1]);
+                        }
+                    } else {
+                        reg.set_value(value.1);
+                    }
+                } else {
+                    eprintln!("Warning: register name {} not found!", name[0]);
+                }
+            }
+        }
+    }
+
+    device.write_changed()
+}
+
 fn write_adapter(
     device: &mut Device,
     adapter: u8,
@@ -89,6 +130,8 @@ fn write_adapter(
                 // Adapter registers are already read but read path registers now if user is
                 // writing to them.
                 adapter.read_paths()?;
+            } else if args.sideband {
+                adapter.read_sideband()?;
             }
 
             for value in values {
@@ -96,6 +139,8 @@ fn write_adapter(
                     Some(offset) => {
                         let reg = if args.path {
                             adapter.path_register_by_offset_mut(offset)
+                        } else if args.sideband {
+                            adapter.sideband_register_by_offset_mut(offset)
                         } else {
                             adapter.register_by_offset_mut(offset)
                         };
@@ -113,7 +158,13 @@ fn write_adapter(
                         } else {
                             let name: Vec<_> = value.0.split('.').collect();
 
-                            if let Some(reg) = adapter.register_by_name_mut(name[0]) {
+                            let reg = if args.sideband {
+                                adapter.sideband_register_by_name_mut(name[0])
+                            } else {
+                                adapter.register_by_name_mut(name[0])
+                            };
+
+                            if let Some(reg) = reg {
                                 if name.len() > 1 {
                                     if reg.has_field(name[1]) {
                                         reg.set_field(name[1], value.1);
@@ -182,8 +233,25 @@ CONFIG_USB4_DEBUGFS_WRITE=y in your kernel .config."
     let values: Vec<(String, u32)> = args.values.iter().map(|s| parse_one_value(s)).collect();
 
     if !values.is_empty() {
-        if let Some(adapter) = args.adapter {
+        if let Some(index) = args.index {
+            let address = Address::Retimer {
+                domain: args.domain,
+                route: args.route,
+                adapter: args.adapter.unwrap(),
+                index,
+            };
+            match tbtools::find_device(&address)? {
+                Some(mut retimer) => write_retimer(&mut retimer, &values)?,
+                None => {
+                    eprintln!("Error: No such retimer found!");
+                    process::exit(1);
+                }
+            }
+        } else if let Some(adapter) = args.adapter {
             write_adapter(&mut device, adapter, &values, args)?
+        } else if args.sideband {
+            eprintln!("Error: Only USB4 ports and retimers have sideband registers!");
+            process::exit(1);
         } else {
             write_router(&mut device, &values)?;
         }
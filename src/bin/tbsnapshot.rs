@@ -0,0 +1,54 @@
+// Take snapshot of Thunderbolt/USB4 devices
+//
+// Copyright (C) 2026, Intel Corporation
+
+use clap::Parser;
+use nix::unistd::Uid;
+use std::{io, path::PathBuf, process};
+
+use tbtools::{debugfs, snapshot::Snapshot};
+
+#[derive(Parser, Debug)]
+#[command(version)]
+#[command(about = "Take snapshot of Thunderbolt/USB4 devices", long_about = None)]
+struct Args {
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+    /// File where the snapshot is written
+    file: PathBuf,
+}
+
+fn capture(args: &Args) -> io::Result<()> {
+    let snapshot = Snapshot::capture()?;
+    snapshot.save(&args.file)?;
+
+    if args.verbose {
+        println!(
+            "Captured {} devices to {}",
+            snapshot.len(),
+            args.file.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if !Uid::current().is_root() {
+        eprintln!("Error: debugfs access requires root permissions");
+        process::exit(1);
+    }
+
+    if let Err(err) = debugfs::mount() {
+        eprintln!("Error: failed to mount debugfs: {err}");
+        process::exit(1);
+    }
+
+    if let Err(err) = capture(&args) {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
@@ -4,20 +4,27 @@
 // Author: Mika Westerberg <mika.westerberg@linux.intel.com>
 
 use ansi_term::{
-    Colour::{Cyan, Yellow},
+    Colour::{Cyan, Red, Yellow},
     Style,
 };
 use clap::Parser;
 use nix::unistd::Uid;
 use std::{
-    io::{self, ErrorKind, IsTerminal},
+    io::{self, Error, ErrorKind, IsTerminal},
+    path::PathBuf,
     process,
 };
 
 use tbtools::{
-    Device,
-    debugfs::{self, BitFields},
+    bandwidth::{self, LinkBandwidth},
+    debugfs,
+    graph::{Format, Graph},
+    mapping::Mappings,
+    pathcheck::{self, Issue, Severity},
+    snapshot,
+    topology::Topology,
     tunnel::{Direction, Hop, Path, Tunnel, Type},
+    util,
 };
 
 #[derive(Parser, Debug)]
@@ -27,25 +34,43 @@ struct Args {
     /// Domain number
     #[arg(short, long, default_value_t = 0)]
     domain: u8,
+    /// Show only tunnels going through adapter of the router with this route string
+    #[arg(
+        value_parser = util::parse_route,
+        short,
+        long,
+        requires = "adapter",
+        conflicts_with_all = ["bandwidth", "check"]
+    )]
+    route: Option<u64>,
+    /// Show only tunnels going through this adapter (for example DisplayPort OUT)
+    #[arg(short, long, requires = "route", value_parser = clap::value_parser!(u8).range(1..64))]
+    adapter: Option<u8>,
     /// Verbose output (use multiple times to get more detailed output)
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
-}
-
-fn discover_tunnels<'a>(device: &'a Device, devices: &'a [Device]) -> io::Result<Vec<Tunnel<'a>>> {
-    let mut tunnels = Vec::new();
-
-    if let Some(adapters) = device.adapters() {
-        for adapter in adapters {
-            if adapter.is_enabled()
-                && let Some(mut tuns) = Tunnel::discover(device, adapter, devices)
-            {
-                tunnels.append(&mut tuns);
-            }
-        }
-    }
-
-    Ok(tunnels)
+    /// Read devices and registers from snapshot FILE instead of the live system
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
+    /// Use router operations to map PCIe adapters missing from the DROM. These may interfere
+    /// with the kernel connection manager
+    #[arg(long)]
+    router_ops: bool,
+    /// Output topology and tunnels as Graphviz DOT graph
+    #[arg(long, group = "output")]
+    dot: bool,
+    /// Output topology and tunnels as Mermaid flowchart
+    #[arg(long, group = "output")]
+    mermaid: bool,
+    /// Show bandwidth reserved by the tunnels on each link
+    #[arg(short, long, group = "output")]
+    bandwidth: bool,
+    /// Check path configuration for errors and exit with non-zero status if any are found
+    #[arg(short, long, group = "output")]
+    check: bool,
+    /// Output tunnels with their full hop lists as JSON
+    #[arg(long, group = "output")]
+    json: bool,
 }
 
 fn color_type_name(type_name: &str) -> String {
@@ -65,6 +90,61 @@ fn color_number(num: u32) -> String {
     }
 }
 
+fn dump_issue(issue: &Issue) {
+    let s = issue.to_string();
+    if io::stdout().is_terminal() {
+        match issue.severity() {
+            Severity::Error => println!("{}", Red.paint(s)),
+            Severity::Warning => println!("{}", Yellow.paint(s)),
+        }
+    } else {
+        println!("{s}");
+    }
+}
+
+fn dump_link_bandwidth(link: &LinkBandwidth) {
+    let capacity = |c: Option<u32>| c.map_or(String::from("?"), color_number);
+
+    println!(
+        "Domain {} Route {:x} Adapter {} ⇔ Route {:x}: {}/{} Mb/s (up/down)",
+        link.domain(),
+        link.route(),
+        link.adapter(),
+        link.child_route(),
+        capacity(link.up_capacity()),
+        capacity(link.down_capacity()),
+    );
+
+    for consumer in link.consumers() {
+        print!(
+            "  {} Route {:x} Adapter {}: {}/{} Mb/s",
+            color_type_name(&consumer.kind().to_string()),
+            consumer.route(),
+            consumer.adapter(),
+            color_number(consumer.upstream()),
+            color_number(consumer.downstream()),
+        );
+        if let Some(estimated) = consumer.estimated() {
+            print!(" (estimated {} Mb/s)", color_number(estimated));
+        }
+        println!();
+    }
+
+    print!(
+        "  Total: {}/{} Mb/s",
+        color_number(link.upstream()),
+        color_number(link.downstream())
+    );
+    if link.is_oversubscribed() {
+        if io::stdout().is_terminal() {
+            print!(" {}", Red.bold().paint("oversubscribed"));
+        } else {
+            print!(" oversubscribed");
+        }
+    }
+    println!();
+}
+
 fn dump_hop(index: usize, hop: &Hop, args: &Args) {
     if args.verbose <= 1 {
         return;
@@ -124,37 +204,43 @@ fn dump_path(path: &Path, args: &Args) {
     }
 }
 
-fn usb3_bw_to_mbps(bw: u32, scale: u32) -> u32 {
-    let uframes = (bw as u64 * 512u64) << scale;
-    let bw: u32 = (uframes * 8000 / 1000000).try_into().unwrap();
-    bw
-}
-
-fn dump_usb3_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
+fn dump_usb3_tunnel(tunnel: &Tunnel, mappings: &Mappings, args: &Args) -> io::Result<()> {
     if args.verbose == 0 {
         return Ok(());
     }
 
+    // Everything enumerated behind the USB 3 Up adapter goes through this tunnel.
+    if let Some(port) = mappings.port(tunnel.dst_adapter()) {
+        for device in port.devices() {
+            print!("  USB {}:", device.name());
+            if let Some(vendor) = device.vendor()
+                && let Some(product) = device.product()
+            {
+                print!(" {vendor:04x}:{product:04x}");
+            }
+            if let Some(product) = device.product_name() {
+                print!(" {product}");
+            }
+            println!();
+        }
+    }
+
     if !tunnel.src_device().is_host_router() {
         return Ok(());
     }
 
     let usb3_down = tunnel.src_adapter();
-    if let Some(cs2) = usb3_down.register_by_name("ADP_USB3_GX_CS_2")
-        && let Some(cs3) = usb3_down.register_by_name("ADP_USB3_GX_CS_3")
+    if let Some(allocated_up_bw) = usb3_down.usb3_allocated_up_bw()
+        && let Some(allocated_down_bw) = usb3_down.usb3_allocated_down_bw()
     {
-        let scale = cs3.field("Scale");
-        let allocated_up_bw = color_number(usb3_bw_to_mbps(
-            cs2.field("Allocated Upstream Bandwidth"),
-            scale,
-        ));
-        let allocated_down_bw = color_number(usb3_bw_to_mbps(
-            cs2.field("Allocated Downstream Bandwidth"),
-            scale,
-        ));
-
-        println!("  Allocated Upstream Bandwidth: {allocated_up_bw} Mb/s");
-        println!("  Allocated Downstream Bandwidth: {allocated_down_bw} Mb/s");
+        println!(
+            "  Allocated Upstream Bandwidth: {} Mb/s",
+            color_number(allocated_up_bw)
+        );
+        println!(
+            "  Allocated Downstream Bandwidth: {} Mb/s",
+            color_number(allocated_down_bw)
+        );
     } else {
         eprintln!(
             "Warning: invalid USB 3 Down adapter: {}",
@@ -165,60 +251,79 @@ fn dump_usb3_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
     Ok(())
 }
 
-fn dump_dp_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
+fn dump_pcie_tunnel(tunnel: &Tunnel, mappings: &Mappings, args: &Args) {
+    if args.verbose == 0 {
+        return;
+    }
+
+    for (name, adapter) in [
+        ("PCIe Down", tunnel.src_adapter()),
+        ("PCIe Up", tunnel.dst_adapter()),
+    ] {
+        if let Some(bridge) = mappings.bridge(adapter) {
+            println!("  {name}: {}", bridge.description());
+        }
+    }
+}
+
+fn dump_dp_tunnel(tunnel: &Tunnel, mappings: &Mappings, args: &Args) -> io::Result<()> {
     if args.verbose == 0 {
         return Ok(());
     }
 
     let dp_in = tunnel.src_adapter();
-    if let Some(reg) = dp_in.register_by_name("ADP_DP_CS_8")
-        && reg.flag("DPME")
-    {
-        if let Some(cs2) = dp_in.register_by_name("ADP_DP_CS_2")
-            && let Some(dp_status) = dp_in.register_by_name("DP_STATUS")
-        {
-            let granularity = match cs2.field("GR") {
-                0 => 250,
-                1 => 500,
-                2 => 1000,
-                val => {
-                    eprintln!("Warning: unsupported granularity: {val}");
-                    0
-                }
-            };
-            let group = color_number(cs2.field("Group_ID"));
-            println!("  Group ID: {group}");
-            let estimated_bw = color_number(cs2.field("Estimated BW") * granularity);
+    if let Some(connector) = mappings.connector(dp_in) {
+        match connector.aux() {
+            Some(aux) => println!("  Connector: {} (drm_dp_aux{aux})", connector.name()),
+            None => println!("  Connector: {}", connector.name()),
+        }
+    }
+
+    let Some(enabled) = dp_in.dp_bw_mode_enabled() else {
+        eprintln!("Warning: invalid DisplayPort adapter: {}", dp_in.adapter());
+        return Ok(());
+    };
+
+    if enabled {
+        println!("  Bandwidth Allocation Mode: enabled");
+        if let Some(discovery) = dp_in.dp_bw_discovery() {
+            println!("  Discovery: {discovery}");
+        }
+        if let Some(group) = dp_in.dp_group_id() {
+            println!("  Group ID: {}", color_number(group));
+        }
+        if let Some(granularity) = dp_in.dp_granularity() {
+            println!("  Granularity: {} Mb/s", color_number(granularity));
+            let estimated_bw = color_number(dp_in.dp_estimated_bw().unwrap_or_default());
             println!("  Estimated Bandwidth: {estimated_bw} Mb/s");
-            let allocated_bw = color_number(dp_status.field("Allocated BW") * granularity);
+            let allocated_bw = color_number(dp_in.dp_allocated_bw().unwrap_or_default());
             println!("  Allocated Bandwidth: {allocated_bw} Mb/s");
-            let requested_bw = color_number(reg.field("Requested BW") * granularity);
+            let requested_bw = color_number(dp_in.dp_requested_bw().unwrap_or_default());
             println!("  Requested Bandwidth: {requested_bw} Mb/s");
-        } else {
-            eprintln!("Warning: failed to read ADP_DP_CS_2");
-        };
-    } else if let Some(reg) = dp_in.register_by_name("DP_STATUS") {
-        let rate = match reg.field("Link Rate") {
-            0 => 1620,
-            1 => 2700,
-            2 => 5400,
-            3 => 8100,
-            val => {
-                eprintln!("Warning: unsupported rate: {val}");
-                0
+            if dp_in.dp_bw_request_pending() == Some(true) {
+                println!("  Request pending");
             }
-        };
-        let rate = color_number(rate);
-        let lanes = color_number(reg.field("Lane Count"));
-        println!("Rate: {rate} Mb/s * {lanes}");
+        } else {
+            eprintln!("Warning: unsupported granularity");
+        }
     } else {
-        eprintln!("Warning: invalid DisplayPort adapter: {}", dp_in.adapter());
+        if dp_in.dp_bw_mode_supported() == Some(true) {
+            println!("  Bandwidth Allocation Mode: supported");
+        }
+        match dp_in.dp_link_rate() {
+            Some(rate) => {
+                let rate = color_number(rate);
+                let lanes = color_number(dp_in.dp_lane_count().unwrap_or_default());
+                println!("  Rate: {rate} Mb/s * {lanes}");
+            }
+            None => eprintln!("Warning: unsupported rate"),
+        }
     }
 
     Ok(())
 }
 
-fn dump_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
+fn dump_tunnel(tunnel: &Tunnel, mappings: &Mappings, args: &Args) -> io::Result<()> {
     let bold: Option<Style> = if io::stdout().is_terminal() {
         Some(Style::new().bold())
     } else {
@@ -238,7 +343,7 @@ fn dump_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
         _ => "⇔ ",
     };
 
-    println!(
+    print!(
         "Domain {} Route {} Adapter {} {arrow} Domain {} Route {} Adapter {}: {}",
         bold.map_or(src_domain.clone(), |b| b.paint(src_domain).to_string()),
         bold.map_or(src_route.clone(), |b| b.paint(src_route).to_string()),
@@ -248,10 +353,19 @@ fn dump_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
         bold.map_or(dst_adapter.clone(), |b| b.paint(dst_adapter).to_string()),
         color_type_name(&tunnel.kind().to_string()),
     );
+    if !tunnel.is_complete() {
+        if io::stdout().is_terminal() {
+            print!(" {}", Red.bold().paint("incomplete"));
+        } else {
+            print!(" incomplete");
+        }
+    }
+    println!();
 
     match tunnel.kind() {
-        Type::Usb3 => dump_usb3_tunnel(tunnel, args)?,
-        Type::DisplayPort => dump_dp_tunnel(tunnel, args)?,
+        Type::Usb3 | Type::Usb3GenT => dump_usb3_tunnel(tunnel, mappings, args)?,
+        Type::Pcie => dump_pcie_tunnel(tunnel, mappings, args),
+        Type::DisplayPort => dump_dp_tunnel(tunnel, mappings, args)?,
         _ => (),
     }
 
@@ -263,15 +377,17 @@ fn dump_tunnel(tunnel: &Tunnel, args: &Args) -> io::Result<()> {
 }
 
 fn dump(args: &Args) -> io::Result<()> {
-    // Pull in all routers and XDomains in the domain.
-    let mut devices: Vec<_> = tbtools::find_devices(None)?
-        .into_iter()
-        .filter(|d| d.domain_index() == args.domain as u32)
-        .filter(|d| d.is_router() || d.is_xdomain())
-        .collect();
+    // Pull in all routers, XDomains and retimers in the domain.
+    let mut topology = Topology::new(
+        tbtools::find_devices(None)?
+            .into_iter()
+            .filter(|d| d.domain_index() == args.domain as u32)
+            .filter(|d| d.is_router() || d.is_xdomain() || d.is_retimer())
+            .collect(),
+    );
 
     // Read the adapters as well so that we can figure out the starting and ending adapters.
-    for device in &mut devices {
+    for device in topology.devices_mut() {
         if !device.is_router() {
             continue;
         }
@@ -283,14 +399,71 @@ fn dump(args: &Args) -> io::Result<()> {
                 }
             }
         }
+        // DROM is only used to map PCIe and USB 3 adapters so ignore errors.
+        device.read_drom().ok();
     }
 
-    let mut tunnels = Vec::new();
+    let devices = topology.devices();
+    let tunnels = if let Some(route) = args.route
+        && let Some(adapter) = args.adapter
+    {
+        let device = devices
+            .iter()
+            .find(|d| d.is_router() && d.route() == route)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such router"))?;
+        let adapter = device
+            .adapter(adapter)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such adapter"))?;
+        Tunnel::discover_at(device, adapter, devices)
+    } else {
+        Tunnel::discover_all(devices)
+    };
 
-    for device in &devices {
-        if device.is_router() {
-            tunnels.append(&mut discover_tunnels(device, &devices)?);
+    if args.dot || args.mermaid {
+        let mut graph = Graph::new();
+        graph.add_topology(&topology);
+        graph.add_tunnels(&tunnels);
+        let format = if args.dot {
+            Format::Dot
+        } else {
+            Format::Mermaid
+        };
+        print!("{}", graph.render(format));
+        return Ok(());
+    }
+
+    if args.json {
+        serde_json::to_writer_pretty(io::stdout(), &tunnels)?;
+        println!();
+        return Ok(());
+    }
+
+    if args.bandwidth {
+        let links = bandwidth::links(&topology, &tunnels);
+        if links.is_empty() {
+            println!("No links found");
         }
+        for (i, link) in links.iter().enumerate() {
+            dump_link_bandwidth(link);
+            if i < links.len() - 1 {
+                println!();
+            }
+        }
+        return Ok(());
+    }
+
+    if args.check {
+        let issues = pathcheck::check(devices, &tunnels);
+        if issues.is_empty() {
+            println!("No issues found");
+        }
+        for issue in &issues {
+            dump_issue(issue);
+        }
+        if issues.iter().any(|i| i.severity() == Severity::Error) {
+            process::exit(1);
+        }
+        return Ok(());
     }
 
     if tunnels.is_empty() {
@@ -298,8 +471,21 @@ fn dump(args: &Args) -> io::Result<()> {
         return Ok(());
     }
 
+    // These are only available on the live system.
+    let mappings = if args.snapshot.is_none() {
+        // Connectors are only shown for DP tunnels in verbose mode and reading them opens every
+        // DP AUX device.
+        let connectors = args.verbose > 0
+            && tunnels
+                .iter()
+                .any(|t| t.kind() == Type::DisplayPort && t.src_device().is_host_router());
+        Mappings::read(devices, connectors, args.router_ops)?
+    } else {
+        Mappings::default()
+    };
+
     for (i, tunnel) in tunnels.iter().enumerate() {
-        dump_tunnel(tunnel, args)?;
+        dump_tunnel(tunnel, &mappings, args)?;
         if args.verbose > 0 && i < tunnels.len() - 1 {
             println!();
         }
@@ -311,14 +497,21 @@ fn dump(args: &Args) -> io::Result<()> {
 fn main() {
     let args = Args::parse();
 
-    if !Uid::current().is_root() {
-        eprintln!("Error: debugfs access requires root permissions");
-        process::exit(1);
-    }
+    if let Some(path) = &args.snapshot {
+        if let Err(err) = snapshot::replay_file(path) {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    } else {
+        if !Uid::current().is_root() {
+            eprintln!("Error: debugfs access requires root permissions");
+            process::exit(1);
+        }
 
-    if let Err(err) = debugfs::mount() {
-        eprintln!("Error: failed to mount debugfs: {err}");
-        process::exit(1);
+        if let Err(err) = debugfs::mount() {
+            eprintln!("Error: failed to mount debugfs: {err}");
+            process::exit(1);
+        }
     }
 
     if let Err(err) = dump(&args) {
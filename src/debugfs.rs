@@ -11,20 +11,34 @@
 //!
 //! Calling [`Device`]'s [`registers_writable()`](Device::registers_writable()) can be used to determine whether registers can be
 //! written to.
-
-use crate::{device::Device, drom::Drom, genmask_t, usb4, util};
+//!
+//! The actual register accesses go through the current [`Backend`](crate::backend::Backend) which
+//! is [`Debugfs`] unless replaced.
+
+use crate::{
+    Address, Kind,
+    backend::{self, Backend},
+    device::{ConfigSpace, Device},
+    drm,
+    drom::Drom,
+    genmask_t,
+    monitor::{self, Event},
+    pci, snapshot, sysfs, usb, usb4, util,
+};
 use include_dir::{Dir, include_dir};
 use lazy_static::lazy_static;
 use nix::{errno::Errno, mount};
 use num_traits::Num;
 use serde_json::Value;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display},
-    fs::{File, OpenOptions},
-    io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Write},
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Error, ErrorKind, Result, Write},
     ops::RangeInclusive,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
 };
 
 pub(crate) static DATA_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/data");
@@ -50,11 +64,15 @@ lazy_static! {
         .collect();
 }
 
-const DEBUGFS_ROOT: &str = "/sys/kernel/debug/thunderbolt";
-const DEBUGFS_DROM: &str = "drom";
-const DEBUGFS_REGS: &str = "regs";
-const DEBUGFS_PATH: &str = "path";
-const DEBUGFS_COUNTERS: &str = "counters";
+pub(crate) const DEBUGFS_ROOT: &str = "/sys/kernel/debug/thunderbolt";
+pub(crate) const DEBUGFS_DROM: &str = "drom";
+pub(crate) const DEBUGFS_REGS: &str = "regs";
+pub(crate) const DEBUGFS_PATH: &str = "path";
+pub(crate) const DEBUGFS_COUNTERS: &str = "counters";
+pub(crate) const DEBUGFS_SB_REGS: &str = "sb_regs";
+
+// How long Downstream Port Reset is kept asserted. Same as the kernel uses.
+const DPR_HOLD_TIME: Duration = Duration::from_millis(10);
 
 const DEBUGFS_HELP: &str = "Note debugfs may not be mounted. To do that manually
 you can run following command as root:
@@ -63,7 +81,10 @@ you can run following command as root:
 ";
 
 /// Type of an adapter.
-#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Hash, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum Type {
     /// Unknown adapter.
     Unknown,
@@ -358,6 +379,14 @@ impl Metadata {
     ) -> Option<Vec<Self>> {
         Self::lookup_from_json(NAMES.get(name).unwrap(), offset, Some(adapter_type))
     }
+
+    fn with_sideband_offset(offset: u16) -> Option<Vec<Self>> {
+        Self::lookup_from_json(NAMES.get("sideband").unwrap(), offset, None)
+    }
+
+    fn with_dpcd_offset(offset: u16) -> Option<Vec<Self>> {
+        Self::lookup_from_json(NAMES.get("dpcd").unwrap(), offset, None)
+    }
 }
 
 /// [`Register`] or similar with optional name attached.
@@ -448,8 +477,10 @@ pub struct Register {
     cap_id: u16,
     /// Vendor specific capability ID. Only present in router and adapter config space registers.
     vs_cap_id: u16,
-    /// Register value.
+    /// Register value. For sideband registers this holds the first four bytes.
     value: u32,
+    /// Raw little-endian bytes of a sideband register. `None` for config space registers.
+    data: Option<Vec<u8>>,
     /// Is this register changed.
     changed: bool,
     /// Metadata for the register if available.
@@ -457,6 +488,36 @@ pub struct Register {
 }
 
 impl Register {
+    /// Creates a new register. This is mostly useful for implementing a
+    /// [`Backend`](crate::backend::Backend).
+    pub fn new(offset: u16, relative_offset: u16, cap_id: u16, vs_cap_id: u16, value: u32) -> Self {
+        Self {
+            offset,
+            relative_offset,
+            cap_id,
+            vs_cap_id,
+            value,
+            data: None,
+            changed: false,
+            metadata: None,
+        }
+    }
+
+    /// Creates a new sideband register from its raw bytes. Sideband registers can be longer than
+    /// 32-bits so [`value()`](Self::value) only covers the first four bytes of `data`.
+    pub fn with_data(offset: u16, data: Vec<u8>) -> Self {
+        let value = data
+            .iter()
+            .take(4)
+            .enumerate()
+            .fold(0, |value, (i, b)| value | (*b as u32) << (i * 8));
+
+        Self {
+            data: Some(data),
+            ..Self::new(offset, offset, 0, 0, value)
+        }
+    }
+
     fn set_metadata(&mut self, metadata: Vec<Metadata>) {
         let mut matches: Vec<_> = metadata
             .into_iter()
@@ -519,15 +580,28 @@ impl Register {
             value = util::parse_hex::<u32>(values[4])?;
         }
 
-        Some(Self {
-            offset,
-            relative_offset,
-            cap_id,
-            vs_cap_id,
-            value,
-            changed: false,
-            metadata: None,
-        })
+        Some(Self::new(offset, relative_offset, cap_id, vs_cap_id, value))
+    }
+
+    fn parse_sideband(regs: &str) -> Option<Self> {
+        if regs.starts_with('#') {
+            return None;
+        }
+
+        let mut values = regs.split_ascii_whitespace();
+        let offset = util::parse_hex::<u16>(values.next()?)?;
+        let data: Option<Vec<_>> = values.map(util::parse_hex::<u8>).collect();
+
+        Some(Self::with_data(offset, data?))
+    }
+
+    /// Copies the value back to the raw bytes of a sideband register.
+    fn update_data(&mut self) {
+        if let Some(data) = &mut self.data {
+            for (i, b) in data.iter_mut().take(4).enumerate() {
+                *b = (self.value >> (i * 8)) as u8;
+            }
+        }
     }
 
     /// Returns register absolute offset in the config space.
@@ -555,10 +629,16 @@ impl Register {
         self.value
     }
 
+    /// Returns raw bytes of a sideband register or `None` for config space registers.
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
+    }
+
     /// Sets the current register value.
     pub fn set_value(&mut self, value: u32) {
         if self.value != value {
             self.value = value;
+            self.update_data();
             self.changed = true;
         }
     }
@@ -574,6 +654,7 @@ impl Register {
 
             self.value &= !mask;
             self.value |= value << shift;
+            self.update_data();
 
             self.changed = true;
             return;
@@ -611,9 +692,122 @@ impl BitFields<u32> for Register {
     }
 }
 
+/// Change of a single register between two reads of the same config space.
+///
+/// Returned by [`Diff::diff()`].
+#[derive(Clone, Debug)]
+pub struct RegisterChange {
+    before: Option<Register>,
+    after: Option<Register>,
+}
+
+impl RegisterChange {
+    /// Returns absolute offset of the changed register.
+    pub fn offset(&self) -> u16 {
+        self.before
+            .as_ref()
+            .or(self.after.as_ref())
+            .map(|r| r.offset)
+            .unwrap()
+    }
+
+    /// Returns the register before the change or `None` if it did not exist.
+    pub fn before(&self) -> Option<&Register> {
+        self.before.as_ref()
+    }
+
+    /// Returns the register after the change or `None` if it does not exist anymore.
+    pub fn after(&self) -> Option<&Register> {
+        self.after.as_ref()
+    }
+
+    /// Returns the fields that changed along with the values before and after the change.
+    ///
+    /// Empty if the register has no field metadata or it only exists on one side.
+    pub fn fields(&self) -> Vec<(&BitField, u32, u32)> {
+        let (Some(before), Some(after)) = (&self.before, &self.after) else {
+            return Vec::new();
+        };
+
+        after
+            .fields()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|f| (f, before.field_value(f), after.field_value(f)))
+                    .filter(|(_, before, after)| before != after)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Compares two collections of registers.
+///
+/// # Examples
+/// ```no_run
+/// # use std::io;
+/// use tbtools::{Address, debugfs::Diff};
+///
+/// # fn main() -> io::Result<()> {
+/// let address = Address::Router { domain: 0, route: 0 };
+/// let mut device = tbtools::find_device(&address)?.unwrap();
+///
+/// device.read_registers()?;
+/// let before = device.registers().unwrap().clone();
+///
+/// device.read_registers()?;
+/// for change in before.diff(device.registers().unwrap()) {
+///     println!("0x{:04x} changed", change.offset());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait Diff {
+    /// Returns registers that differ between `self` and `other` ordered by offset. Registers are
+    /// matched by their absolute offset.
+    fn diff(&self, other: &Self) -> Vec<RegisterChange>;
+}
+
+impl Diff for [Register] {
+    fn diff(&self, other: &Self) -> Vec<RegisterChange> {
+        let mut changes: BTreeMap<u16, RegisterChange> = BTreeMap::new();
+
+        for reg in self {
+            changes.insert(
+                reg.offset,
+                RegisterChange {
+                    before: Some(reg.clone()),
+                    after: None,
+                },
+            );
+        }
+
+        for reg in other {
+            changes
+                .entry(reg.offset)
+                .or_insert(RegisterChange {
+                    before: None,
+                    after: None,
+                })
+                .after = Some(reg.clone());
+        }
+
+        changes
+            .into_values()
+            .filter(|c| match (&c.before, &c.after) {
+                (Some(before), Some(after)) => {
+                    before.value != after.value || before.data != after.data
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
+
 /// Returns debugfs root as PathBuf
 pub(crate) fn path_buf() -> Result<PathBuf> {
-    let path_buf = PathBuf::from(DEBUGFS_ROOT);
+    let path_buf = sysfs::path_buf(DEBUGFS_ROOT);
 
     if !path_buf.exists() {
         eprintln!("{DEBUGFS_HELP}");
@@ -623,15 +817,14 @@ pub(crate) fn path_buf() -> Result<PathBuf> {
     Ok(path_buf)
 }
 
-fn router_path_buf(router: &Device) -> Result<PathBuf> {
-    let mut path_buf = path_buf()?;
-    path_buf.push(router.kernel_name());
-    path_buf.push(DEBUGFS_REGS);
-    Ok(path_buf)
-}
-
 /// Mounts debugfs if not already mounted. User must be `root`.
+///
+/// Does nothing if an alternate [`root`](crate::sysfs::root()) is used.
 pub fn mount() -> Result<()> {
+    if sysfs::root().is_some() {
+        return Ok(());
+    }
+
     match mount::mount(
         None::<&PathBuf>,
         &PathBuf::from("/sys/kernel/debug"),
@@ -646,51 +839,183 @@ pub fn mount() -> Result<()> {
     }
 }
 
-fn read(path_buf: &PathBuf, offset: Option<u16>, nregs: Option<usize>) -> Result<Vec<Register>> {
-    let file = File::open(path_buf)?;
-    let reader = BufReader::new(file);
-    let offset = offset.unwrap_or(0);
-    let mut regs = Vec::new();
+/// Parses registers from the `debugfs` file format and sorts them by the absolute offset.
+pub(crate) fn parse_registers(contents: &str) -> Vec<Register> {
+    let mut regs: Vec<_> = contents
+        .lines()
+        .filter_map(Register::parse_debugfs)
+        .collect();
 
-    // Initially we just read all the registers as that's pretty much the same anyway.
-    for line in reader.lines() {
-        if let Some(reg) = Register::parse_debugfs(&line?) {
-            regs.push(reg);
+    // Kernel reports them by capability so sort them out here by the actual offset.
+    regs.sort_by_key(|a| a.offset);
+
+    regs
+}
+
+/// Parses sideband registers from the `debugfs` `sb_regs` file format.
+pub(crate) fn parse_sideband_registers(contents: &str) -> Vec<Register> {
+    contents
+        .lines()
+        .filter_map(Register::parse_sideband)
+        .collect()
+}
+
+/// Register access through the kernel `debugfs` interface.
+///
+/// This is the default [`Backend`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Debugfs;
+
+impl Debugfs {
+    fn path_buf(router: &str, adapter: Option<u8>, name: &str) -> Result<PathBuf> {
+        let mut path_buf = path_buf()?;
+        path_buf.push(router);
+        if let Some(adapter) = adapter {
+            path_buf.push(format!("port{adapter}"));
         }
+        path_buf.push(name);
+        Ok(path_buf)
     }
 
-    // Then filter out what was asked.
-    let num = regs.len();
+    fn read(path_buf: &Path) -> Result<Vec<Register>> {
+        Ok(parse_registers(&fs::read_to_string(path_buf)?))
+    }
 
-    let mut regs: Vec<_> = regs
-        .into_iter()
-        .filter(|r| r.offset >= offset)
-        .enumerate()
-        .filter(|(i, _)| *i < nregs.unwrap_or(num))
-        .map(|(_, r)| r)
-        .collect();
+    fn write(path_buf: &Path, regs: &[Register]) -> Result<()> {
+        let file = OpenOptions::new().write(true).open(path_buf)?;
+        let mut writer = BufWriter::new(file);
 
-    // Kernel reports them by capability so sort them out here by the actual offset.
-    regs.sort_by_key(|a| a.offset);
+        for reg in regs {
+            if let Some(data) = &reg.data {
+                // Sideband registers are written byte by byte.
+                write!(&mut writer, "0x{:02x}", reg.offset)?;
+                for b in data {
+                    write!(&mut writer, " 0x{b:02x}")?;
+                }
+                writeln!(&mut writer)?;
+            } else {
+                writeln!(&mut writer, "0x{:04x} 0x{:08x}", reg.offset, reg.value)?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+impl Backend for Debugfs {
+    fn read_registers(&self, router: &str, adapter: Option<u8>) -> Result<Vec<Register>> {
+        Self::read(&Self::path_buf(router, adapter, DEBUGFS_REGS)?)
+    }
+
+    fn read_paths(&self, router: &str, adapter: u8) -> Result<Vec<Register>> {
+        Self::read(&Self::path_buf(router, Some(adapter), DEBUGFS_PATH)?)
+    }
+
+    fn read_counters(&self, router: &str, adapter: u8) -> Result<Vec<Register>> {
+        Self::read(&Self::path_buf(router, Some(adapter), DEBUGFS_COUNTERS)?)
+    }
+
+    fn read_sideband(&self, device: &str, adapter: Option<u8>) -> Result<Vec<Register>> {
+        let contents = fs::read_to_string(Self::path_buf(device, adapter, DEBUGFS_SB_REGS)?)?;
+        Ok(parse_sideband_registers(&contents))
+    }
+
+    fn write_registers(
+        &self,
+        router: &str,
+        adapter: Option<u8>,
+        space: ConfigSpace,
+        regs: &[Register],
+    ) -> Result<()> {
+        let name = match space {
+            ConfigSpace::Router | ConfigSpace::Adapter => DEBUGFS_REGS,
+            ConfigSpace::Path => DEBUGFS_PATH,
+            ConfigSpace::Counters => DEBUGFS_COUNTERS,
+            ConfigSpace::Sideband => DEBUGFS_SB_REGS,
+            ConfigSpace::Unknown => return Err(Error::from(ErrorKind::InvalidInput)),
+        };
+
+        Self::write(&Self::path_buf(router, adapter, name)?, regs)
+    }
+
+    fn clear_counters(&self, router: &str, adapter: u8) -> Result<()> {
+        let path_buf = Self::path_buf(router, Some(adapter), DEBUGFS_COUNTERS)?;
+        let file = OpenOptions::new().write(true).open(path_buf)?;
+        let mut writer = BufWriter::new(file);
+
+        // Empty line clear all counters.
+        writeln!(&mut writer)?;
+
+        writer.flush()
+    }
+
+    fn read_drom(&self, router: &str) -> Result<Vec<u8>> {
+        fs::read(Self::path_buf(router, None, DEBUGFS_DROM)?)
+    }
+
+    fn registers_writable(&self, router: &str) -> bool {
+        if let Ok(path_buf) = Self::path_buf(router, None, DEBUGFS_REGS)
+            && let Ok(file) = File::open(path_buf)
+            && let Ok(metadata) = file.metadata()
+        {
+            return !metadata.permissions().readonly();
+        }
+        false
+    }
+}
+
+/// Writes registers that have been changed through the current backend.
+fn write_changed(
+    router: &str,
+    adapter: Option<u8>,
+    space: ConfigSpace,
+    regs: &[Register],
+) -> Result<()> {
+    let changed: Vec<_> = regs.iter().filter(|r| r.is_changed()).cloned().collect();
 
-    Ok(regs)
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    backend::get().write_registers(router, adapter, space, &changed)
 }
 
-fn write_changed(path_buf: &PathBuf, regs: &[Register]) -> Result<()> {
-    let file = OpenOptions::new().write(true).open(path_buf)?;
-    let mut writer = BufWriter::new(file);
+/// Attaches sideband register metadata to `regs`.
+fn sideband_with_metadata(mut regs: Vec<Register>) -> Vec<Register> {
+    for reg in &mut regs {
+        if let Some(metadata) = Metadata::with_sideband_offset(reg.offset) {
+            reg.set_metadata(metadata);
+        }
+    }
+    regs
+}
 
-    for reg in regs.iter().filter(|r| r.is_changed()) {
-        writeln!(&mut writer, "0x{:04x} 0x{:08x}", reg.offset, reg.value)?;
+/// Attaches USB4 DPCD register metadata to `regs`.
+pub(crate) fn dpcd_with_metadata(mut regs: Vec<Register>) -> Vec<Register> {
+    for reg in &mut regs {
+        if let Some(metadata) = Metadata::with_dpcd_offset(reg.offset) {
+            reg.set_metadata(metadata);
+        }
     }
+    regs
+}
 
-    writer.flush()?;
+fn find_by_name<'a>(regs: &'a [Register], name: &str) -> Option<&'a Register> {
+    regs.iter().find(|r| {
+        r.name()
+            .is_some_and(|n| n.to_lowercase() == name.to_lowercase())
+    })
+}
 
-    Ok(())
+fn find_by_name_mut<'a>(regs: &'a mut [Register], name: &str) -> Option<&'a mut Register> {
+    regs.iter_mut().find(|r| {
+        r.name()
+            .is_some_and(|n| n.to_lowercase() == name.to_lowercase())
+    })
 }
 
 /// Parsed enabled path configuration space entry.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct PathEntry {
     /// Input adapter number.
     in_adapter: u8,
@@ -743,7 +1068,10 @@ impl PathEntry {
 ///
 /// These are all possible states the adapter can be. Not all states apply to all adapter types.
 /// For instance CL states only apply to lane adapters.
-#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Hash, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum State {
     /// Adapter state is not known.
     Unknown,
@@ -782,6 +1110,28 @@ pub enum Speed {
     Gen4,
 }
 
+/// Result of DisplayPort bandwidth allocation mode discovery.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BwDiscovery {
+    /// Discovery has not completed yet.
+    Pending,
+    /// DPTX and DPRX both support bandwidth allocation mode.
+    Success,
+    /// Bandwidth allocation mode cannot be used.
+    Failure,
+}
+
+impl Display for BwDiscovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::Pending => "pending",
+            Self::Success => "success",
+            Self::Failure => "failure",
+        };
+        write!(f, "{s}")
+    }
+}
+
 /// Adapter of a router.
 ///
 /// This represents a single adapter of a router. When an adapter is created it contains its
@@ -824,11 +1174,12 @@ pub struct Adapter {
     adapter: u8,
     kind: Type,
     state: State,
-    debugfs_path: Option<PathBuf>,
+    router: Option<String>,
     regs: Option<Vec<Register>>,
     path_regs: Option<Vec<Register>>,
     paths: Option<Vec<PathEntry>>,
     counter_regs: Option<Vec<Register>>,
+    sb_regs: Option<Vec<Register>>,
     usb4: bool,
     upstream: bool,
 }
@@ -844,7 +1195,7 @@ impl Adapter {
         depth: u32,
         adapter: u8,
         kind: Type,
-        debugfs_path: Option<PathBuf>,
+        router: Option<String>,
         usb4: bool,
         upstream: bool,
     ) -> Self {
@@ -855,11 +1206,12 @@ impl Adapter {
             adapter,
             kind,
             state: State::Unknown,
-            debugfs_path,
+            router,
             regs: None,
             path_regs: None,
             paths: None,
             counter_regs: None,
+            sb_regs: None,
             usb4,
             upstream,
         }
@@ -883,8 +1235,9 @@ impl Adapter {
         }
     }
 
-    fn debugfs_path(&self) -> Option<&PathBuf> {
-        self.debugfs_path.as_ref()
+    /// Returns kernel name of the router this adapter belongs to.
+    fn router(&self) -> &str {
+        self.router.as_deref().unwrap()
     }
 
     fn parse_state(&self) -> State {
@@ -1160,15 +1513,186 @@ impl Adapter {
         })
     }
 
+    fn dp_in_register(&self, name: &str) -> Option<&Register> {
+        if self.kind == Type::DisplayPortIn {
+            self.register_by_name(name)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the connection manager supports DisplayPort bandwidth allocation mode.
+    ///
+    /// Returns `None` if this is not a DisplayPort IN adapter.
+    pub fn dp_bw_mode_supported(&self) -> Option<bool> {
+        Some(self.dp_in_register("ADP_DP_CS_2")?.flag("CMMS"))
+    }
+
+    /// Returns `true` if the DPTX has enabled DisplayPort bandwidth allocation mode.
+    ///
+    /// Returns `None` if this is not a DisplayPort IN adapter.
+    pub fn dp_bw_mode_enabled(&self) -> Option<bool> {
+        Some(self.dp_in_register("ADP_DP_CS_8")?.flag("DPME"))
+    }
+
+    /// Returns bandwidth allocation mode granularity in Mb/s.
+    ///
+    /// Returns `None` if this is not a DisplayPort IN adapter or the granularity value is
+    /// reserved.
+    pub fn dp_granularity(&self) -> Option<u32> {
+        match self.dp_in_register("ADP_DP_CS_2")?.field("GR") {
+            0 => Some(250),
+            1 => Some(500),
+            2 => Some(1000),
+            _ => None,
+        }
+    }
+
+    /// Returns bandwidth allocation mode group ID of the DisplayPort IN adapter.
+    pub fn dp_group_id(&self) -> Option<u32> {
+        Some(self.dp_in_register("ADP_DP_CS_2")?.field("Group_ID"))
+    }
+
+    /// Returns estimated bandwidth in Mb/s set by the connection manager.
+    pub fn dp_estimated_bw(&self) -> Option<u32> {
+        Some(self.dp_in_register("ADP_DP_CS_2")?.field("Estimated BW") * self.dp_granularity()?)
+    }
+
+    /// Returns bandwidth in Mb/s the connection manager has allocated for the DPTX.
+    pub fn dp_allocated_bw(&self) -> Option<u32> {
+        Some(self.dp_in_register("DP_STATUS")?.field("Allocated BW") * self.dp_granularity()?)
+    }
+
+    /// Returns bandwidth in Mb/s last requested by the DPTX.
+    pub fn dp_requested_bw(&self) -> Option<u32> {
+        Some(self.dp_in_register("ADP_DP_CS_8")?.field("Requested BW") * self.dp_granularity()?)
+    }
+
+    /// Returns `true` if the DPTX has a bandwidth request pending.
+    pub fn dp_bw_request_pending(&self) -> Option<bool> {
+        Some(self.dp_in_register("ADP_DP_CS_8")?.flag("DR"))
+    }
+
+    /// Returns the result of the bandwidth allocation mode discovery.
+    pub fn dp_bw_discovery(&self) -> Option<BwDiscovery> {
+        let reg = self.dp_in_register("ADP_DP_CS_9")?;
+        if reg.flag("DF") {
+            Some(BwDiscovery::Failure)
+        } else if reg.flag("DS") {
+            Some(BwDiscovery::Success)
+        } else {
+            Some(BwDiscovery::Pending)
+        }
+    }
+
+    /// Returns negotiated DisplayPort link rate in Mb/s per lane.
+    ///
+    /// Returns `None` if this is not a DisplayPort IN adapter or the rate is not known.
+    pub fn dp_link_rate(&self) -> Option<u32> {
+        match self.dp_in_register("DP_STATUS")?.field("Link Rate") {
+            0 => Some(1620),
+            1 => Some(2700),
+            2 => Some(5400),
+            3 => Some(8100),
+            4 => Some(10000),
+            5 => Some(20000),
+            6 => Some(13500),
+            _ => None,
+        }
+    }
+
+    /// Returns negotiated DisplayPort lane count.
+    pub fn dp_lane_count(&self) -> Option<u32> {
+        Some(self.dp_in_register("DP_STATUS")?.field("Lane Count"))
+    }
+
+    fn usb3_register(&self, name: &str) -> Option<&Register> {
+        if matches!(
+            self.kind,
+            Type::Usb3Down | Type::Usb3Up | Type::Usb3GenTDown | Type::Usb3GenTUp
+        ) {
+            self.register_by_name(name)
+        } else {
+            None
+        }
+    }
+
+    fn usb3_bw_to_mbps(&self, bw: u32) -> Option<u32> {
+        let scale = self.usb3_register("ADP_USB3_GX_CS_3")?.field("Scale");
+        // Bandwidth is in units of 512 << Scale bytes per 125 us microframe.
+        let uframes = (bw as u64 * 512) << scale;
+        (uframes * 8000 / 1_000_000).try_into().ok()
+    }
+
+    /// Returns upstream bandwidth in Mb/s the connection manager has allocated for the USB 3
+    /// tunnel.
+    ///
+    /// Returns `None` if this is not a USB 3 adapter.
+    pub fn usb3_allocated_up_bw(&self) -> Option<u32> {
+        let bw = self
+            .usb3_register("ADP_USB3_GX_CS_2")?
+            .field("Allocated Upstream Bandwidth");
+        self.usb3_bw_to_mbps(bw)
+    }
+
+    /// Returns downstream bandwidth in Mb/s the connection manager has allocated for the USB 3
+    /// tunnel.
+    pub fn usb3_allocated_down_bw(&self) -> Option<u32> {
+        let bw = self
+            .usb3_register("ADP_USB3_GX_CS_2")?
+            .field("Allocated Downstream Bandwidth");
+        self.usb3_bw_to_mbps(bw)
+    }
+
+    /// Returns upstream bandwidth in Mb/s the USB 3 tunnel is currently consuming.
+    pub fn usb3_consumed_up_bw(&self) -> Option<u32> {
+        let bw = self
+            .usb3_register("ADP_USB3_GX_CS_1")?
+            .field("Consumed Upstream Bandwidth");
+        self.usb3_bw_to_mbps(bw)
+    }
+
+    /// Returns downstream bandwidth in Mb/s the USB 3 tunnel is currently consuming.
+    pub fn usb3_consumed_down_bw(&self) -> Option<u32> {
+        let bw = self
+            .usb3_register("ADP_USB3_GX_CS_1")?
+            .field("Consumed Downstream Bandwidth");
+        self.usb3_bw_to_mbps(bw)
+    }
+
+    /// Finds the PCI bridge of this PCIe adapter from `bridges`.
+    pub fn pci_bridge<'a>(&self, bridges: &'a [pci::Bridge]) -> Option<&'a pci::Bridge> {
+        bridges.iter().find(|b| {
+            b.domain() == self.domain && b.route() == self.route && b.adapter() == self.adapter
+        })
+    }
+
+    /// Finds the USB port of this USB 3 adapter from `ports`.
+    pub fn usb_port<'a>(&self, ports: &'a [usb::Port]) -> Option<&'a usb::Port> {
+        ports.iter().find(|p| {
+            p.domain() == self.domain && p.route() == self.route && p.adapter() == self.adapter
+        })
+    }
+
+    /// Finds the DRM connector driven through this DP IN adapter from `connectors`.
+    ///
+    /// Only DP IN adapters of host routers are connected to graphics and the connector must have
+    /// an active tunnel so that its DPCD can be read.
+    pub fn dp_connector<'a>(&self, connectors: &'a [drm::Connector]) -> Option<&'a drm::Connector> {
+        if self.route != 0 {
+            return None;
+        }
+        let cm_id = self.dp_in_register("ADP_DP_CS_2")?.field("CM_ID");
+        connectors.iter().find(|c| {
+            c.adapter() == Some(self.adapter) && c.driver_id().map(u32::from) == Some(cm_id)
+        })
+    }
+
     /// Reads the adapter register space.
     ///
     /// Must be called before accessing any other register space.
     pub fn read_registers(&mut self) -> Result<()> {
-        let mut path_buf = self.debugfs_path().unwrap().clone();
-        path_buf.push(format!("port{}", self.adapter()));
-        path_buf.push(DEBUGFS_REGS);
-
-        let mut regs = match read(&path_buf, None, None) {
+        let regs = match backend::get().read_registers(self.router(), Some(self.adapter)) {
             Ok(regs) => regs,
             Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
             Err(err) => return Err(err),
@@ -1176,7 +1700,13 @@ impl Adapter {
 
         // Figure out the type of the adapter from the registers now.
         self.kind = Self::parse_kind(&regs);
+        self.set_registers(regs);
+
+        Ok(())
+    }
 
+    // Attaches metadata to `regs` according to the adapter type and assigns them.
+    fn set_registers(&mut self, mut regs: Vec<Register>) {
         // Pull in metadata.
         for reg in &mut regs {
             let metadata =
@@ -1192,8 +1722,6 @@ impl Adapter {
         // At this point we have full metadata available for the registers so we can update the
         // initial state.
         self.state = self.parse_state();
-
-        Ok(())
     }
 
     /// Reads registers if they are not read already.
@@ -1246,18 +1774,24 @@ impl Adapter {
     ///
     /// This must be called before accessing path config space registers.
     pub fn read_paths(&mut self) -> Result<()> {
-        let mut path_buf = self.debugfs_path().unwrap().clone();
-        path_buf.push(format!("port{}", self.adapter()));
-        path_buf.push(DEBUGFS_PATH);
-
-        self.path_regs = match read(&path_buf, None, None) {
-            Ok(path_regs) => Some(path_regs),
+        let path_regs = match backend::get().read_paths(self.router(), self.adapter) {
+            Ok(path_regs) => path_regs,
             // Lane 1 adapter path config space is not accessible in USB4 v2 devices so this is
             // fine, just return empty paths.
             Err(_) if self.is_lane1() => return Ok(()),
             Err(err) => return Err(err),
         };
 
+        self.set_path_registers(path_regs);
+
+        Ok(())
+    }
+
+    // Attaches metadata to path config space `path_regs`, assigns them and parses the enabled
+    // path entries.
+    fn set_path_registers(&mut self, path_regs: Vec<Register>) {
+        self.path_regs = Some(path_regs);
+
         // Pull in metadata.
         let kind = self.kind();
 
@@ -1294,8 +1828,6 @@ impl Adapter {
                 self.paths = None;
             }
         }
-
-        Ok(())
     }
 
     /// Reads path config spaces if not read already.
@@ -1343,11 +1875,7 @@ impl Adapter {
     ///
     /// Must be called before accessing adapter counter registers.
     pub fn read_counters(&mut self) -> Result<()> {
-        let mut path_buf = self.debugfs_path().unwrap().clone();
-        path_buf.push(format!("port{}", self.adapter()));
-        path_buf.push(DEBUGFS_COUNTERS);
-
-        self.counter_regs = match read(&path_buf, None, None) {
+        self.counter_regs = match backend::get().read_counters(self.router(), self.adapter) {
             Ok(counter_regs) => Some(counter_regs),
             // Lane 1 adapter counters config space is not accessible in USB4 v2 devices so this is
             // fine, just return empty paths.
@@ -1392,71 +1920,224 @@ impl Adapter {
 
     /// Clears all counters. This takes effect immediately.
     pub fn clear_counters(&mut self) -> Result<()> {
-        let mut path_buf = self.debugfs_path().unwrap().clone();
-        path_buf.push(format!("port{}", self.adapter()));
-        path_buf.push(DEBUGFS_COUNTERS);
+        backend::get().clear_counters(self.router(), self.adapter)
+    }
 
-        let file = OpenOptions::new().write(true).open(path_buf)?;
-        let mut writer = BufWriter::new(file);
+    /// Reads USB4 port sideband registers.
+    ///
+    /// Only lane adapters of USB4 ports have these. Must be called before accessing sideband
+    /// registers.
+    pub fn read_sideband(&mut self) -> Result<()> {
+        let regs = backend::get().read_sideband(self.router(), Some(self.adapter))?;
+        self.sb_regs = Some(sideband_with_metadata(regs));
+        Ok(())
+    }
 
-        // Empty line clear all counters.
-        writeln!(&mut writer)?;
+    /// Reads sideband registers if they are not read already.
+    ///
+    /// If they are already read this does nothing. You can force re-read by calling
+    /// [`read_sideband()`](Self::read_sideband).
+    pub fn read_sideband_cached(&mut self) -> Result<()> {
+        if self.sb_regs.is_none() {
+            return self.read_sideband();
+        }
+        Ok(())
+    }
 
-        writer.flush()
+    /// Returns port sideband registers.
+    pub fn sideband_registers(&self) -> Option<&Vec<Register>> {
+        self.sb_regs.as_ref()
+    }
+
+    /// Returns port sideband register by name.
+    ///
+    /// The match is case insensitive.
+    pub fn sideband_register_by_name(&self, name: &str) -> Option<&Register> {
+        find_by_name(self.sb_regs.as_ref()?, name)
+    }
+
+    /// Returns mutable reference to a port sideband register by name.
+    ///
+    /// The match is case insensitive.
+    pub fn sideband_register_by_name_mut(&mut self, name: &str) -> Option<&mut Register> {
+        find_by_name_mut(self.sb_regs.as_mut()?, name)
+    }
+
+    /// Returns port sideband register by offset.
+    pub fn sideband_register_by_offset(&self, offset: u16) -> Option<&Register> {
+        self.sb_regs.as_ref()?.iter().find(|r| r.offset == offset)
+    }
+
+    /// Returns mutable reference to a port sideband register by offset.
+    pub fn sideband_register_by_offset_mut(&mut self, offset: u16) -> Option<&mut Register> {
+        self.sb_regs
+            .as_mut()?
+            .iter_mut()
+            .find(|r| r.offset == offset)
     }
 
     /// Writes all changed registers in all configuration spaces back to the hardware. After this
     /// you should call [`read_registers()`](Self::read_registers()),
-    /// [`read_paths()`](Self::read_paths()) and [`read_counters()`](Self::read_counters()) to
-    /// re-read registers from the hardware.
+    /// [`read_paths()`](Self::read_paths()), [`read_counters()`](Self::read_counters()) and
+    /// [`read_sideband()`](Self::read_sideband()) to re-read registers from the hardware.
     pub fn write_changed(&mut self) -> Result<()> {
-        let mut path_buf = self.debugfs_path().unwrap().clone();
-        path_buf.push(format!("port{}", self.adapter()));
+        let router = self.router();
+        let adapter = Some(self.adapter);
 
         if let Some(regs) = &self.regs {
-            path_buf.push(DEBUGFS_REGS);
-            write_changed(&path_buf, regs)?;
-            path_buf.pop();
+            write_changed(router, adapter, ConfigSpace::Adapter, regs)?;
         }
 
         if let Some(path_regs) = &self.path_regs {
-            path_buf.push(DEBUGFS_PATH);
-            write_changed(&path_buf, path_regs)?;
-            path_buf.pop();
+            write_changed(router, adapter, ConfigSpace::Path, path_regs)?;
         }
 
         if let Some(counter_regs) = &self.counter_regs {
-            path_buf.push(DEBUGFS_COUNTERS);
-            write_changed(&path_buf, counter_regs)?;
-            path_buf.pop();
+            write_changed(router, adapter, ConfigSpace::Counters, counter_regs)?;
+        }
+
+        if let Some(sb_regs) = &self.sb_regs {
+            write_changed(router, adapter, ConfigSpace::Sideband, sb_regs)?;
         }
 
         Ok(())
     }
+
+    /// Triggers Downstream Port Reset on this lane adapter.
+    ///
+    /// This is the same as unplugging and plugging back whatever is connected to the USB4 port.
+    /// Waits up to `timeout` for the router behind the port to go away and then get enumerated
+    /// again and returns how long each took. If nothing is connected to the port, returns
+    /// immediately after the reset has been triggered.
+    ///
+    /// Adapter registers must be read before calling this.
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if this is not a downstream lane 0 adapter,
+    /// [`ErrorKind::PermissionDenied`] if the registers are not writable and
+    /// [`ErrorKind::TimedOut`] if the router did not come back in time.
+    pub fn downstream_port_reset(&mut self, timeout: Duration) -> Result<PortReset> {
+        if !self.is_lane0() || self.is_upstream() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "not a downstream lane 0 adapter",
+            ));
+        }
+        if !backend::get().registers_writable(self.router()) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "registers are not writable",
+            ));
+        }
+        if !self
+            .register_by_name("PORT_CS_19")
+            .is_some_and(|r| r.has_field("DPR"))
+        {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Downstream Port Reset not supported",
+            ));
+        }
+
+        let route = self.downstream_route().unwrap();
+        let domain = self.domain;
+        let address = Address::Router {
+            domain: domain as u8,
+            route,
+        };
+        let connected = crate::find_device(&address)?.is_some();
+        let is_router = move |d: &Device| d.domain_index() == domain && d.route() == route;
+
+        // Start monitoring before the reset so that we don't miss any events.
+        let mut monitor = monitor::Builder::new()?.kind(Kind::Router)?.build()?;
+        let start = Instant::now();
+
+        self.register_by_name_mut("PORT_CS_19")
+            .unwrap()
+            .set_field("DPR", 1);
+        self.write_changed()?;
+        thread::sleep(DPR_HOLD_TIME);
+        self.register_by_name_mut("PORT_CS_19")
+            .unwrap()
+            .set_field("DPR", 0);
+        self.write_changed()?;
+
+        let mut reset = PortReset::default();
+
+        if !connected {
+            return Ok(reset);
+        }
+
+        while reset.enumerated.is_none() {
+            let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    "router was not enumerated in time",
+                ));
+            };
+
+            if !monitor.poll(Some(remaining))? {
+                continue;
+            }
+
+            for event in monitor.iter_mut() {
+                match event {
+                    Event::Remove(device) if is_router(&device) => {
+                        reset.link_down = Some(start.elapsed());
+                    }
+                    Event::Add(device) if is_router(&device) && reset.link_down.is_some() => {
+                        reset.enumerated = Some(start.elapsed());
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(reset)
+    }
+}
+
+/// Result of [`Adapter::downstream_port_reset()`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PortReset {
+    link_down: Option<Duration>,
+    enumerated: Option<Duration>,
+}
+
+impl PortReset {
+    /// Time from triggering the reset until the router behind the port was removed. `None` if
+    /// nothing was connected.
+    pub fn link_down(&self) -> Option<Duration> {
+        self.link_down
+    }
+
+    /// Time from triggering the reset until the router behind the port was enumerated again.
+    /// `None` if nothing was connected.
+    pub fn enumerated(&self) -> Option<Duration> {
+        self.enumerated
+    }
 }
 
 impl Device {
     /// Return debugfs path.
     pub fn debugfs_path(&self) -> PathBuf {
-        let mut path_buf = PathBuf::from(DEBUGFS_ROOT);
+        let mut path_buf = sysfs::path_buf(DEBUGFS_ROOT);
         path_buf.push(self.kernel_name());
         path_buf
     }
 
     /// Return `true` if device registers are writable.
     pub fn registers_writable(&self) -> bool {
-        if let Ok(path_buf) = router_path_buf(self)
-            && let Ok(file) = File::open(path_buf)
-            && let Ok(metadata) = file.metadata()
-        {
-            return !metadata.permissions().readonly();
-        }
-        false
+        backend::get().registers_writable(&self.kernel_name())
     }
 
     /// Returns parent device of this, or `None` if no such device is found.
     pub fn parent(&self) -> Option<Self> {
-        let parent = self.udev().ok()?.parent()?;
+        if snapshot::active().is_some() || sysfs::root().is_some() {
+            return Self::from_syspath(self.sysfs_path().parent()?);
+        }
+        let parent = udev::Device::from_syspath(&self.sysfs_path())
+            .ok()?
+            .parent()?;
         Self::parse(parent)
     }
 
@@ -1497,7 +2178,7 @@ impl Device {
 
     /// Read registers from hardware.
     pub fn read_registers(&mut self) -> Result<()> {
-        let mut regs = read(&router_path_buf(self)?, None, None)?;
+        let mut regs = backend::get().read_registers(&self.kernel_name(), None)?;
 
         // Pull in metadata.
         for reg in &mut regs {
@@ -1564,20 +2245,17 @@ impl Device {
         self.regs.as_mut()?.iter_mut().find(|r| r.offset == offset)
     }
 
-    fn read_adapter(&self, adapter: u8, upstream: bool) -> Result<Adapter> {
-        let mut path_buf = path_buf()?;
-        path_buf.push(self.kernel_name());
-
-        Ok(Adapter::new(
+    fn read_adapter(&self, adapter: u8, upstream: bool) -> Adapter {
+        Adapter::new(
             self.domain_index(),
             self.route(),
             self.depth(),
             adapter,
             Type::Inactive,
-            Some(path_buf),
+            Some(self.kernel_name()),
             self.usb4_version().is_some(),
             upstream,
-        ))
+        )
     }
 
     /// Return max adapter number.
@@ -1633,7 +2311,7 @@ impl Device {
         let mut adapters = Vec::new();
 
         for i in 1..=max_adapter {
-            let mut adapter = self.read_adapter(i, i == upstream_adapter)?;
+            let mut adapter = self.read_adapter(i, i == upstream_adapter);
             adapter.read_registers()?;
             adapters.push(adapter);
         }
@@ -1692,12 +2370,62 @@ impl Device {
     /// [`read_registers()`](Self::read_registers()) to refresh the registers from the hardware.
     pub fn write_changed(&mut self) -> Result<()> {
         if let Some(regs) = &self.regs {
-            write_changed(&router_path_buf(self)?, regs)?;
+            write_changed(&self.kernel_name(), None, ConfigSpace::Router, regs)?;
+        }
+
+        if let Some(sb_regs) = &self.sb_regs {
+            write_changed(&self.kernel_name(), None, ConfigSpace::Sideband, sb_regs)?;
         }
 
         Ok(())
     }
 
+    /// Reads retimer sideband registers.
+    ///
+    /// Returns [`ErrorKind::Unsupported`] if this is not a retimer. Must be called before
+    /// accessing sideband registers.
+    pub fn read_sideband(&mut self) -> Result<()> {
+        if !self.is_retimer() {
+            return Err(Error::from(ErrorKind::Unsupported));
+        }
+
+        let regs = backend::get().read_sideband(&self.kernel_name(), None)?;
+        self.sb_regs = Some(sideband_with_metadata(regs));
+        Ok(())
+    }
+
+    /// Returns retimer sideband registers.
+    pub fn sideband_registers(&self) -> Option<&Vec<Register>> {
+        self.sb_regs.as_ref()
+    }
+
+    /// Returns retimer sideband register by name.
+    ///
+    /// The match is case insensitive.
+    pub fn sideband_register_by_name(&self, name: &str) -> Option<&Register> {
+        find_by_name(self.sb_regs.as_ref()?, name)
+    }
+
+    /// Returns mutable reference to a retimer sideband register by name.
+    ///
+    /// The match is case insensitive.
+    pub fn sideband_register_by_name_mut(&mut self, name: &str) -> Option<&mut Register> {
+        find_by_name_mut(self.sb_regs.as_mut()?, name)
+    }
+
+    /// Returns retimer sideband register by offset.
+    pub fn sideband_register_by_offset(&self, offset: u16) -> Option<&Register> {
+        self.sb_regs.as_ref()?.iter().find(|r| r.offset == offset)
+    }
+
+    /// Returns mutable reference to a retimer sideband register by offset.
+    pub fn sideband_register_by_offset_mut(&mut self, offset: u16) -> Option<&mut Register> {
+        self.sb_regs
+            .as_mut()?
+            .iter_mut()
+            .find(|r| r.offset == offset)
+    }
+
     /// Reads device DROM and makes it available through [`drom()`](Self::drom).
     ///
     /// This requires that the kernel supports exposing DROM contents via debugfs which was added
@@ -1705,13 +2433,7 @@ impl Device {
     pub fn read_drom(&mut self) -> Result<()> {
         self.read_adapters()?;
 
-        let mut path_buf = path_buf()?;
-        path_buf.push(self.kernel_name());
-        path_buf.push(DEBUGFS_DROM);
-
-        let mut bytes = Vec::new();
-        let mut file = File::open(path_buf)?;
-        file.read_to_end(&mut bytes)?;
+        let bytes = backend::get().read_drom(&self.kernel_name())?;
 
         self.drom = Some(Drom::parse(&bytes, self.adapters().unwrap())?);
 
@@ -1725,3 +2447,202 @@ impl Device {
         self.drom.as_ref()
     }
 }
+
+#[cfg(test)]
+impl Adapter {
+    /// Sets adapter config space as if it was read from the hardware. `regs` are the basic
+    /// `ADP_CS_x` registers and `protocol` the adapter configuration capability.
+    pub(crate) fn set_test_registers(&mut self, regs: &[u32], protocol: &[u32]) {
+        let mut all: Vec<_> = regs
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Register::new(i as u16, i as u16, 0, 0, *value))
+            .collect();
+        all.extend(protocol.iter().enumerate().map(|(i, value)| {
+            Register::new(0x40 + i as u16, i as u16, usb4::ADP_CAP_ID_ADP, 0, *value)
+        }));
+        self.set_registers(all);
+    }
+
+    /// Sets path config space from `(in_hop, PATH_CS_0, PATH_CS_1)` entries.
+    pub(crate) fn set_test_paths(&mut self, entries: &[(u16, u32, u32)]) {
+        let regs = entries
+            .iter()
+            .flat_map(|(in_hop, cs_0, cs_1)| {
+                [
+                    Register::new(in_hop * 2, 0, 0, 0, *cs_0),
+                    Register::new(in_hop * 2 + 1, 1, 0, 0, *cs_1),
+                ]
+            })
+            .collect();
+        self.set_path_registers(regs);
+    }
+}
+
+#[cfg(test)]
+impl Device {
+    /// Sets router config space from `values` starting at `ROUTER_CS_0` and the adapters.
+    pub(crate) fn set_test_registers(&mut self, values: &[u32], adapters: Vec<Adapter>) {
+        let regs = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let mut reg = Register::new(i as u16, i as u16, 0, 0, *value);
+                if let Some(metadata) = Metadata::with_router_offset(reg.relative_offset) {
+                    reg.set_metadata(metadata);
+                }
+                reg
+            })
+            .collect();
+        self.regs = Some(regs);
+        self.adapters = Some(adapters);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn router_regs(values: &[u32]) -> Vec<Register> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let mut reg = Register::new(i as u16, i as u16, 0, 0, *value);
+                if let Some(metadata) = Metadata::with_router_offset(reg.relative_offset) {
+                    reg.set_metadata(metadata);
+                }
+                reg
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff() {
+        let before = router_regs(&[0x15ea8086, 0x00310100]);
+        let after = router_regs(&[0x15ea8086, 0x00310200, 0x12345678]);
+
+        assert!(before.diff(&before).is_empty());
+
+        let changes = before.diff(&after);
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!(changes[0].offset(), 1);
+        assert_eq!(changes[0].before().unwrap().value(), 0x00310100);
+        assert_eq!(changes[0].after().unwrap().value(), 0x00310200);
+
+        let fields = changes[0].fields();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].0.name(), "Upstream Adapter");
+        assert_eq!(fields[0].1, 1);
+        assert_eq!(fields[0].2, 2);
+
+        assert_eq!(changes[1].offset(), 2);
+        assert!(changes[1].before().is_none());
+        assert!(changes[1].fields().is_empty());
+
+        let changes = after.diff(&before);
+        assert_eq!(changes.len(), 2);
+        assert!(changes[1].after().is_none());
+    }
+
+    fn protocol_adapter(adapter_num: u8, kind: Type, values: &[u32]) -> Adapter {
+        let mut adapter = Adapter::new(0, 0, 0, adapter_num, kind, None, true, false);
+        let regs = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let mut reg = Register::new(0x40 + i as u16, i as u16, 4, 0, *value);
+                if let Some(metadata) =
+                    Metadata::with_adapter_type_and_offset("adapter", kind, reg.relative_offset)
+                {
+                    reg.set_metadata(metadata);
+                }
+                reg
+            })
+            .collect();
+        adapter.regs = Some(regs);
+        adapter
+    }
+
+    fn dp_in(values: &[u32]) -> Adapter {
+        protocol_adapter(10, Type::DisplayPortIn, values)
+    }
+
+    #[test]
+    fn dp_bandwidth() {
+        // GR=1 (500 Mb/s), Group_ID=2, CMMS, Estimated BW=40.
+        let cs_2 = 1 << 11 | 2 << 13 | 1 << 20 | 40 << 24;
+        // Link Rate=3, Lane Count=4, Allocated BW=30.
+        let dp_status = 4 | 3 << 8 | 30 << 24;
+        // Requested BW=20, DPME.
+        let cs_8 = 20 | 1 << 30;
+        let adapter = dp_in(&[0, 0, cs_2, 0, 0, 0, dp_status, 0, cs_8, 0x1]);
+
+        assert_eq!(adapter.dp_bw_mode_supported(), Some(true));
+        assert_eq!(adapter.dp_bw_mode_enabled(), Some(true));
+        assert_eq!(adapter.dp_granularity(), Some(500));
+        assert_eq!(adapter.dp_group_id(), Some(2));
+        assert_eq!(adapter.dp_estimated_bw(), Some(20000));
+        assert_eq!(adapter.dp_allocated_bw(), Some(15000));
+        assert_eq!(adapter.dp_requested_bw(), Some(10000));
+        assert_eq!(adapter.dp_bw_request_pending(), Some(false));
+        assert_eq!(adapter.dp_bw_discovery(), Some(BwDiscovery::Success));
+        assert_eq!(adapter.dp_link_rate(), Some(8100));
+        assert_eq!(adapter.dp_lane_count(), Some(4));
+
+        // Reserved granularity.
+        let adapter = dp_in(&[0, 0, 3 << 11]);
+        assert_eq!(adapter.dp_granularity(), None);
+        assert_eq!(adapter.dp_estimated_bw(), None);
+        assert_eq!(adapter.dp_bw_mode_enabled(), None);
+
+        let lane = Adapter::new(0, 0, 0, 1, Type::Lane, None, true, false);
+        assert_eq!(lane.dp_group_id(), None);
+    }
+
+    #[test]
+    fn usb3_bandwidth() {
+        // Consumed 100/200, allocated 300/400 with Scale=1 (1024 bytes per microframe).
+        let cs_1 = 100 | 200 << 12;
+        let cs_2 = 300 | 400 << 12;
+        let adapter = protocol_adapter(4, Type::Usb3Down, &[0, cs_1, cs_2, 1]);
+
+        assert_eq!(adapter.usb3_consumed_up_bw(), Some(819));
+        assert_eq!(adapter.usb3_consumed_down_bw(), Some(1638));
+        assert_eq!(adapter.usb3_allocated_up_bw(), Some(2457));
+        assert_eq!(adapter.usb3_allocated_down_bw(), Some(3276));
+
+        let adapter = dp_in(&[0, 0, 0]);
+        assert_eq!(adapter.usb3_allocated_up_bw(), None);
+    }
+
+    #[test]
+    fn sideband() {
+        let regs = parse_sideband_registers(
+            "# register value\n0x08 0x41 0x46 0x52 0x52\n0x0c 0x01 0x02 0x03\n\
+             0x12 0x01 0x02 0x03 0x04 0x05 0x06\n",
+        );
+        let mut regs = sideband_with_metadata(regs);
+        assert_eq!(regs.len(), 3);
+
+        assert_eq!(regs[0].name(), Some("OPCODE"));
+        assert_eq!(regs[0].value(), 0x52524641);
+        assert_eq!(
+            regs[0]
+                .field_by_name("Opcode")
+                .and_then(|f| f.value_name(regs[0].field("Opcode"))),
+            Some("NVM Read")
+        );
+
+        assert_eq!(regs[1].value(), 0x030201);
+        assert_eq!(regs[2].data(), Some(&[1, 2, 3, 4, 5, 6][..]));
+
+        regs[2].set_value(0xaabbccdd);
+        assert!(regs[2].is_changed());
+        assert_eq!(regs[2].data(), Some(&[0xdd, 0xcc, 0xbb, 0xaa, 5, 6][..]));
+
+        regs[1].set_value(0x040506);
+        assert_eq!(regs[1].data(), Some(&[6, 5, 4][..]));
+    }
+}
@@ -7,17 +7,16 @@ use lazy_static::lazy_static;
 
 use serde::{Serialize, Serializer};
 use std::cmp::Ordering;
-use std::ffi::OsStr;
 use std::fmt::{self, Display};
 use std::io::{self, Error, ErrorKind};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use regex::Regex;
 
 use crate::{
     debugfs::{Adapter, Register},
     drom::Drom,
-    usb4, util,
+    snapshot, sysfs, usb4, util,
 };
 
 lazy_static! {
@@ -30,8 +29,48 @@ lazy_static! {
     static ref SPEED_RE: Regex = Regex::new(r"(\d+).0 Gb/s").unwrap();
 }
 
+/// Sysfs attributes and uevent properties of a device.
+///
+/// [`Device`] is parsed through this so that the same code works for live `udev` devices and for
+/// devices replayed from a [`Snapshot`](snapshot::Snapshot).
+pub(crate) trait Attributes {
+    /// Returns `DEVTYPE` of the device.
+    fn devtype(&self) -> Option<String>;
+    /// Returns kernel name of the device.
+    fn sysname(&self) -> Option<String>;
+    /// Returns full path of the device in sysfs.
+    fn syspath(&self) -> PathBuf;
+    /// Returns value of a sysfs attribute.
+    fn attribute(&self, name: &str) -> Option<String>;
+    /// Returns value of a uevent property.
+    fn property(&self, name: &str) -> Option<String>;
+}
+
+impl Attributes for udev::Device {
+    fn devtype(&self) -> Option<String> {
+        self.devtype()?.to_str().map(String::from)
+    }
+
+    fn sysname(&self) -> Option<String> {
+        self.sysname().to_str().map(String::from)
+    }
+
+    fn syspath(&self) -> PathBuf {
+        self.syspath().to_path_buf()
+    }
+
+    fn attribute(&self, name: &str) -> Option<String> {
+        self.attribute_value(name)?.to_str().map(String::from)
+    }
+
+    fn property(&self, name: &str) -> Option<String> {
+        self.property_value(name)?.to_str().map(String::from)
+    }
+}
+
 /// Describes type of the device.
-#[derive(Clone, Debug, Eq, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Kind {
     /// Device is host.
     Domain,
@@ -80,7 +119,8 @@ impl Display for Kind {
 /// There is more information about these in the kernel [Thunderbolt/USB4 documentation].
 ///
 /// [Thunderbolt/USB4 documentation]: https://docs.kernel.org/admin-guide/thunderbolt.html#security-levels-and-how-to-use-them
-#[derive(Clone, Debug, Eq, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SecurityLevel {
     /// PCIe tunnel is created automatically.
     None,
@@ -113,7 +153,7 @@ impl From<&str> for SecurityLevel {
 }
 
 /// Encodes hardware and firmware version numbers.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
@@ -141,6 +181,7 @@ pub struct Device {
     security_level: Option<SecurityLevel>,
     iommu: Option<bool>,
     deauthorization: Option<bool>,
+    boot_acl: Option<Vec<Option<String>>>,
     usb4_version: Option<Version>,
     rx_speed: Option<u32>,
     rx_lanes: Option<u32>,
@@ -156,13 +197,50 @@ pub struct Device {
     syspath: PathBuf,
 
     pub(crate) regs: Option<Vec<Register>>,
+    pub(crate) sb_regs: Option<Vec<Register>>,
     pub(crate) adapters: Option<Vec<Adapter>>,
     pub(crate) drom: Option<Drom>,
 }
 
 impl Device {
-    pub(crate) fn udev(&self) -> io::Result<udev::Device> {
-        udev::Device::from_syspath(&self.syspath)
+    /// Returns sysfs attributes and uevent properties of the device.
+    pub(crate) fn attributes(&self) -> io::Result<Box<dyn Attributes>> {
+        if snapshot::active().is_some() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "not available with a snapshot",
+            ));
+        }
+        if sysfs::root().is_some() {
+            return sysfs::SysfsDevice::new(&self.syspath)
+                .map(|d| Box::new(d) as Box<dyn Attributes>)
+                .ok_or_else(|| Error::from(ErrorKind::NotFound));
+        }
+        Ok(Box::new(udev::Device::from_syspath(&self.syspath)?))
+    }
+
+    pub(crate) fn set_attribute(&self, name: &str, value: &str) -> io::Result<()> {
+        if snapshot::active().is_some() {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "snapshot is read-only",
+            ));
+        }
+        if sysfs::root().is_some() {
+            return sysfs::set_attribute(&self.syspath, name, value);
+        }
+        udev::Device::from_syspath(&self.syspath)?.set_attribute_value(name, value)
+    }
+
+    /// Parses the device in `syspath` again.
+    pub(crate) fn from_syspath(syspath: &Path) -> Option<Self> {
+        if let Some(snapshot) = snapshot::active() {
+            return snapshot.device(syspath);
+        }
+        if sysfs::root().is_some() {
+            return Self::parse_attributes(&sysfs::SysfsDevice::new(syspath)?);
+        }
+        Self::parse(udev::Device::from_syspath(syspath).ok()?)
     }
 
     /// Returns kernel name of the device.
@@ -238,10 +316,15 @@ impl Device {
         self.kind == Kind::Service
     }
 
+    /// Returns `true` if the device is retimer.
+    pub fn is_retimer(&self) -> bool {
+        self.kind == Kind::Retimer
+    }
+
     /// Returns the domain this device belongs to
     pub fn domain(&self) -> Option<Self> {
         if self.is_domain() {
-            return Self::parse(self.udev().ok()?);
+            return Self::from_syspath(&self.syspath);
         }
 
         let mut parent = self.parent()?;
@@ -340,7 +423,7 @@ impl Device {
         if self.kind != Kind::Router || !self.has_key() {
             return Err(Error::from(ErrorKind::InvalidData));
         } else {
-            self.udev()?.set_attribute_value("key", key)?;
+            self.set_attribute("key", key)?;
         }
 
         Ok(())
@@ -351,8 +434,7 @@ impl Device {
         if self.kind != Kind::Router {
             Err(Error::from(ErrorKind::InvalidData))
         } else {
-            self.udev()?
-                .set_attribute_value("authorized", format!("{authorize}"))
+            self.set_attribute("authorized", &format!("{authorize}"))
         }
     }
 
@@ -382,6 +464,52 @@ impl Device {
         self.deauthorization
     }
 
+    /// Returns the pre-boot access control list of the domain.
+    ///
+    /// Each slot holds the [`unique_id()`](Self::unique_id()) of a router the firmware
+    /// authorizes automatically on boot, or `None` if the slot is empty. Returns `None` if the
+    /// domain does not support boot ACL.
+    pub fn boot_acl(&self) -> Option<Vec<Option<String>>> {
+        self.boot_acl.clone()
+    }
+
+    /// Writes the pre-boot access control list of the domain.
+    ///
+    /// `acl` must have exactly the same number of slots as [`boot_acl()`](Self::boot_acl())
+    /// returns and each non-empty slot must be a UUID. Returns [`ErrorKind::Unsupported`] if the
+    /// domain does not support boot ACL and [`ErrorKind::InvalidInput`] if `acl` is not valid.
+    pub fn set_boot_acl(&mut self, acl: &[Option<String>]) -> io::Result<()> {
+        let Some(slots) = self.boot_acl.as_ref().map(Vec::len) else {
+            return Err(Error::new(ErrorKind::Unsupported, "boot ACL not supported"));
+        };
+        if acl.len() != slots {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("boot ACL must have {slots} slots"),
+            ));
+        }
+        if let Some(uuid) = acl
+            .iter()
+            .flatten()
+            .find(|uuid| uuid::Uuid::parse_str(uuid).is_err())
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{uuid} is not a valid UUID"),
+            ));
+        }
+
+        let value = acl
+            .iter()
+            .map(|uuid| uuid.as_deref().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.set_attribute("boot_acl", &value)?;
+        self.boot_acl = Some(acl.to_vec());
+
+        Ok(())
+    }
+
     /// Returns `true` if DMA is protected by an IOMMU.
     pub fn iommu_dma_protection(&self) -> Option<bool> {
         self.iommu
@@ -417,18 +545,18 @@ impl Device {
         self.protocol_settings
     }
 
-    fn parse_speed(value: Option<&OsStr>) -> Option<u32> {
+    fn parse_speed(value: Option<String>) -> Option<u32> {
         if let Some(speed) = value {
-            let caps = SPEED_RE.captures(speed.to_str()?)?;
+            let caps = SPEED_RE.captures(&speed)?;
             caps[1].parse::<u32>().ok()
         } else {
             None
         }
     }
 
-    fn parse_lanes(value: Option<&OsStr>) -> Option<u32> {
+    fn parse_lanes(value: Option<String>) -> Option<u32> {
         if let Some(lanes) = value {
-            lanes.to_str()?.parse::<u32>().ok()
+            lanes.parse::<u32>().ok()
         } else {
             None
         }
@@ -454,6 +582,7 @@ impl Device {
         security_level: Option<SecurityLevel>,
         iommu: Option<bool>,
         deauthorization: Option<bool>,
+        boot_acl: Option<Vec<Option<String>>>,
         usb4_version: Option<Version>,
         rx_speed: Option<u32>,
         rx_lanes: Option<u32>,
@@ -485,6 +614,7 @@ impl Device {
             security_level,
             iommu,
             deauthorization,
+            boot_acl,
             usb4_version,
             rx_speed,
             rx_lanes,
@@ -499,62 +629,58 @@ impl Device {
             protocol_settings,
             syspath,
             regs: None,
+            sb_regs: None,
             adapters: None,
             drom: None,
         }
     }
 
     pub(crate) fn parse(udev: udev::Device) -> Option<Self> {
-        let kind = Kind::from(udev.devtype()?.to_str()?);
+        Self::parse_attributes(&udev)
+    }
 
-        let vendor_id = udev
-            .attribute_value("vendor")
-            .and_then(|v| util::parse_hex(v.to_str()?))
+    pub(crate) fn parse_attributes(attrs: &impl Attributes) -> Option<Self> {
+        let kind = Kind::from(attrs.devtype()?.as_str());
+
+        let vendor_id = attrs
+            .attribute("vendor")
+            .and_then(|v| util::parse_hex(&v))
             .unwrap_or(0);
-        let device_id = udev
-            .attribute_value("device")
-            .and_then(|v| util::parse_hex(v.to_str()?))
+        let device_id = attrs
+            .attribute("device")
+            .and_then(|v| util::parse_hex(&v))
             .unwrap_or(0);
 
-        let device_name = udev
-            .attribute_value("device_name")
-            .and_then(|n| n.to_str())
-            .map(String::from);
-        let vendor_name = udev
-            .attribute_value("vendor_name")
-            .and_then(|n| n.to_str())
-            .map(String::from);
-        let authorized = udev
-            .attribute_value("authorized")
-            .and_then(|n| n.to_str())
+        let device_name = attrs.attribute("device_name");
+        let vendor_name = attrs.attribute("vendor_name");
+        let authorized = attrs
+            .attribute("authorized")
             .map(|n| n.parse::<u32>().unwrap_or(0) > 0);
-        let unique_id = udev
-            .attribute_value("unique_id")
-            .and_then(|n| n.to_str())
-            .map(String::from);
-        let generation = udev
-            .attribute_value("generation")
-            .and_then(|n| n.to_str())
+        let unique_id = attrs.attribute("unique_id");
+        let generation = attrs
+            .attribute("generation")
             .map(|n| n.parse::<u8>().unwrap());
 
-        let security_level = udev
-            .attribute_value("security")
-            .and_then(|n| n.to_str())
-            .map(String::from)
+        let security_level = attrs
+            .attribute("security")
             .map(|security_level| SecurityLevel::from(security_level.as_str()));
 
-        let iommu = udev
-            .attribute_value("iommu_dma_protection")
-            .and_then(|n| n.to_str())
+        let iommu = attrs
+            .attribute("iommu_dma_protection")
             .map(|n| n.parse::<u32>().unwrap_or(0) > 0);
-        let deauthorization = udev
-            .attribute_value("deauthorization")
-            .and_then(|n| n.to_str())
+        let deauthorization = attrs
+            .attribute("deauthorization")
             .map(|n| n.parse::<u32>().unwrap_or(0) > 0);
+        // Empty slots are empty strings.
+        let boot_acl = attrs.attribute("boot_acl").map(|acl| {
+            acl.split(',')
+                .map(|uuid| (!uuid.is_empty()).then(|| uuid.to_string()))
+                .collect()
+        });
 
         let usb4_version = if generation >= Some(4) {
-            let version = udev.property_value("USB4_VERSION")?.to_str().unwrap();
-            let caps = USB4_VERSION_RE.captures(version).unwrap();
+            let version = attrs.property("USB4_VERSION")?;
+            let caps = USB4_VERSION_RE.captures(&version).unwrap();
             let major = util::parse_hex::<u8>(&caps[1]).unwrap();
             let minor = util::parse_hex::<u8>(&caps[2]).unwrap();
 
@@ -563,23 +689,20 @@ impl Device {
             None
         };
 
-        let rx_speed = Self::parse_speed(udev.attribute_value("rx_speed"));
-        let rx_lanes = Self::parse_lanes(udev.attribute_value("rx_lanes"));
-        let tx_speed = Self::parse_speed(udev.attribute_value("tx_speed"));
-        let tx_lanes = Self::parse_lanes(udev.attribute_value("tx_lanes"));
+        let rx_speed = Self::parse_speed(attrs.attribute("rx_speed"));
+        let rx_lanes = Self::parse_lanes(attrs.attribute("rx_lanes"));
+        let tx_speed = Self::parse_speed(attrs.attribute("tx_speed"));
+        let tx_lanes = Self::parse_lanes(attrs.attribute("tx_lanes"));
 
-        let nvm_version = udev
-            .attribute_value("nvm_version")
-            .and_then(|n| n.to_str())
-            .and_then(|n| {
-                let caps = NVM_VERSION_RE.captures(n)?;
-                let major = util::parse_hex::<u8>(&caps[1])?;
-                let minor = util::parse_hex::<u8>(&caps[2])?;
+        let nvm_version = attrs.attribute("nvm_version").and_then(|n| {
+            let caps = NVM_VERSION_RE.captures(&n)?;
+            let major = util::parse_hex::<u8>(&caps[1])?;
+            let minor = util::parse_hex::<u8>(&caps[2])?;
 
-                Some(Version { major, minor })
-            });
+            Some(Version { major, minor })
+        });
 
-        let kernel_name = String::from(udev.sysname().to_str()?);
+        let kernel_name = attrs.sysname()?;
         let domain: u32;
         let mut route: u64 = 0;
         let mut adapter_num: u8 = 0;
@@ -610,40 +733,24 @@ impl Device {
                 domain = caps[1].parse().unwrap_or(0);
                 route = util::parse_hex::<u64>(&caps[2]).unwrap_or(0);
                 index = caps[3].parse().unwrap();
-                service_key = udev
-                    .attribute_value("key")
-                    .and_then(|n| n.to_str())
-                    .map(String::from);
-                protocol_id = udev
-                    .attribute_value("prtcid")
-                    .and_then(|n| n.to_str())
-                    .map(|n| n.parse::<u32>().ok())?;
-                protocol_version = udev
-                    .attribute_value("prtcvers")
-                    .and_then(|n| n.to_str())
-                    .map(|n| n.parse::<u32>().ok())?;
-                protocol_revision = udev
-                    .attribute_value("prtcrevs")
-                    .and_then(|n| n.to_str())
-                    .map(|n| n.parse::<u32>().ok())?;
-                protocol_settings = udev
-                    .attribute_value("prtcstns")
-                    .and_then(|n| n.to_str())
-                    .map(util::parse_hex::<u32>)?;
+                service_key = attrs.attribute("key");
+                protocol_id = attrs.attribute("prtcid").map(|n| n.parse::<u32>().ok())?;
+                protocol_version = attrs.attribute("prtcvers").map(|n| n.parse::<u32>().ok())?;
+                protocol_revision = attrs.attribute("prtcrevs").map(|n| n.parse::<u32>().ok())?;
+                protocol_settings = attrs
+                    .attribute("prtcstns")
+                    .map(|n| util::parse_hex::<u32>(&n))?;
             }
 
             _ => {
                 let caps = ROUTER_RE.captures(&kernel_name)?;
                 domain = caps[1].parse().unwrap_or(0);
                 route = util::parse_hex::<u64>(&caps[2]).unwrap_or(0);
-                key = udev
-                    .attribute_value("key")
-                    .and_then(|n| n.to_str())
-                    .map(String::from);
+                key = attrs.attribute("key");
             }
         }
 
-        let syspath = udev.syspath().to_path_buf();
+        let syspath = attrs.syspath();
 
         Some(Self::new(
             kernel_name,
@@ -662,6 +769,7 @@ impl Device {
             security_level,
             iommu,
             deauthorization,
+            boot_acl,
             usb4_version,
             rx_speed,
             rx_lanes,
@@ -730,6 +838,37 @@ pub enum Address {
     Service { domain: u8, route: u64, index: u8 },
 }
 
+impl Address {
+    /// Returns `true` if `device` is the one addressed.
+    ///
+    /// Matches the same way as [`find_devices()`] does with `udev`. Addresses that do not refer to
+    /// a device on their own (adapters and services) match everything.
+    pub(crate) fn matches(&self, device: &Device) -> bool {
+        match *self {
+            Self::Domain { domain } => device.is_domain() && device.domain == domain as u32,
+            Self::Router { domain, route } => {
+                device.is_router() && device.domain == domain as u32 && device.route == route
+            }
+            Self::Xdomain { domain, route } => {
+                device.is_xdomain() && device.domain == domain as u32 && device.route == route
+            }
+            Self::Retimer {
+                domain,
+                route,
+                adapter,
+                index,
+            } => {
+                device.kind == Kind::Retimer
+                    && device.domain == domain as u32
+                    && device.route == route
+                    && device.adapter_num == adapter
+                    && device.index == index
+            }
+            _ => true,
+        }
+    }
+}
+
 /// Configuration spaces.
 ///
 /// These are the possible configuration spaces defined in the USB4 specification. If the
@@ -746,6 +885,8 @@ pub enum ConfigSpace {
     Router,
     /// Adapter counters config space.
     Counters,
+    /// Sideband register space of a USB4 port or retimer.
+    Sideband,
 }
 
 impl From<u8> for ConfigSpace {
@@ -767,6 +908,7 @@ impl Display for ConfigSpace {
             Self::Adapter => "Adapter",
             Self::Router => "Router",
             Self::Counters => "Counters",
+            Self::Sideband => "Sideband",
             _ => panic!("unknown config space"),
         };
         write!(f, "{s}")
@@ -892,6 +1034,13 @@ impl Serialize for Pdf {
 /// # }
 /// ```
 pub fn find_devices(address: Option<&Address>) -> io::Result<Vec<Device>> {
+    if let Some(snapshot) = snapshot::active() {
+        return Ok(snapshot.find_devices(address));
+    }
+    if sysfs::root().is_some() {
+        return sysfs::find_devices(address);
+    }
+
     let mut enumerator = udev::Enumerator::new()?;
 
     enumerator.match_subsystem("thunderbolt")?;
@@ -0,0 +1,300 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Access to the USB4 DisplayPort Configuration Data (DPCD) registers.
+//!
+//! The DisplayPort AUX channel of a DPTX is exposed by the kernel through `/dev/drm_dp_auxN`
+//! character devices where the file offset is the DPCD address. The USB4 specific registers
+//! start at [`USB4_DPCD_BASE`] and are used for instance to control the DisplayPort bandwidth
+//! allocation mode from the graphics driver side.
+//!
+//! Any file can be used in place of the AUX device so this can be run against a DPCD dump too.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::dpcd::Dpcd;
+//!
+//! # fn main() -> io::Result<()> {
+//! let dpcd = Dpcd::with_aux(0)?;
+//!
+//! if dpcd.bw_mode_enabled()? {
+//!     println!("Estimated bandwidth: {} Mb/s", dpcd.estimated_bw()?);
+//!     // Request 10 Gb/s for the DisplayPort tunnel.
+//!     dpcd.request_bw(10000)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Result},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    debugfs::{self, Register},
+    sysfs,
+};
+
+/// DPCD address where the USB4 registers start.
+pub const USB4_DPCD_BASE: u32 = 0xe0000;
+/// Number of bytes in the USB4 DPCD register block.
+pub const USB4_DPCD_SIZE: usize = 0x40;
+
+const DP_TUNNELING_CAPABILITIES: u32 = 0xe000d;
+const DP_TUNNELING_SUPPORT: u8 = 1 << 0;
+const DP_IN_BW_ALLOCATION_MODE_SUPPORT: u8 = 1 << 7;
+const DP_IN_ADAPTER_INFO: u32 = 0xe000e;
+const DP_IN_ADAPTER_NUMBER_MASK: u8 = 0x7f;
+const DP_USB4_DRIVER_ID: u32 = 0xe000f;
+const DP_USB4_DRIVER_ID_MASK: u8 = 0x0f;
+const DP_IN_ADAPTER_TUNNEL_INFORMATION: u32 = 0xe0021;
+const DP_GROUP_ID_MASK: u8 = 0x07;
+const DP_BW_GRANULARITY: u32 = 0xe0022;
+const DP_BW_GRANULARITY_MASK: u8 = 0x03;
+const DP_ESTIMATED_BW: u32 = 0xe0023;
+const DP_ALLOCATED_BW: u32 = 0xe0024;
+const DP_TUNNELING_STATUS: u32 = 0xe0025;
+const DP_BW_REQUEST_FAILED: u8 = 1 << 0;
+const DP_BW_REQUEST_SUCCEEDED: u8 = 1 << 1;
+const DP_DPTX_BW_ALLOCATION_MODE_CONTROL: u32 = 0xe0030;
+const DP_DISPLAY_DRIVER_BW_ALLOCATION_MODE_ENABLE: u8 = 1 << 7;
+const DP_REQUEST_BW: u32 = 0xe0031;
+
+/// Result of the last bandwidth request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestStatus {
+    /// No request has completed since the status was last cleared.
+    None,
+    /// Connection manager granted the requested bandwidth.
+    Succeeded,
+    /// Connection manager could not grant the requested bandwidth.
+    Failed,
+}
+
+/// DPCD of a single DPTX.
+pub struct Dpcd {
+    path: PathBuf,
+    file: File,
+}
+
+impl Dpcd {
+    /// Opens DPCD through `path`.
+    ///
+    /// This is normally `/dev/drm_dp_auxN` but can be any file. If the file cannot be opened for
+    /// writing it is opened read-only.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => File::open(path)?,
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    /// Opens DPCD through AUX character device `/dev/drm_dp_aux<aux>`.
+    pub fn with_aux(aux: u32) -> Result<Self> {
+        Self::open(&sysfs::path_buf(&format!("/dev/drm_dp_aux{aux}")))
+    }
+
+    /// Returns path of the file used to access the DPCD.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads `len` bytes starting from DPCD `address`.
+    pub fn read(&self, address: u32, len: usize) -> Result<Vec<u8>> {
+        let mut data = vec![0; len];
+        self.file.read_exact_at(&mut data, address.into())?;
+        Ok(data)
+    }
+
+    /// Writes `data` starting from DPCD `address`.
+    pub fn write(&self, address: u32, data: &[u8]) -> Result<()> {
+        self.file.write_all_at(data, address.into())
+    }
+
+    fn read_byte(&self, address: u32) -> Result<u8> {
+        Ok(self.read(address, 1)?[0])
+    }
+
+    fn write_byte(&self, address: u32, value: u8) -> Result<()> {
+        self.write(address, &[value])
+    }
+
+    /// Reads the whole USB4 DPCD register block.
+    ///
+    /// Each byte is returned as separate [`Register`] and the offset is relative to
+    /// [`USB4_DPCD_BASE`]. Registers that are known have their name and fields filled.
+    pub fn read_registers(&self) -> Result<Vec<Register>> {
+        let data = self.read(USB4_DPCD_BASE, USB4_DPCD_SIZE)?;
+        let regs = data
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Register::new(i as u16, i as u16, 0, 0, (*value).into()))
+            .collect();
+
+        Ok(debugfs::dpcd_with_metadata(regs))
+    }
+
+    /// Returns `true` if the DisplayPort link is tunneled through USB4.
+    pub fn tunneling_supported(&self) -> Result<bool> {
+        Ok(self.read_byte(DP_TUNNELING_CAPABILITIES)? & DP_TUNNELING_SUPPORT != 0)
+    }
+
+    /// Returns number of the DP IN adapter the DPTX is connected to.
+    pub fn dp_in_adapter(&self) -> Result<u8> {
+        Ok(self.read_byte(DP_IN_ADAPTER_INFO)? & DP_IN_ADAPTER_NUMBER_MASK)
+    }
+
+    /// Returns ID of the USB4 connection manager that handles the DP IN adapter. This matches
+    /// `CM_ID` in `ADP_DP_CS_2` of the adapter.
+    pub fn driver_id(&self) -> Result<u8> {
+        Ok(self.read_byte(DP_USB4_DRIVER_ID)? & DP_USB4_DRIVER_ID_MASK)
+    }
+
+    /// Returns `true` if the DP IN adapter supports bandwidth allocation mode.
+    pub fn bw_mode_supported(&self) -> Result<bool> {
+        Ok(self.read_byte(DP_TUNNELING_CAPABILITIES)? & DP_IN_BW_ALLOCATION_MODE_SUPPORT != 0)
+    }
+
+    /// Returns `true` if the graphics driver has enabled bandwidth allocation mode.
+    pub fn bw_mode_enabled(&self) -> Result<bool> {
+        Ok(self.read_byte(DP_DPTX_BW_ALLOCATION_MODE_CONTROL)?
+            & DP_DISPLAY_DRIVER_BW_ALLOCATION_MODE_ENABLE
+            != 0)
+    }
+
+    /// Enables or disables bandwidth allocation mode on the DPTX side.
+    pub fn set_bw_mode(&self, enable: bool) -> Result<()> {
+        let mut value = self.read_byte(DP_DPTX_BW_ALLOCATION_MODE_CONTROL)?;
+        if enable {
+            value |= DP_DISPLAY_DRIVER_BW_ALLOCATION_MODE_ENABLE;
+        } else {
+            value &= !DP_DISPLAY_DRIVER_BW_ALLOCATION_MODE_ENABLE;
+        }
+        self.write_byte(DP_DPTX_BW_ALLOCATION_MODE_CONTROL, value)
+    }
+
+    /// Returns bandwidth allocation group ID of the tunnel.
+    pub fn group_id(&self) -> Result<u8> {
+        Ok(self.read_byte(DP_IN_ADAPTER_TUNNEL_INFORMATION)? & DP_GROUP_ID_MASK)
+    }
+
+    /// Returns bandwidth granularity in Mb/s.
+    ///
+    /// Returns [`ErrorKind::InvalidData`] if the granularity value is reserved.
+    pub fn granularity(&self) -> Result<u32> {
+        match self.read_byte(DP_BW_GRANULARITY)? & DP_BW_GRANULARITY_MASK {
+            0 => Ok(250),
+            1 => Ok(500),
+            2 => Ok(1000),
+            _ => Err(Error::new(ErrorKind::InvalidData, "reserved granularity")),
+        }
+    }
+
+    /// Returns estimated bandwidth of the tunnel in Mb/s.
+    pub fn estimated_bw(&self) -> Result<u32> {
+        Ok(u32::from(self.read_byte(DP_ESTIMATED_BW)?) * self.granularity()?)
+    }
+
+    /// Returns bandwidth allocated for the tunnel in Mb/s.
+    pub fn allocated_bw(&self) -> Result<u32> {
+        Ok(u32::from(self.read_byte(DP_ALLOCATED_BW)?) * self.granularity()?)
+    }
+
+    /// Returns bandwidth last requested in Mb/s.
+    pub fn requested_bw(&self) -> Result<u32> {
+        Ok(u32::from(self.read_byte(DP_REQUEST_BW)?) * self.granularity()?)
+    }
+
+    /// Returns status of the last bandwidth request.
+    pub fn request_status(&self) -> Result<RequestStatus> {
+        let status = self.read_byte(DP_TUNNELING_STATUS)?;
+        if status & DP_BW_REQUEST_FAILED != 0 {
+            Ok(RequestStatus::Failed)
+        } else if status & DP_BW_REQUEST_SUCCEEDED != 0 {
+            Ok(RequestStatus::Succeeded)
+        } else {
+            Ok(RequestStatus::None)
+        }
+    }
+
+    /// Requests `bw` Mb/s for the tunnel.
+    ///
+    /// The value is rounded up to the next multiple of the granularity. Previous request status
+    /// is cleared first so the result can be read with [`request_status()`](Self::request_status)
+    /// once the connection manager has processed the request.
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if `bw` cannot be represented with the current
+    /// granularity.
+    pub fn request_bw(&self, bw: u32) -> Result<()> {
+        let value: u8 = bw
+            .div_ceil(self.granularity()?)
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "bandwidth too large"))?;
+
+        // Status bits are write 1 to clear.
+        let status =
+            self.read_byte(DP_TUNNELING_STATUS)? & (DP_BW_REQUEST_FAILED | DP_BW_REQUEST_SUCCEEDED);
+        if status != 0 {
+            self.write_byte(DP_TUNNELING_STATUS, status)?;
+        }
+        self.write_byte(DP_REQUEST_BW, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debugfs::{BitFields, Name};
+    use crate::test_util::TempDir;
+    use std::fs;
+
+    #[test]
+    fn file() {
+        let dir = TempDir::new("dpcd");
+        let path = dir.join("dpcd");
+        let mut data = vec![0; USB4_DPCD_BASE as usize + USB4_DPCD_SIZE];
+        let base = USB4_DPCD_BASE as usize;
+        data[base + 0x0d] = 0x81;
+        data[base + 0x21] = 0x02;
+        data[base + 0x22] = 0x01;
+        data[base + 0x23] = 40;
+        data[base + 0x24] = 30;
+        data[base + 0x25] = 0x02;
+        fs::write(&path, &data).unwrap();
+
+        let dpcd = Dpcd::open(&path).unwrap();
+        assert!(dpcd.bw_mode_supported().unwrap());
+        assert!(!dpcd.bw_mode_enabled().unwrap());
+        assert_eq!(dpcd.group_id().unwrap(), 2);
+        assert_eq!(dpcd.granularity().unwrap(), 500);
+        assert_eq!(dpcd.estimated_bw().unwrap(), 20000);
+        assert_eq!(dpcd.allocated_bw().unwrap(), 15000);
+        assert_eq!(dpcd.request_status().unwrap(), RequestStatus::Succeeded);
+
+        dpcd.set_bw_mode(true).unwrap();
+        assert!(dpcd.bw_mode_enabled().unwrap());
+        dpcd.request_bw(10001).unwrap();
+        assert_eq!(dpcd.requested_bw().unwrap(), 10500);
+        assert!(dpcd.request_bw(1000000).is_err());
+
+        let regs = dpcd.read_registers().unwrap();
+        assert_eq!(regs.len(), USB4_DPCD_SIZE);
+        let reg = &regs[0x22];
+        assert_eq!(reg.name(), Some("DP_BW_GRANULARITY"));
+        assert_eq!(reg.field("GR"), 1);
+        let reg = &regs[0x30];
+        assert_eq!(reg.name(), Some("DP_DPTX_BW_ALLOCATION_MODE_CONTROL"));
+        assert!(reg.flag("DPME"));
+    }
+}
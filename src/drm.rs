@@ -0,0 +1,213 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Mapping between DisplayPort IN adapters and DRM connectors.
+//!
+//! Graphics drivers expose their DisplayPort connectors under `/sys/class/drm` and the AUX
+//! channel of each connector as `/dev/drm_dp_auxN`. When the DisplayPort link is tunneled over
+//! USB4 the DPCD of the connector tells which DP IN adapter it is connected to and the ID of the
+//! connection manager handling it. This is used to find the DRM connector for a DP IN adapter
+//! with [`Adapter::dp_connector()`](crate::debugfs::Adapter::dp_connector()).
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::{Address, drm};
+//!
+//! # fn main() -> io::Result<()> {
+//! let connectors = drm::connectors()?;
+//!
+//! if let Some(mut device) = tbtools::find_device(&Address::Router { domain: 0, route: 0 })? {
+//!     device.read_adapters()?;
+//!
+//!     for adapter in device.adapters().unwrap() {
+//!         if let Some(connector) = adapter.dp_connector(&connectors) {
+//!             println!("{}: {}", adapter.adapter(), connector.name());
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fs,
+    io::{ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+use crate::{dpcd::Dpcd, sysfs};
+
+const SYSFS_CLASS_DRM: &str = "/sys/class/drm";
+const DEV: &str = "/dev";
+const DRM_DP_AUX: &str = "drm_dp_aux";
+
+/// DisplayPort connector of a graphics device.
+#[derive(Clone, Debug)]
+pub struct Connector {
+    name: String,
+    status: Option<String>,
+    aux: Option<u32>,
+    aux_path: Option<PathBuf>,
+    driver_id: Option<u8>,
+    adapter: Option<u8>,
+}
+
+impl Connector {
+    fn new(syspath: &Path, dev: &Path) -> Option<Self> {
+        let name = syspath.file_name()?.to_str()?.to_string();
+        let status = fs::read_to_string(syspath.join("status"))
+            .ok()
+            .map(|s| s.trim_end().to_string());
+
+        // AUX device is a child of the connector.
+        let aux = fs::read_dir(syspath).ok()?.find_map(|entry| {
+            entry
+                .ok()?
+                .file_name()
+                .to_str()?
+                .strip_prefix(DRM_DP_AUX)?
+                .parse::<u32>()
+                .ok()
+        });
+        let aux_path = aux.map(|aux| dev.join(format!("{DRM_DP_AUX}{aux}")));
+
+        let mut connector = Self {
+            name,
+            status,
+            aux,
+            aux_path,
+            driver_id: None,
+            adapter: None,
+        };
+
+        // DPCD can only be read when there is something connected.
+        if connector.is_connected()
+            && let Some(aux_path) = &connector.aux_path
+            && let Ok(dpcd) = Dpcd::open(aux_path)
+            && dpcd.tunneling_supported().unwrap_or(false)
+        {
+            connector.driver_id = dpcd.driver_id().ok();
+            connector.adapter = dpcd.dp_in_adapter().ok();
+        }
+
+        Some(connector)
+    }
+
+    /// Returns name of the connector such as `card0-DP-1`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` if a monitor is connected.
+    pub fn is_connected(&self) -> bool {
+        self.status.as_deref() == Some("connected")
+    }
+
+    /// Returns number `N` of the `/dev/drm_dp_auxN` device of this connector.
+    pub fn aux(&self) -> Option<u32> {
+        self.aux
+    }
+
+    /// Returns path to the AUX device of this connector.
+    pub fn aux_path(&self) -> Option<&Path> {
+        self.aux_path.as_deref()
+    }
+
+    /// Returns connection manager ID read from the DPCD if the link is tunneled.
+    pub fn driver_id(&self) -> Option<u8> {
+        self.driver_id
+    }
+
+    /// Returns number of the DP IN adapter read from the DPCD if the link is tunneled.
+    pub fn adapter(&self) -> Option<u8> {
+        self.adapter
+    }
+}
+
+fn connectors_in(class: &Path, dev: &Path) -> Result<Vec<Connector>> {
+    let entries = match fs::read_dir(class) {
+        Ok(entries) => entries,
+        // No graphics at all.
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut connectors = Vec::new();
+
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+
+        // Only DisplayPort connectors have AUX channel.
+        if !name.to_str().is_some_and(|n| n.contains("-DP-")) {
+            continue;
+        }
+
+        let syspath = fs::canonicalize(entry.path())?;
+        if let Some(connector) = Connector::new(&syspath, dev) {
+            connectors.push(connector);
+        }
+    }
+
+    connectors.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(connectors)
+}
+
+/// Returns all DisplayPort connectors of the system.
+pub fn connectors() -> Result<Vec<Connector>> {
+    connectors_in(&sysfs::path_buf(SYSFS_CLASS_DRM), &sysfs::path_buf(DEV))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dpcd::USB4_DPCD_BASE;
+    use crate::test_util::TempDir;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn connectors() {
+        let root = TempDir::new("drm");
+        let card = root.join("sys/devices/pci0000:00/0000:00:02.0/drm/card0");
+        let class = root.join("sys/class/drm");
+        let dev = root.join("dev");
+
+        for (name, status, aux) in [
+            ("card0-DP-1", "connected", 0),
+            ("card0-DP-2", "disconnected", 1),
+        ] {
+            fs::create_dir_all(card.join(name).join(format!("drm_dp_aux{aux}"))).unwrap();
+            fs::write(card.join(name).join("status"), format!("{status}\n")).unwrap();
+        }
+        fs::create_dir_all(card.join("card0-eDP-1")).unwrap();
+        fs::create_dir_all(&class).unwrap();
+        for name in ["card0-DP-1", "card0-DP-2", "card0-eDP-1"] {
+            symlink(card.join(name), class.join(name)).unwrap();
+        }
+
+        // Tunneled through DP IN adapter 10 of connection manager 1.
+        let mut dpcd = vec![0; USB4_DPCD_BASE as usize + 0x10];
+        dpcd[USB4_DPCD_BASE as usize + 0x0d] = 0x01;
+        dpcd[USB4_DPCD_BASE as usize + 0x0e] = 10;
+        dpcd[USB4_DPCD_BASE as usize + 0x0f] = 1;
+        fs::create_dir_all(&dev).unwrap();
+        fs::write(dev.join("drm_dp_aux0"), &dpcd).unwrap();
+
+        let connectors = connectors_in(&class, &dev).unwrap();
+        assert_eq!(connectors.len(), 2);
+
+        assert_eq!(connectors[0].name(), "card0-DP-1");
+        assert!(connectors[0].is_connected());
+        assert_eq!(connectors[0].aux(), Some(0));
+        assert_eq!(connectors[0].driver_id(), Some(1));
+        assert_eq!(connectors[0].adapter(), Some(10));
+
+        assert_eq!(connectors[1].name(), "card0-DP-2");
+        assert!(!connectors[1].is_connected());
+        assert_eq!(connectors[1].aux(), Some(1));
+        assert_eq!(connectors[1].adapter(), None);
+    }
+}
@@ -85,7 +85,8 @@ const DROM_TMU_RATE_HIFI: u8 = 0x1;
 const DROM_TMU_MODE_LOWRES: u8 = 0x2;
 
 /// Possible modes of [`Tmu`](DromEntry::Tmu) generic entry.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TmuMode {
     Unknown = -1,
     /// Preferred TMU mode is Off.
@@ -97,7 +98,8 @@ pub enum TmuMode {
 }
 
 /// Possible rates of [`Tmu`](DromEntry::Tmu) generic entry.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TmuRate {
     Unknown,
     /// Preferred TMU refresh rate is HiFi.
@@ -109,7 +111,7 @@ pub enum TmuRate {
 /// USB Port Mapping generic entry.
 ///
 /// Required for USB4 hubs and standalone add-in-card USB4 hosts.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Usb3PortMap {
     /// Downstream USB 3 port number of the internal SuperSpeed Plus host or hub.
     pub usb3_port_num: u8,
@@ -128,7 +130,8 @@ pub struct Usb3PortMap {
 }
 
 /// Value containing the preferred single data path to be established.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SingleDataPathPreference {
     /// PCIe tunneling is preferred.
     PcieTunneling,
@@ -138,7 +141,8 @@ pub enum SingleDataPathPreference {
 }
 
 /// Specified the record rank type.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RankType {
     /// Power rank type.
     Power,
@@ -148,7 +152,7 @@ pub enum RankType {
 }
 
 /// Single ranking record.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
 pub struct DptxRank {
     /// Type of the record.
     pub rank_type: RankType,
@@ -157,7 +161,8 @@ pub struct DptxRank {
 }
 
 /// All known entry types.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DromEntry<'a> {
     /// Entry is not known.
     Unknown(&'a [u8]),
@@ -627,6 +632,37 @@ impl Drom {
         self.version() < 3
     }
 
+    /// Returns vendor ID of the device.
+    ///
+    /// Thunderbolt 3 compatible DROMs carry this in the header, others in the product descriptor
+    /// entry.
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.ids().map(|(vendor, _)| vendor)
+    }
+
+    /// Returns model ID of the device.
+    ///
+    /// Thunderbolt 3 compatible DROMs carry this in the header, others in the product descriptor
+    /// entry.
+    pub fn model_id(&self) -> Option<u16> {
+        self.ids().map(|(_, model)| model)
+    }
+
+    fn ids(&self) -> Option<(u16, u16)> {
+        if self.is_tb3_compatible() {
+            let vendor = u16::from_le_bytes(<[u8; 2]>::try_from(&self.bytes[16..=17]).unwrap());
+            let model = u16::from_le_bytes(<[u8; 2]>::try_from(&self.bytes[18..=19]).unwrap());
+            return Some((vendor, model));
+        }
+
+        self.entries().find_map(|entry| match entry {
+            DromEntry::ProductDescriptor {
+                vendor, product, ..
+            } => Some((vendor, product)),
+            _ => None,
+        })
+    }
+
     /// Produces an iterator over the [`DromEntries`] of the DROM.
     pub fn entries(&self) -> DromEntries<'_> {
         DromEntries::new(self.start, &self.bytes, &self.adapters)
@@ -995,4 +1031,17 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn ids() {
+        // USB4 DROM has the IDs in the product descriptor entry.
+        let drom = Drom::parse(&HOST_DROM, &[]).unwrap();
+        assert_eq!(drom.vendor_id(), Some(0x8087));
+        assert_eq!(drom.model_id(), Some(0x7eb2));
+
+        // Thunderbolt 3 compatible DROM has them in the header.
+        let drom = Drom::parse(&DEVICE_DROM, &[]).unwrap();
+        assert_eq!(drom.vendor_id(), Some(0x8087));
+        assert_eq!(drom.model_id(), Some(0x1234));
+    }
 }
@@ -0,0 +1,536 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Export of the topology and tunnels as a graph.
+//!
+//! [`Graph`] collects routers, XDomain peers, retimers and the USB4 links between them from a
+//! [`Topology`], and optionally the [`Tunnels`](Tunnel) running over them. The result can be
+//! rendered as [Graphviz] DOT or [Mermaid] flowchart.
+//!
+//! Links are labeled with the link speed and width. Each hop of a tunnel path is drawn as an edge
+//! between the routers labeled with the adapters and HopIDs used.
+//!
+//! [Graphviz]: https://graphviz.org
+//! [Mermaid]: https://mermaid.js.org
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::{
+//!     graph::{Format, Graph},
+//!     topology::Topology,
+//! };
+//!
+//! # fn main() -> io::Result<()> {
+//! let topology = Topology::read()?;
+//! let mut graph = Graph::new();
+//!
+//! graph.add_topology(&topology);
+//! print!("{}", graph.render(Format::Dot));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    Device,
+    debugfs::Adapter,
+    topology::Topology,
+    tunnel::{self, Tunnel},
+};
+
+/// Output format of the graph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Graphviz DOT.
+    Dot,
+    /// Mermaid flowchart.
+    Mermaid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NodeKind {
+    Router,
+    Xdomain,
+    Retimer,
+    Adapter,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    id: String,
+    label: Vec<String>,
+    kind: NodeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EdgeKind {
+    Link,
+    Attach,
+    Tunnel(tunnel::Type),
+}
+
+#[derive(Clone, Debug)]
+struct Edge {
+    from: String,
+    to: String,
+    label: Option<String>,
+    kind: EdgeKind,
+}
+
+/// Graph of devices, links and tunnels.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+fn device_id(device: &Device) -> String {
+    match device.kind() {
+        crate::Kind::Retimer => format!(
+            "rt{}_{:x}_{}_{}",
+            device.domain_index(),
+            device.route(),
+            device.adapter_num(),
+            device.index()
+        ),
+        crate::Kind::Xdomain => format!("x{}_{:x}", device.domain_index(), device.route()),
+        _ => format!("r{}_{:x}", device.domain_index(), device.route()),
+    }
+}
+
+fn adapter_id(device: &Device, adapter: u8) -> String {
+    format!("a{}_{:x}_{adapter}", device.domain_index(), device.route())
+}
+
+fn tunnel_color(kind: tunnel::Type) -> &'static str {
+    match kind {
+        tunnel::Type::Usb3 | tunnel::Type::Usb3GenT => "blue",
+        tunnel::Type::DisplayPort => "darkgreen",
+        tunnel::Type::Pcie => "red",
+        tunnel::Type::Dma => "orange",
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+impl Graph {
+    /// Creates an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_node(&mut self, id: String, label: Vec<String>, kind: NodeKind) {
+        if !self.nodes.iter().any(|n| n.id == id) {
+            self.nodes.push(Node { id, label, kind });
+        }
+    }
+
+    fn add_edge(&mut self, from: String, to: String, label: Option<String>, kind: EdgeKind) {
+        self.edges.push(Edge {
+            from,
+            to,
+            label,
+            kind,
+        });
+    }
+
+    fn add_device(&mut self, device: &Device) -> String {
+        let id = device_id(device);
+        let (mut label, kind) = match device.kind() {
+            crate::Kind::Retimer => (
+                vec![format!(
+                    "Retimer {}:{}",
+                    device.adapter_num(),
+                    device.index()
+                )],
+                NodeKind::Retimer,
+            ),
+            crate::Kind::Xdomain => (
+                vec![format!(
+                    "XDomain {} Route {:x}",
+                    device.domain_index(),
+                    device.route()
+                )],
+                NodeKind::Xdomain,
+            ),
+            _ => (
+                vec![format!(
+                    "Domain {} Route {:x}",
+                    device.domain_index(),
+                    device.route()
+                )],
+                NodeKind::Router,
+            ),
+        };
+
+        let name: Vec<_> = [device.vendor_name(), device.device_name()]
+            .into_iter()
+            .flatten()
+            .collect();
+        if name.is_empty() {
+            label.push(format!("{:04x}:{:04x}", device.vendor(), device.device()));
+        } else {
+            label.push(name.join(" "));
+        }
+
+        self.add_node(id.clone(), label, kind);
+        id
+    }
+
+    fn add_adapter(&mut self, device: &Device, adapter: &Adapter) -> String {
+        let id = adapter_id(device, adapter.adapter());
+        if !self.nodes.iter().any(|n| n.id == id) {
+            let router = self.add_device(device);
+            self.add_node(
+                id.clone(),
+                vec![format!("{} {}", adapter.adapter(), adapter.kind())],
+                NodeKind::Adapter,
+            );
+            self.add_edge(router, id.clone(), None, EdgeKind::Attach);
+        }
+        id
+    }
+
+    /// Adds routers, XDomain peers, retimers and the links between them from `topology`.
+    pub fn add_topology(&mut self, topology: &Topology) {
+        for device in topology.devices() {
+            if device.is_router() || device.is_xdomain() {
+                self.add_device(device);
+            }
+        }
+
+        for link in topology.links() {
+            let mut from = device_id(topology.link_parent(link));
+            for retimer in topology.link_retimers(link) {
+                let to = self.add_device(retimer);
+                self.add_edge(from, to.clone(), None, EdgeKind::Link);
+                from = to;
+            }
+
+            let mut label = format!("Adapter {}", link.adapter());
+            if let Some(speed) = link.rx_speed()
+                && let Some(lanes) = link.rx_lanes()
+            {
+                write!(label, ", {} Gb/s ({lanes} x {speed})", speed * lanes).unwrap();
+            }
+            if link.is_bonded() {
+                label.push_str(", bonded");
+            }
+
+            self.add_edge(
+                from,
+                device_id(topology.link_child(link)),
+                Some(label),
+                EdgeKind::Link,
+            );
+        }
+    }
+
+    /// Adds `tunnels` to the graph.
+    ///
+    /// The source and destination adapters of each path are added as nodes and each hop between
+    /// two routers is added as an edge labeled with the output adapter and HopID of the first
+    /// router and the input adapter and HopID of the second.
+    pub fn add_tunnels(&mut self, tunnels: &[Tunnel]) {
+        for tunnel in tunnels {
+            let kind = EdgeKind::Tunnel(tunnel.kind());
+
+            for path in tunnel.paths() {
+                let hops = path.hops();
+                let Some(first) = hops.first() else {
+                    continue;
+                };
+
+                let src = self.add_adapter(path.src_device(), path.src_adapter());
+                let router = self.add_device(first.device());
+                self.add_edge(
+                    src,
+                    router,
+                    Some(format!("{} {}", path.name(), first.entry().in_hop())),
+                    kind,
+                );
+
+                for pair in hops.windows(2) {
+                    let (out, next) = (pair[0].entry(), pair[1].entry());
+                    let from = self.add_device(pair[0].device());
+                    let to = self.add_device(pair[1].device());
+                    self.add_edge(
+                        from,
+                        to,
+                        Some(format!(
+                            "{} {}/{} → {}/{}",
+                            path.name(),
+                            out.out_adapter(),
+                            out.out_hop(),
+                            next.in_adapter(),
+                            next.in_hop()
+                        )),
+                        kind,
+                    );
+                }
+
+                let last = hops.last().unwrap();
+                let router = self.add_device(last.device());
+                let dst = self.add_adapter(path.dst_device(), path.dst_adapter());
+                self.add_edge(
+                    router,
+                    dst,
+                    Some(format!("{} {}", path.name(), last.entry().out_hop())),
+                    kind,
+                );
+            }
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph usb4 {\n    node [shape=box];\n");
+
+        for node in &self.nodes {
+            let label: Vec<_> = node.label.iter().map(|l| escape_dot(l)).collect();
+            let label = label.join("\\n");
+            let attrs = match node.kind {
+                NodeKind::Router => "",
+                NodeKind::Xdomain => ", style=dashed",
+                NodeKind::Retimer => ", shape=diamond",
+                NodeKind::Adapter => ", shape=ellipse, fontsize=10",
+            };
+            writeln!(out, "    {} [label=\"{label}\"{attrs}];", node.id).unwrap();
+        }
+
+        for edge in &self.edges {
+            let mut attrs = Vec::new();
+            if let Some(label) = &edge.label {
+                attrs.push(format!("label=\"{}\"", escape_dot(label)));
+            }
+            match edge.kind {
+                EdgeKind::Link => attrs.push(String::from("dir=none, penwidth=2")),
+                EdgeKind::Attach => attrs.push(String::from("dir=none, style=dotted")),
+                EdgeKind::Tunnel(kind) => {
+                    attrs.push(format!("color={0}, fontcolor={0}", tunnel_color(kind)))
+                }
+            }
+            writeln!(
+                out,
+                "    {} -> {} [{}];",
+                edge.from,
+                edge.to,
+                attrs.join(", ")
+            )
+            .unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+
+        for node in &self.nodes {
+            let label = escape_mermaid(&node.label.join("<br/>"));
+            let shape = match node.kind {
+                NodeKind::Router | NodeKind::Xdomain => format!("[\"{label}\"]"),
+                NodeKind::Retimer => format!("{{\"{label}\"}}"),
+                NodeKind::Adapter => format!("([\"{label}\"])"),
+            };
+            writeln!(out, "    {}{shape}", node.id).unwrap();
+        }
+
+        for edge in &self.edges {
+            let arrow = match edge.kind {
+                EdgeKind::Link => "---",
+                EdgeKind::Attach => "-.-",
+                EdgeKind::Tunnel(_) => "-->",
+            };
+            match &edge.label {
+                Some(label) => writeln!(
+                    out,
+                    "    {} {arrow}|\"{}\"| {}",
+                    edge.from,
+                    escape_mermaid(label),
+                    edge.to
+                ),
+                None => writeln!(out, "    {} {arrow} {}", edge.from, edge.to),
+            }
+            .unwrap();
+        }
+
+        for (i, edge) in self.edges.iter().enumerate() {
+            if let EdgeKind::Tunnel(kind) = edge.kind {
+                writeln!(out, "    linkStyle {i} stroke:{}", tunnel_color(kind)).unwrap();
+            }
+        }
+
+        out
+    }
+
+    /// Renders the graph in given `format`.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.to_dot(),
+            Format::Mermaid => self.to_mermaid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        debugfs::Type,
+        test_util::{adapter, hop, router, router_with, upstream_lane},
+    };
+
+    #[test]
+    fn render() {
+        let mut graph = Graph::new();
+
+        graph.add_node(
+            String::from("r0_0"),
+            vec![
+                String::from("Domain 0 Route 0"),
+                String::from("Intel \"Gen14\""),
+            ],
+            NodeKind::Router,
+        );
+        graph.add_node(
+            String::from("rt0_0_1_1"),
+            vec![String::from("Retimer 1:1")],
+            NodeKind::Retimer,
+        );
+        graph.add_node(
+            String::from("r0_1"),
+            vec![String::from("Domain 0 Route 1")],
+            NodeKind::Router,
+        );
+        // Adding the same node again is ignored.
+        graph.add_node(String::from("r0_1"), Vec::new(), NodeKind::Router);
+        graph.add_edge(
+            String::from("r0_0"),
+            String::from("rt0_0_1_1"),
+            None,
+            EdgeKind::Link,
+        );
+        graph.add_edge(
+            String::from("rt0_0_1_1"),
+            String::from("r0_1"),
+            Some(String::from("Adapter 1, 40 Gb/s (2 x 20), bonded")),
+            EdgeKind::Link,
+        );
+        graph.add_edge(
+            String::from("r0_0"),
+            String::from("r0_1"),
+            Some(String::from("PCIe Down 1/8 → 3/8")),
+            EdgeKind::Tunnel(tunnel::Type::Pcie),
+        );
+
+        let dot = graph.render(Format::Dot);
+        assert!(dot.starts_with("digraph usb4 {\n"));
+        assert!(dot.contains("r0_0 [label=\"Domain 0 Route 0\\nIntel \\\"Gen14\\\"\"];"));
+        assert!(dot.contains("rt0_0_1_1 [label=\"Retimer 1:1\", shape=diamond];"));
+        assert!(dot.contains("r0_0 -> rt0_0_1_1 [dir=none, penwidth=2];"));
+        assert!(
+            dot.contains("r0_0 -> r0_1 [label=\"PCIe Down 1/8 → 3/8\", color=red, fontcolor=red];")
+        );
+        assert!(dot.ends_with("}\n"));
+
+        let mermaid = graph.render(Format::Mermaid);
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("r0_0[\"Domain 0 Route 0<br/>Intel #quot;Gen14#quot;\"]"));
+        assert!(mermaid.contains("rt0_0_1_1{\"Retimer 1:1\"}"));
+        assert!(mermaid.contains("rt0_0_1_1 ---|\"Adapter 1, 40 Gb/s (2 x 20), bonded\"| r0_1"));
+        assert!(mermaid.contains("linkStyle 2 stroke:red"));
+        assert_eq!(mermaid.matches("r0_1[").count(), 1);
+    }
+
+    #[test]
+    fn topology() {
+        // PCIe tunnel from the host router adapter 9 to adapter 9 of 0-1.
+        let mut lane = adapter(0, 1, Type::Lane);
+        lane.set_test_paths(&[hop(9, 9, 8)]);
+        let mut down = adapter(0, 9, Type::PcieDown);
+        down.set_test_paths(&[hop(8, 1, 9)]);
+        let host = router(0, vec![lane, down]);
+
+        let link = [
+            ("rx_speed", "20.0 Gb/s"),
+            ("rx_lanes", "2"),
+            ("tx_speed", "20.0 Gb/s"),
+            ("tx_lanes", "2"),
+        ];
+        let mut lane = upstream_lane(1, 1);
+        lane.set_test_paths(&[hop(9, 9, 8)]);
+        let mut up = adapter(1, 9, Type::PcieUp);
+        up.set_test_paths(&[hop(8, 1, 9)]);
+        let device = router_with(1, &link, vec![lane, up]);
+
+        let topology = Topology::new(vec![host, device]);
+        let tunnels = Tunnel::discover_all(topology.devices());
+        let mut graph = Graph::new();
+        graph.add_topology(&topology);
+        graph.add_tunnels(&tunnels);
+
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.id.as_str(), n.kind))
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                ("r0_0", NodeKind::Router),
+                ("r0_1", NodeKind::Router),
+                ("a0_0_9", NodeKind::Adapter),
+                ("a0_1_9", NodeKind::Adapter),
+            ]
+        );
+
+        let pcie = EdgeKind::Tunnel(tunnel::Type::Pcie);
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.kind))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("r0_0", "r0_1", EdgeKind::Link),
+                ("r0_0", "a0_0_9", EdgeKind::Attach),
+                ("a0_0_9", "r0_0", pcie),
+                ("r0_0", "r0_1", pcie),
+                ("r0_1", "a0_1_9", EdgeKind::Attach),
+                ("r0_1", "a0_1_9", pcie),
+                ("a0_1_9", "r0_1", pcie),
+                ("r0_1", "r0_0", pcie),
+                ("r0_0", "a0_0_9", pcie),
+            ]
+        );
+        assert_eq!(
+            graph.edges[0].label.as_deref(),
+            Some("Adapter 1, 40 Gb/s (2 x 20), bonded")
+        );
+
+        let dot = graph.render(Format::Dot);
+        assert!(
+            dot.contains("r0_0 -> r0_1 [label=\"PCIe Down 1/9 → 1/9\", color=red, fontcolor=red];")
+        );
+        assert!(
+            dot.contains("r0_1 -> r0_0 [label=\"PCIe Up 1/9 → 1/9\", color=red, fontcolor=red];")
+        );
+        assert_eq!(dot.matches("fontcolor=red").count(), 6);
+
+        let mermaid = graph.render(Format::Mermaid);
+        assert!(mermaid.contains("linkStyle 3 stroke:red"));
+        assert!(!mermaid.contains("linkStyle 0 "));
+        assert_eq!(mermaid.matches("stroke:red").count(), 6);
+    }
+}
@@ -0,0 +1,324 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Persistent store of keys used with the secure security level.
+//!
+//! With [`SecurityLevel::Secure`](crate::SecurityLevel::Secure) the first time a device is
+//! authorized a random key is written to it along with the authorization. Next time the device is
+//! connected, it is challenged with the same key, which only succeeds if the device still has it.
+//! [`KeyStore`] keeps these keys in files named after the [`unique_id()`](Device::unique_id())
+//! of the routers so that they survive reboots. The directory is only accessible by the owner and
+//! the key files have `0600` permissions.
+//!
+//! # Examples
+//! Authorize a device with route `1` either by challenging it with the stored key or enrolling a
+//! new one.
+//! ```no_run
+//! # use std::io;
+//! use tbtools::{Address, keystore::KeyStore};
+//!
+//! # fn main() -> io::Result<()> {
+//! let store = KeyStore::open_default();
+//! let address = Address::Router { domain: 0, route: 1 };
+//! if let Some(mut device) = tbtools::find_device(&address)? {
+//!     let authorization = store.authorize(&mut device)?;
+//!     println!("{}: {authorization}", device.kernel_name());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    env,
+    fmt::{self, Display, Write as FmtWrite},
+    fs::{self, DirBuilder, OpenOptions, Permissions},
+    io::{Error, ErrorKind, Result, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use rand::RngCore;
+
+use crate::Device;
+
+/// Directory used by [`KeyStore::open_default()`] unless [`DIR_ENV`] is set.
+pub const DEFAULT_DIR: &str = "/var/lib/tbtools/keys";
+/// Environment variable that can be used to change the default key directory.
+pub const DIR_ENV: &str = "TBTOOLS_KEY_DIR";
+
+/// Size of the key in bytes. The kernel expects it as hex string.
+const KEY_SIZE: usize = 32;
+
+/// How the device was authorized by [`KeyStore::authorize()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authorization {
+    /// New key was generated, added to the device and stored.
+    Enrolled,
+    /// Device was challenged with the stored key.
+    Challenged,
+}
+
+impl Display for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Enrolled => "enrolled",
+            Self::Challenged => "challenged",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Generates a new random key.
+pub fn generate_key() -> String {
+    let mut data = [0u8; KEY_SIZE];
+
+    rand::rng().fill_bytes(&mut data);
+
+    data.iter().fold(String::new(), |mut key, b| {
+        write!(key, "{b:02x}").unwrap();
+        key
+    })
+}
+
+fn is_valid_key(key: &str) -> bool {
+    key.len() == KEY_SIZE * 2 && key.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// File based store of keys.
+#[derive(Clone, Debug)]
+pub struct KeyStore {
+    dir: PathBuf,
+}
+
+impl KeyStore {
+    /// Creates store keeping the keys in `dir`. The directory is created when the first key is
+    /// added.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Creates store in [`DIR_ENV`] if set, and [`DEFAULT_DIR`] otherwise.
+    pub fn open_default() -> Self {
+        let dir = env::var_os(DIR_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map_or_else(|| PathBuf::from(DEFAULT_DIR), PathBuf::from);
+        Self { dir }
+    }
+
+    /// Returns directory of the store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns path of the key file for `unique_id`.
+    ///
+    /// The ID must be a UUID so that it can not point outside of the directory.
+    fn path(&self, unique_id: &str) -> Result<PathBuf> {
+        let uuid = uuid::Uuid::parse_str(unique_id).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{unique_id} is not a valid UUID"),
+            )
+        })?;
+        Ok(self.dir.join(uuid.hyphenated().to_string()))
+    }
+
+    /// Returns key stored for router with `unique_id` or `None` if there is no key.
+    pub fn get(&self, unique_id: &str) -> Result<Option<String>> {
+        match fs::read_to_string(self.path(unique_id)?) {
+            Ok(key) => Ok(Some(key.trim_end().to_string())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Stores `key` for router with `unique_id` replacing any previous key.
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if `key` is not 32 bytes as hex string.
+    pub fn insert(&self, unique_id: &str, key: &str) -> Result<()> {
+        let path = self.path(unique_id)?;
+        if !is_valid_key(key) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("key must be {} hex digits", KEY_SIZE * 2),
+            ));
+        }
+
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+        // The mode above only applies if the directory did not exist.
+        fs::set_permissions(&self.dir, Permissions::from_mode(0o700))?;
+
+        // Write to a temporary file first so that an existing key is not lost if this fails. A
+        // leftover one is removed so that the key file never inherits its permissions.
+        let tmp = path.with_extension("tmp");
+        if let Err(err) = fs::remove_file(&tmp)
+            && err.kind() != ErrorKind::NotFound
+        {
+            return Err(err);
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&tmp)?;
+        file.write_all(key.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, path)
+    }
+
+    /// Removes key of router with `unique_id`. Returns `true` if there was a key.
+    pub fn remove(&self, unique_id: &str) -> Result<bool> {
+        match fs::remove_file(self.path(unique_id)?) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns unique IDs of all routers that have a key stored.
+    pub fn unique_ids(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut ids = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            if let Some(name) = name.to_str()
+                && uuid::Uuid::parse_str(name).is_ok()
+            {
+                ids.push(name.to_string());
+            }
+        }
+        ids.sort();
+
+        Ok(ids)
+    }
+
+    /// Authorizes `device` using the secure security level.
+    ///
+    /// If there is a key stored for the device it is challenged with it, otherwise a new key is
+    /// generated, stored and then added to the device. The stored key is removed again if the
+    /// authorization fails. Unauthorized parent devices are authorized the same way first.
+    ///
+    /// Returns [`ErrorKind::Unsupported`] if the device does not support keys and
+    /// [`ErrorKind::InvalidInput`] if it is already authorized.
+    pub fn authorize(&self, device: &mut Device) -> Result<Authorization> {
+        if !device.is_router() || !device.has_key() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "device does not support keys",
+            ));
+        }
+        if device.authorized().unwrap_or(false) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "device is already authorized",
+            ));
+        }
+        let unique_id = device
+            .unique_id()
+            .ok_or_else(|| Error::new(ErrorKind::Unsupported, "device has no unique ID"))?;
+
+        if let Some(mut parent) = device.parent()
+            && parent.is_router()
+            && !parent.authorized().unwrap_or(false)
+        {
+            self.authorize(&mut parent)?;
+        }
+
+        if let Some(key) = self.get(&unique_id)? {
+            device.set_key(&key)?;
+            device.authorize(2)?;
+            return Ok(Authorization::Challenged);
+        }
+
+        // Store the key first so that the device is never enrolled with a key that is lost.
+        let key = generate_key();
+        self.insert(&unique_id, &key)?;
+        if let Err(err) = device.set_key(&key).and_then(|_| device.authorize(1)) {
+            self.remove(&unique_id).ok();
+            return Err(err);
+        }
+
+        Ok(Authorization::Enrolled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{TempDir, write};
+
+    #[test]
+    fn store() {
+        let dir = TempDir::new("keystore");
+        let store = KeyStore::new(&dir.join("keys"));
+        let unique_id = "1D7F3A2C-0D3E-4F00-8000-123456789ABC";
+        let key = generate_key();
+
+        assert!(is_valid_key(&key));
+        assert_eq!(store.get(unique_id).unwrap(), None);
+        assert!(store.unique_ids().unwrap().is_empty());
+
+        store.insert(unique_id, &key).unwrap();
+        assert_eq!(store.get(unique_id).unwrap(), Some(key.clone()));
+        assert_eq!(
+            store.unique_ids().unwrap(),
+            ["1d7f3a2c-0d3e-4f00-8000-123456789abc"]
+        );
+
+        let path = store.path(unique_id).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mode = fs::metadata(store.dir()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        assert_eq!(
+            store.insert(unique_id, "1234").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            store.get("../../etc/passwd").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        assert!(store.remove(unique_id).unwrap());
+        assert!(!store.remove(unique_id).unwrap());
+        assert_eq!(store.get(unique_id).unwrap(), None);
+    }
+
+    #[test]
+    fn permissions() {
+        let dir = TempDir::new("keystore-permissions");
+        let store = KeyStore::new(&dir.join("keys"));
+        let unique_id = "1d7f3a2c-0d3e-4f00-8000-123456789abc";
+
+        // Leftovers from an earlier run with looser permissions.
+        let tmp = store.path(unique_id).unwrap().with_extension("tmp");
+        write(&tmp, "stale");
+        fs::set_permissions(&tmp, Permissions::from_mode(0o644)).unwrap();
+        fs::set_permissions(store.dir(), Permissions::from_mode(0o755)).unwrap();
+
+        let key = generate_key();
+        store.insert(unique_id, &key).unwrap();
+        assert_eq!(store.get(unique_id).unwrap(), Some(key));
+        assert!(!tmp.exists());
+
+        let mode = fs::metadata(store.path(unique_id).unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mode = fs::metadata(store.dir()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
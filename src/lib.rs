@@ -9,18 +9,297 @@
 //! expects it is accessible.
 //!
 //! [Linux Thunderbolt/USB4 bus]: https://docs.kernel.org/admin-guide/thunderbolt.html
+//!
+//! # Serialization
+//! The library types implement [`serde::Serialize`] and the plain data types ([`Version`],
+//! [`Kind`], [`SecurityLevel`], [`debugfs::PathEntry`], [`debugfs::Type`], [`debugfs::State`],
+//! [`drom::Usb3PortMap`], [`tunnel::Type`] and [`tunnel::Direction`]) also
+//! [`serde::Deserialize`]. The schema is stable
+//! and only extended by adding new keys. Keys are always present and `null` when the value is not
+//! available (for instance registers that have not been read).
+//!
+//! - Enums are `snake_case` strings, for example `"router"`, `"usb3_down"` or `"display_port"`.
+//!   [`SecurityLevel`] uses the same names as the kernel (`"dponly"`).
+//!   [`margining::Lanes`], [`margining::Margin`], [`margining::Mode`], [`margining::Test`] and
+//!   [`monitor::TunnelEvent`] use their [`Display`](std::fmt::Display) output.
+//! - [`Device`]: `kernel_name`, `kind`, `domain`, `route`, `adapter`, `index`, `depth`, `vendor`,
+//!   `device`, `vendor_name`, `device_name`, `authorized`, `unique_id`, `generation`,
+//!   `security_level`, `usb4_version`, `nvm_version` (`{"major", "minor"}`), `rx_speed`,
+//!   `rx_lanes`, `tx_speed`, `tx_lanes`, `registers`, `sideband_registers`, `adapters`, `drom`
+//!   and `boot_acl` (slots with `null` for empty ones, `null` unless the domain supports it).
+//! - [`debugfs::Adapter`]: `adapter`, `kind`, `state`, `upstream`, `registers`, `path_registers`,
+//!   `paths`, `counter_registers` and `sideband_registers`.
+//! - [`debugfs::Register`]: `offset`, `relative_offset`, `cap_id`, `vs_cap_id`, `name`, `value`,
+//!   `data` (raw bytes of sideband registers) and `fields`. Each field has `name`, `short_name`,
+//!   `start_bit`, `end_bit`, `value` and `value_name`.
+//! - [`debugfs::PathEntry`]: `in_adapter`, `in_hop`, `out_adapter` and `out_hop`.
+//! - [`drom::Drom`]: `version`, `length`, `crc8`, `crc8_valid`, `crc32`, `crc32_valid`, `uuid` and
+//!   `entries`. A [`drom::DromEntry`] is an object with the entry type as the only key, for
+//!   example `{"lane_adapter": {"adapter_num": 1, ...}}`.
+//! - [`tunnel::Tunnel`]: `kind`, `src` and `dst` (`{"domain", "route", "adapter"}`), `direction`,
+//!   `complete` and `paths`. Each path has `name` and `hops` and each hop `domain`, `route`,
+//!   `in_adapter`, `in_hop`, `out_adapter` and `out_hop`.
+//! - [`margining::Results`]: `test`, `lanes`, `unit` (`"mV"` or `"UI"`), `margins` (one entry per
+//!   lane, `null` if not margined) and `error_counter`. Margin values are `{"value", "exceeds"}`.
+//!   [`margining::Caps`] uses the field names of the struct.
+//! - [`monitor::Event`]: `event` (`"add"`, `"remove"` or `"change"`), `device` and `change`. The
+//!   change is tagged with `type`: `{"type": "router", "authorized"}` or
+//!   `{"type": "tunnel", "event", "details"}`.
+//!
+//! There is no cargo feature to leave these out. `serde` and `serde_json` are needed anyway
+//! because the register metadata is stored as JSON and the [`snapshot`] and [`trace`] file
+//! formats use them.
 
 mod cros;
 mod device;
 
 pub use device::*;
 
+pub mod backend;
+pub mod bandwidth;
 pub mod debugfs;
+pub mod dpcd;
+pub mod drm;
 pub mod drom;
+pub mod graph;
+pub mod keystore;
+pub mod mapping;
 pub mod margining;
 pub mod monitor;
+pub mod nvm;
+pub mod pathcheck;
+pub mod pci;
+pub mod policy;
+pub mod router_op;
+mod serialize;
+pub mod snapshot;
+pub mod sysfs;
+pub mod topology;
 pub mod trace;
 pub mod tunnel;
 pub mod typec;
+pub mod usb;
 pub mod usb4;
 pub mod util;
+
+/// Helpers shared by the unit tests.
+#[cfg(test)]
+pub(crate) mod test_util {
+    use std::{
+        collections::HashMap,
+        env, fs,
+        ops::Deref,
+        path::{Path, PathBuf},
+        process,
+        sync::{Mutex, MutexGuard, PoisonError},
+    };
+
+    use crate::{
+        Device,
+        debugfs::{Adapter, Type},
+        device, sysfs,
+    };
+
+    /// Temporary directory that is removed along with its contents when dropped, so that a
+    /// failing test does not leave it behind.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// Creates a new empty directory. `name` must be unique among the tests.
+        pub(crate) fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("tbtools-{name}-{}", process::id()));
+            // Left over from an earlier run that was killed.
+            fs::remove_dir_all(&path).ok();
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    /// Alternate sysfs root that is reset when dropped, so that a failing test does not leave it
+    /// behind. Only one test can use it at a time because the root is global.
+    pub(crate) struct SysfsRoot {
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl SysfsRoot {
+        /// Sets `root` as the alternate root waiting for other tests using it to finish first.
+        pub(crate) fn set(root: &Path) -> Self {
+            static LOCK: Mutex<()> = Mutex::new(());
+
+            let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+            sysfs::set_root(Some(root));
+            Self { _lock: lock }
+        }
+    }
+
+    impl Drop for SysfsRoot {
+        fn drop(&mut self) {
+            sysfs::set_root(None);
+        }
+    }
+
+    /// Writes `contents` to `path` creating the parent directories as needed.
+    pub(crate) fn write(path: &Path, contents: impl AsRef<[u8]>) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// PCI device of the host interface the routers returned by [`router()`] are below.
+    pub(crate) const NHI: &str = "/sys/devices/pci0000:00/0000:00:0d.2";
+
+    /// `ADP_CS_0` - `ADP_CS_5` of the adapters returned by [`adapter()`] with Max Input HopID 19.
+    pub(crate) const ADP_CS: [u32; 6] = [0, 0, 0, 0, 0, 19];
+
+    /// Attributes of a router that exists only in memory.
+    struct Router {
+        kernel_name: String,
+        syspath: PathBuf,
+        attributes: HashMap<String, String>,
+    }
+
+    impl device::Attributes for Router {
+        fn devtype(&self) -> Option<String> {
+            Some(String::from("thunderbolt_device"))
+        }
+
+        fn sysname(&self) -> Option<String> {
+            Some(self.kernel_name.clone())
+        }
+
+        fn syspath(&self) -> PathBuf {
+            self.syspath.clone()
+        }
+
+        fn attribute(&self, name: &str) -> Option<String> {
+            self.attributes.get(name).cloned()
+        }
+
+        fn property(&self, _name: &str) -> Option<String> {
+            None
+        }
+    }
+
+    fn depth(route: u64) -> u32 {
+        (u64::BITS - route.leading_zeros()).div_ceil(8)
+    }
+
+    fn new_router(
+        nhi: &Path,
+        route: u64,
+        attributes: &[(&str, &str)],
+        adapters: Vec<Adapter>,
+    ) -> Device {
+        // Nested below the parents like in sysfs.
+        let mut syspath = nhi.join("domain0");
+        for depth in 0..=depth(route) {
+            let route = route & ((1 << (depth * 8)) - 1);
+            syspath.push(format!("0-{route:x}"));
+        }
+
+        let router = Router {
+            kernel_name: format!("0-{route:x}"),
+            syspath,
+            attributes: attributes
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
+        let mut device = Device::parse_attributes(&router).unwrap();
+
+        let upstream = adapters
+            .iter()
+            .find(|a| a.is_upstream())
+            .map_or(0, |a| a.adapter());
+        let max_adapter = adapters.iter().map(|a| a.adapter()).max().unwrap_or(0);
+        // ROUTER_CS_1 holds the upstream and max adapter numbers.
+        let cs_1 = (max_adapter as u32) << 14 | (upstream as u32) << 8;
+        device.set_test_registers(&[0, cs_1], adapters);
+
+        device
+    }
+
+    /// Returns router with `route` in domain 0 below host interface `nhi`. The upstream adapter
+    /// of the router is the one in `adapters` that is upstream.
+    pub(crate) fn router_at(nhi: &Path, route: u64, adapters: Vec<Adapter>) -> Device {
+        new_router(nhi, route, &[], adapters)
+    }
+
+    /// Returns router with `route` in domain 0 below [`NHI`].
+    pub(crate) fn router(route: u64, adapters: Vec<Adapter>) -> Device {
+        new_router(Path::new(NHI), route, &[], adapters)
+    }
+
+    /// Returns router with `route` and sysfs `attributes` such as `rx_speed`.
+    pub(crate) fn router_with(
+        route: u64,
+        attributes: &[(&str, &str)],
+        adapters: Vec<Adapter>,
+    ) -> Device {
+        new_router(Path::new(NHI), route, attributes, adapters)
+    }
+
+    fn new_adapter(route: u64, adapter_num: u8, kind: Type, upstream: bool) -> Adapter {
+        let mut adapter = Adapter::new(
+            0,
+            route,
+            depth(route),
+            adapter_num,
+            kind,
+            Some(format!("0-{route:x}")),
+            true,
+            upstream,
+        );
+        // Protocol adapters are enabled.
+        let protocol: &[u32] = match kind {
+            Type::PcieDown | Type::PcieUp => &[1 << 31],
+            Type::Usb3Down | Type::Usb3Up | Type::DisplayPortIn | Type::DisplayPortOut => {
+                &[1 << 31 | 1 << 30]
+            }
+            // One Gen T port with its ADP_USB3_GT_PORT_CS_1 at offset 3.
+            Type::Usb3GenTDown | Type::Usb3GenTUp => &[1 << 16, 0, 0, 1 << 31 | 1 << 30],
+            _ => &[],
+        };
+        adapter.set_test_registers(&ADP_CS, protocol);
+        adapter
+    }
+
+    /// Returns adapter `adapter_num` of router `route`.
+    pub(crate) fn adapter(route: u64, adapter_num: u8, kind: Type) -> Adapter {
+        new_adapter(route, adapter_num, kind, false)
+    }
+
+    /// Returns lane adapter `adapter_num` of router `route` that is connected to the parent.
+    pub(crate) fn upstream_lane(route: u64, adapter_num: u8) -> Adapter {
+        new_adapter(route, adapter_num, Type::Lane, true)
+    }
+
+    /// Returns `PATH_CS_0` of an enabled path entry routing to `out_hop` of `out_adapter`.
+    pub(crate) fn path_cs_0(out_adapter: u8, out_hop: u16, credits: u32) -> u32 {
+        1 << 31 | credits << 17 | (out_adapter as u32) << 11 | out_hop as u32
+    }
+
+    /// Returns path entry for [`Adapter::set_test_paths()`] routing `in_hop` to `out_hop` of
+    /// `out_adapter`.
+    pub(crate) fn hop(in_hop: u16, out_adapter: u8, out_hop: u16) -> (u16, u32, u32) {
+        (in_hop, path_cs_0(out_adapter, out_hop, 0), 0)
+    }
+
+    /// Returns `PATH_CS_1` with `priority` and `weight`.
+    pub(crate) fn path_cs_1(priority: u32, weight: u32) -> u32 {
+        priority << 8 | weight
+    }
+}
@@ -0,0 +1,98 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Linux devices the adapters are connected to.
+//!
+//! [`Mappings`] collects the DisplayPort connectors, PCI bridges and USB ports resolved by
+//! [`drm`], [`pci`] and [`usb`] so that they can be looked up for each adapter.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::mapping::Mappings;
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut devices = tbtools::find_devices(None)?;
+//!
+//! for device in &mut devices {
+//!     if device.is_router() {
+//!         device.read_adapters()?;
+//!         for adapter in device.adapters_mut().unwrap() {
+//!             adapter.read_paths().ok();
+//!         }
+//!         device.read_drom().ok();
+//!     }
+//! }
+//!
+//! let mappings = Mappings::read(&devices, true, false)?;
+//! for device in &devices {
+//!     for adapter in device.adapters().into_iter().flatten() {
+//!         if let Some(bridge) = mappings.bridge(adapter) {
+//!             println!("{} {}: {}", device.kernel_name(), adapter.adapter(), bridge.description());
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Result;
+
+use crate::{
+    Device,
+    debugfs::Adapter,
+    drm::{self, Connector},
+    pci::{self, Bridge},
+    usb::{self, Port},
+};
+
+/// DisplayPort connectors, PCI bridges and USB ports of the adapters.
+#[derive(Clone, Debug, Default)]
+pub struct Mappings {
+    connectors: Vec<Connector>,
+    bridges: Vec<Bridge>,
+    ports: Vec<Port>,
+}
+
+impl Mappings {
+    /// Resolves the PCI bridges and USB ports of the adapters in `devices`.
+    ///
+    /// The adapters, path config spaces and DROMs of the routers should be read as described in
+    /// [`pci::bridges()`] and [`usb::ports()`]. If `router_ops` is `true` uses
+    /// [`pci::bridges_with_router_ops()`] instead. Reading the DisplayPort connectors opens every
+    /// DP AUX device so they are only read if `connectors` is `true`.
+    pub fn read(devices: &[Device], connectors: bool, router_ops: bool) -> Result<Self> {
+        let connectors = if connectors {
+            drm::connectors()?
+        } else {
+            Vec::new()
+        };
+        let bridges = if router_ops {
+            pci::bridges_with_router_ops(devices)
+        } else {
+            pci::bridges(devices)
+        };
+
+        Ok(Self {
+            connectors,
+            bridges,
+            ports: usb::ports(devices),
+        })
+    }
+
+    /// Returns DisplayPort connector of a DP IN `adapter`.
+    pub fn connector(&self, adapter: &Adapter) -> Option<&Connector> {
+        adapter.dp_connector(&self.connectors)
+    }
+
+    /// Returns PCI bridge of a PCIe `adapter`.
+    pub fn bridge(&self, adapter: &Adapter) -> Option<&Bridge> {
+        adapter.pci_bridge(&self.bridges)
+    }
+
+    /// Returns USB port of a USB 3 `adapter`.
+    pub fn port(&self, adapter: &Adapter) -> Option<&Port> {
+        adapter.usb_port(&self.ports)
+    }
+}
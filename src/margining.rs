@@ -128,7 +128,8 @@ fn read_results(path: &Path) -> Result<[u32; 3]> {
 }
 
 /// Which type of independent voltage margins are supported.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum IndependentVoltage {
     /// Minimum between high and low margins is returned.
     Gen23Minimum,
@@ -143,7 +144,8 @@ pub enum IndependentVoltage {
 }
 
 /// Which type of independent timing margins are supported.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum IndependentTiming {
     /// Minimum between right and left margins is returned.
     Gen23Minimum,
@@ -158,7 +160,7 @@ pub enum IndependentTiming {
 }
 
 /// Timing margining specific capabilities result from `READ_LANE_MARGIN_CAP` USB4 port operation.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize)]
 pub struct TimeCaps {
     /// Is time margining supported.
     pub destructive: bool,
@@ -171,7 +173,7 @@ pub struct TimeCaps {
 }
 
 /// Margining capabilities result from `READ_LANE_MARGIN_CAP` USB4 port operation.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize)]
 pub struct Caps {
     /// Is hardware margining supported.
     pub hardware: bool,
@@ -443,7 +445,8 @@ impl ResultValue {
 }
 
 /// Holds either Gen 4 upper eye or lower eye result.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LaneResultGen4Both {
     UpperEye(ResultValue),
     LowerEye(ResultValue),
@@ -459,7 +462,8 @@ impl LaneResultGen4Both {
 }
 
 /// Holds voltage lane margining result.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LaneVoltageResult {
     /// Result contains minimum between high and low voltage margins.
     Minimum(ResultValue),
@@ -477,7 +481,8 @@ pub enum LaneVoltageResult {
 }
 
 /// Holds time lane margining result.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LaneTimingResult {
     /// Result contains minimum between left and right time margins.
     Minimum(ResultValue),
@@ -497,7 +502,8 @@ pub enum LaneTimingResult {
 }
 
 /// Type of the margining result.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LaneResult {
     /// Result is voltage margining.
     Voltage(LaneVoltageResult),
@@ -643,6 +649,16 @@ impl Results {
         ResultValue::new(self.to_margin(margin_value), exceeds)
     }
 
+    /// Returns the test that was run.
+    pub fn test(&self) -> Test {
+        self.test
+    }
+
+    /// Returns the lanes that were margined.
+    pub fn lanes(&self) -> Lanes {
+        self.lanes
+    }
+
     /// High (or right) margin values.
     ///
     /// Depending on which lane was selected returns tuple of values in either `mV` or `UI` for
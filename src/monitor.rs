@@ -57,7 +57,8 @@ impl Display for TunnelEvent {
 }
 
 /// Describes the type of the change event.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ChangeEvent {
     /// Router was authorized or de-authorized.
     Router { authorized: u8 },
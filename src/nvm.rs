@@ -0,0 +1,574 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! NVM firmware update of routers and retimers.
+//!
+//! The kernel exposes the NVM of routers and retimers that can be upgraded through two NVMem
+//! devices. `nvm_activeN` holds the firmware currently running and `nvm_non_activeN` is where the
+//! new image is written. Writing to `nvm_authenticate` then flushes the image to the flash and
+//! makes the device authenticate it. If the image passes, the device restarts running the new
+//! firmware. Device routers may alternatively support delaying the authentication until they are
+//! disconnected through `nvm_authenticate_on_disconnect`.
+//!
+//! Status of the last authentication is read back from `nvm_authenticate`. Zero means success
+//! and anything else is the error code returned by the router or retimer.
+//!
+//! Before writing, the image can be parsed with [`Image::parse()`] and checked against the target
+//! device with [`Image::validate()`]. The kernel only checks the hardware device ID so this catches
+//! images meant for another product built on the same chip as well.
+//!
+//! # Examples
+//! Update the NVM of a device router with route `1`.
+//! ```no_run
+//! # use std::{fs, io, time::Duration};
+//! use tbtools::{
+//!     Address,
+//!     nvm::{Authenticate, Image, Nvm},
+//! };
+//!
+//! # fn main() -> io::Result<()> {
+//! let address = Address::Router { domain: 0, route: 1 };
+//! if let Some(device) = tbtools::find_device(&address)? {
+//!     let image = Image::parse(&fs::read("firmware.bin")?)?;
+//!     image.validate(&device)?;
+//!
+//!     let mut nvm = Nvm::new(&device)?;
+//!     nvm.write(image.bytes())?;
+//!     nvm.authenticate(Authenticate::Now, Duration::from_secs(60))?;
+//!     if let Some(version) = nvm.device().nvm_version() {
+//!         println!("NVM version is now {:x}.{:x}", version.major, version.minor);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt::{self, Display},
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind, Result, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use nix::errno::Errno;
+
+use crate::{
+    Device, Kind, Version,
+    debugfs::BitFields,
+    drom::Drom,
+    genmask_t,
+    monitor::{self, Event},
+    snapshot, util,
+};
+
+const NVM_ACTIVE: &str = "nvm_active";
+const NVM_NON_ACTIVE: &str = "nvm_non_active";
+const NVMEM: &str = "nvmem";
+const NVM_AUTHENTICATE: &str = "nvm_authenticate";
+const NVM_AUTHENTICATE_ON_DISCONNECT: &str = "nvm_authenticate_on_disconnect";
+
+const NVM_FARB_MASK: u32 = genmask_t!(u32, 23, 0);
+const NVM_DIGITAL_ALIGN: usize = 4096;
+// Offsets inside the digital section.
+const NVM_DEVID: usize = 0x05;
+const NVM_VERSION: usize = 0x08;
+const NVM_DROM: usize = 0x10e;
+const DROM_LENGTH: usize = 14;
+const DROM_LENGTH_MASK: u16 = genmask_t!(u16, 11, 0);
+const DROM_HEADER_SIZE: usize = 13;
+
+/// How the new NVM image is taken into use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authenticate {
+    /// Flush the image and authenticate it right away. The device restarts with the new
+    /// firmware.
+    Now,
+    /// Flush the image and authenticate it when the device is disconnected. Only supported by
+    /// some device routers.
+    OnDisconnect,
+    /// Validate and flush the image but do not authenticate it.
+    FlushOnly,
+}
+
+impl Display for Authenticate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Now => "authenticate",
+            Self::OnDisconnect => "authenticate on disconnect",
+            Self::FlushOnly => "flush only",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Upgradeable NVM of a router or retimer.
+#[derive(Clone, Debug)]
+pub struct Nvm {
+    device: Device,
+    active: Option<PathBuf>,
+    non_active: PathBuf,
+}
+
+/// Adds explanation to errors the kernel returns when the NVM is written or authenticated.
+fn kernel_error(err: Error) -> Error {
+    let reason = match err.raw_os_error().map(Errno::from_raw) {
+        Some(Errno::EINVAL) => "image is missing or failed validation",
+        Some(Errno::EBUSY) | Some(Errno::EAGAIN) => "device is busy",
+        Some(Errno::EOPNOTSUPP) => "not supported by the device",
+        Some(Errno::ENODEV) => "device was removed",
+        Some(Errno::EIO) => "device failed to respond",
+        _ => return err,
+    };
+    Error::new(err.kind(), format!("{reason} ({err})"))
+}
+
+impl Nvm {
+    /// Returns NVM of `device`.
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if `device` is not a router or retimer and
+    /// [`ErrorKind::Unsupported`] if the kernel does not support upgrading its NVM.
+    pub fn new(device: &Device) -> Result<Self> {
+        if !device.is_router() && !device.is_retimer() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "only routers and retimers have NVM",
+            ));
+        }
+
+        let non_active = Self::find_nvmem(device, NVM_NON_ACTIVE)
+            .ok_or_else(|| Error::new(ErrorKind::Unsupported, "NVM upgrade not supported"))?;
+
+        Ok(Self {
+            device: device.clone(),
+            active: Self::find_nvmem(device, NVM_ACTIVE),
+            non_active,
+        })
+    }
+
+    fn find_nvmem(device: &Device, prefix: &str) -> Option<PathBuf> {
+        fs::read_dir(device.sysfs_path())
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with(prefix))
+            })
+            .map(|entry| entry.path().join(NVMEM))
+    }
+
+    /// Returns the device owning this NVM.
+    ///
+    /// After successful [`authenticate()`](Self::authenticate()) this is re-read so that it
+    /// reflects the new NVM version.
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Returns size of the non-active NVM in bytes. The image can not be larger than this.
+    pub fn size(&self) -> Result<u64> {
+        Ok(fs::metadata(&self.non_active)?.len())
+    }
+
+    /// Returns size of the active NVM in bytes or `None` if the kernel does not expose it, for
+    /// example when the router is in safe mode.
+    pub fn active_size(&self) -> Option<u64> {
+        Some(fs::metadata(self.active.as_ref()?).ok()?.len())
+    }
+
+    /// Returns `true` if the device supports authentication on disconnect.
+    pub fn supports_authenticate_on_disconnect(&self) -> bool {
+        self.device
+            .sysfs_path()
+            .join(NVM_AUTHENTICATE_ON_DISCONNECT)
+            .exists()
+    }
+
+    /// Writes `image` to the non-active NVM.
+    ///
+    /// The kernel keeps the image in memory until it is flushed by
+    /// [`authenticate()`](Self::authenticate()).
+    pub fn write(&self, image: &[u8]) -> Result<()> {
+        if snapshot::active().is_some() {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "snapshot is read-only",
+            ));
+        }
+        if image.len() as u64 > self.size()? {
+            return Err(Error::new(ErrorKind::InvalidInput, "image is too large"));
+        }
+
+        let mut file = OpenOptions::new().write(true).open(&self.non_active)?;
+        file.write_all(image).map_err(kernel_error)
+    }
+
+    /// Returns status of the last authentication. `0` means success.
+    pub fn status(&self) -> Result<u32> {
+        self.device
+            .attributes()?
+            .attribute(NVM_AUTHENTICATE)
+            .and_then(|s| util::parse_hex(&s))
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))
+    }
+
+    /// Clears status of the last authentication.
+    pub fn clear_status(&self) -> Result<()> {
+        self.device.set_attribute(NVM_AUTHENTICATE, "0")
+    }
+
+    /// Flushes the image written with [`write()`](Self::write()) and authenticates it as
+    /// specified by `mode`.
+    ///
+    /// With [`Authenticate::Now`] device routers are removed while they restart, in which case
+    /// waits up to `timeout` for the router to be enumerated again. Host routers and retimers
+    /// complete the authentication before the kernel returns. Either way the device is re-read
+    /// afterwards and the status checked.
+    ///
+    /// Returns [`ErrorKind::Unsupported`] if `mode` is not supported by the device,
+    /// [`ErrorKind::TimedOut`] if the router did not come back in time and
+    /// [`ErrorKind::Other`] with the status code if the authentication failed.
+    pub fn authenticate(&mut self, mode: Authenticate, timeout: Duration) -> Result<()> {
+        let (name, value) = match mode {
+            Authenticate::Now => (NVM_AUTHENTICATE, "1"),
+            Authenticate::OnDisconnect => (NVM_AUTHENTICATE_ON_DISCONNECT, "1"),
+            Authenticate::FlushOnly => (NVM_AUTHENTICATE, "2"),
+        };
+
+        if mode == Authenticate::OnDisconnect && !self.supports_authenticate_on_disconnect() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "authentication on disconnect not supported",
+            ));
+        }
+
+        if mode != Authenticate::Now || !self.device.is_device_router() {
+            self.device
+                .set_attribute(name, value)
+                .map_err(kernel_error)?;
+            return self.check_status();
+        }
+
+        let domain = self.device.domain_index();
+        let route = self.device.route();
+        let is_router = move |d: &Device| d.domain_index() == domain && d.route() == route;
+
+        // Start monitoring before the authentication so that we don't miss any events.
+        let mut monitor = monitor::Builder::new()?.kind(Kind::Router)?.build()?;
+        let start = Instant::now();
+
+        self.device
+            .set_attribute(name, value)
+            .map_err(kernel_error)?;
+
+        let mut removed = false;
+        let mut enumerated = false;
+
+        while !enumerated {
+            let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    "router was not enumerated in time",
+                ));
+            };
+
+            if !monitor.poll(Some(remaining))? {
+                continue;
+            }
+
+            for event in monitor.iter_mut() {
+                match event {
+                    Event::Remove(device) if is_router(&device) => removed = true,
+                    Event::Add(device) if is_router(&device) && removed => enumerated = true,
+                    _ => (),
+                }
+            }
+        }
+
+        self.check_status()
+    }
+
+    fn check_status(&mut self) -> Result<()> {
+        if let Some(device) = Device::from_syspath(&self.device.sysfs_path()) {
+            self.device = device;
+        }
+
+        match self.status()? {
+            0 => Ok(()),
+            status => Err(Error::other(format!(
+                "NVM authentication failed with status {status:#x}"
+            ))),
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Parsed NVM image file.
+///
+/// The image starts with headers that are not written to the flash. The first double word points
+/// to the digital section which holds the hardware device ID, firmware version and the offset of
+/// the DROM.
+#[derive(Clone, Debug)]
+pub struct Image {
+    bytes: Vec<u8>,
+    digital: usize,
+    device_id: u16,
+    version: Version,
+    drom: Option<Drom>,
+}
+
+impl Image {
+    /// Parses NVM image from `bytes`.
+    ///
+    /// Runs the same sanity checks for the headers as the kernel. Returns
+    /// [`ErrorKind::InvalidData`] if they fail. Images without DROM, such as retimer images, are
+    /// accepted.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let invalid = |msg| Error::new(ErrorKind::InvalidData, msg);
+
+        let digital = read_u32(bytes, 0).ok_or_else(|| invalid("image too small"))?;
+        let digital = (digital & NVM_FARB_MASK) as usize;
+
+        if digital + NVM_DEVID + 2 >= bytes.len() {
+            return Err(invalid("digital section outside of the image"));
+        }
+        if !digital.is_multiple_of(NVM_DIGITAL_ALIGN) {
+            return Err(invalid("digital section not aligned"));
+        }
+        if read_u16(bytes, digital).unwrap() as usize >= bytes.len() {
+            return Err(invalid("digital section size too large"));
+        }
+
+        let device_id = read_u16(bytes, digital + NVM_DEVID).unwrap();
+        let version = read_u32(bytes, digital + NVM_VERSION)
+            .ok_or_else(|| invalid("digital section too small"))?;
+        let version = Version {
+            major: (version >> 16) as u8,
+            minor: (version >> 8) as u8,
+        };
+
+        let drom = read_u32(bytes, digital + NVM_DROM)
+            .filter(|offset| *offset != 0 && *offset != u32::MAX)
+            .and_then(|offset| {
+                let start = digital + offset as usize;
+                let length = read_u16(bytes, start + DROM_LENGTH)? & DROM_LENGTH_MASK;
+                let end = start + length as usize + DROM_HEADER_SIZE;
+                Drom::parse(bytes.get(start..end)?, &[]).ok()
+            });
+
+        Ok(Self {
+            bytes: Vec::from(bytes),
+            digital,
+            device_id,
+            version,
+            drom,
+        })
+    }
+
+    /// Returns the whole image as passed to [`parse()`](Self::parse()).
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns offset of the digital section in the image.
+    pub fn digital_offset(&self) -> usize {
+        self.digital
+    }
+
+    /// Returns hardware device ID the image is built for.
+    pub fn device_id(&self) -> u16 {
+        self.device_id
+    }
+
+    /// Returns firmware version of the image.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns DROM embedded in the image.
+    pub fn drom(&self) -> Option<&Drom> {
+        self.drom.as_ref()
+    }
+
+    /// Checks that the image is meant for `device`.
+    ///
+    /// For routers compares the vendor and model IDs in the DROM of the image against
+    /// [`Device::vendor()`] and [`Device::device()`], and if the router registers have been read
+    /// the hardware device ID against `ROUTER_CS_0`. For retimers compares the hardware device ID
+    /// against [`Device::device()`].
+    ///
+    /// Returns [`ErrorKind::InvalidData`] describing the first mismatch found.
+    pub fn validate(&self, device: &Device) -> Result<()> {
+        let mismatch = |what, image: u16, device: u16| {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("image {what} {image:#06x} does not match device {device:#06x}"),
+            ))
+        };
+
+        if device.is_retimer() {
+            if self.device_id != device.device() {
+                return mismatch("device ID", self.device_id, device.device());
+            }
+            return Ok(());
+        }
+
+        if let Some(drom) = &self.drom {
+            if let Some(vendor) = drom.vendor_id()
+                && vendor != device.vendor()
+            {
+                return mismatch("vendor ID", vendor, device.vendor());
+            }
+            if let Some(model) = drom.model_id()
+                && model != device.device()
+            {
+                return mismatch("model ID", model, device.device());
+            }
+        }
+
+        if let Some(reg) = device.register_by_name("ROUTER_CS_0") {
+            let device_id = reg.field("Product ID") as u16;
+            if self.device_id != device_id {
+                return mismatch("hardware device ID", self.device_id, device_id);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{SysfsRoot, TempDir, write};
+    use std::os::unix::fs::symlink;
+
+    fn image_bytes(vendor: u16, model: u16) -> Vec<u8> {
+        let mut image = vec![0; 0x2000];
+        image[..4].copy_from_slice(&0x1000u32.to_le_bytes());
+        image[0x1000..0x1002].copy_from_slice(&0x800u16.to_le_bytes());
+        image[0x1005..0x1007].copy_from_slice(&0x15efu16.to_le_bytes());
+        image[0x1008..0x100c].copy_from_slice(&0x00362000u32.to_le_bytes());
+        image[0x110e..0x1112].copy_from_slice(&0x200u32.to_le_bytes());
+
+        // Thunderbolt 3 compatible DROM with just the header.
+        let drom = &mut image[0x1200..0x1216];
+        drom[13] = 1;
+        drom[14] = 22 - 13;
+        drom[16..18].copy_from_slice(&vendor.to_le_bytes());
+        drom[18..20].copy_from_slice(&model.to_le_bytes());
+
+        image
+    }
+
+    #[test]
+    fn parse_image() {
+        let image = Image::parse(&image_bytes(0x8087, 0x1234)).unwrap();
+        assert_eq!(image.digital_offset(), 0x1000);
+        assert_eq!(image.device_id(), 0x15ef);
+        assert_eq!(
+            image.version(),
+            Version {
+                major: 0x36,
+                minor: 0x20
+            }
+        );
+        let drom = image.drom().unwrap();
+        assert_eq!(drom.vendor_id(), Some(0x8087));
+        assert_eq!(drom.model_id(), Some(0x1234));
+
+        let mut bytes = image.bytes().to_vec();
+        bytes[0x110e..0x1112].fill(0xff);
+        assert!(Image::parse(&bytes).unwrap().drom().is_none());
+
+        bytes[0] = 0x10;
+        assert_eq!(
+            Image::parse(&bytes).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(Image::parse(&bytes[..0x1004]).is_err());
+        assert!(Image::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn flush() {
+        let root = TempDir::new("nvm");
+        let host = root.join("sys/devices/pci0000:00/0000:00:0d.2/domain0/0-0");
+        let retimer = host.join("0-0:1.1");
+        let devices = root.join("sys/bus/thunderbolt/devices");
+
+        write(&host.join("uevent"), "DEVTYPE=thunderbolt_device\n");
+        write(&host.join("vendor"), "0x8087\n");
+        write(&host.join("device"), "0x1234\n");
+        write(&host.join("nvm_version"), "1.2\n");
+        write(&host.join("nvm_authenticate"), "0\n");
+        write(&host.join("nvm_active0/nvmem"), "\0".repeat(32));
+        write(&host.join("nvm_non_active0/nvmem"), "\0".repeat(16));
+        write(&retimer.join("uevent"), "DEVTYPE=thunderbolt_retimer\n");
+
+        fs::create_dir_all(&devices).unwrap();
+        symlink(&host, devices.join("0-0")).unwrap();
+        symlink(&retimer, devices.join("0-0:1.1")).unwrap();
+
+        let _root = SysfsRoot::set(&root);
+
+        let found = crate::find_devices(None).unwrap();
+        let router = found.iter().find(|d| d.is_host_router()).unwrap();
+        let retimer = found.iter().find(|d| d.is_retimer()).unwrap();
+
+        assert_eq!(
+            Nvm::new(retimer).unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+
+        let mut nvm = Nvm::new(router).unwrap();
+        assert_eq!(nvm.size().unwrap(), 16);
+        assert_eq!(nvm.active_size(), Some(32));
+        assert!(!nvm.supports_authenticate_on_disconnect());
+        assert_eq!(
+            nvm.write(&[0xff; 32]).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        nvm.write(&[0xff; 8]).unwrap();
+        assert_eq!(
+            fs::read(host.join("nvm_non_active0/nvmem")).unwrap()[..8],
+            [0xff; 8]
+        );
+        assert_eq!(
+            nvm.authenticate(Authenticate::OnDisconnect, Duration::ZERO)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Unsupported
+        );
+
+        assert_eq!(nvm.status().unwrap(), 0);
+        write(&host.join("nvm_authenticate"), "0x3\n");
+        assert_eq!(nvm.status().unwrap(), 3);
+        nvm.clear_status().unwrap();
+        assert_eq!(nvm.status().unwrap(), 0);
+        let version = nvm.device().nvm_version().unwrap();
+        assert_eq!((version.major, version.minor), (1, 2));
+
+        let image = Image::parse(&image_bytes(0x8087, 0x1234)).unwrap();
+        image.validate(router).unwrap();
+        let image = Image::parse(&image_bytes(0x8087, 0x1235)).unwrap();
+        assert_eq!(
+            image.validate(router).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
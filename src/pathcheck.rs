@@ -0,0 +1,744 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Consistency checks of the path configuration.
+//!
+//! Goes through the discovered [`Tunnels`](Tunnel) and the path config spaces of all the
+//! adapters and reports problems that usually point to a connection manager bug:
+//!
+//! - Paths that end at a lane adapter instead of a protocol adapter (dangling hops).
+//! - Output HopIDs that are outside of the input HopID range of the adapter on the other side of
+//!   the link, or that do not match any entry there.
+//! - Enabled path entries that are not part of any tunnel.
+//! - Path credits allocated over the buffers the adapter has.
+//! - Weight and priority values that differ from the ones recommended for the tunnel type.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::{pathcheck, tunnel::Tunnel};
+//!
+//! # fn main() -> io::Result<()> {
+//! # let devices: Vec<_> = tbtools::find_devices(None)?;
+//! # let tunnels: Vec<Tunnel> = Vec::new();
+//! // Devices need to have adapters and paths read and tunnels discovered from them.
+//! for issue in pathcheck::check(&devices, &tunnels) {
+//!     println!("{issue}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fmt::{self, Display},
+};
+
+use crate::{
+    Device,
+    debugfs::{Adapter, BitFields},
+    tunnel::{self, Path, Tunnel},
+};
+
+/// How serious the issue is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Configuration differs from the recommended values but works.
+    Warning,
+    /// Configuration is broken.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Type of the issue found.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IssueKind {
+    /// Path ends at a lane adapter and the other side of the link has no matching entry.
+    DanglingHop {
+        /// Name of the path.
+        path: String,
+        /// Output HopID of the last entry.
+        out_hop: u16,
+    },
+    /// Output HopID is outside of the input HopID range of the adapter on the other side of the
+    /// link.
+    HopIdOutOfRange {
+        /// Name of the path.
+        path: String,
+        /// Output HopID of the last entry.
+        out_hop: u16,
+        /// Maximum input HopID of the adapter on the other side.
+        max_hop: u16,
+    },
+    /// Output HopID does not match any entry on the other side of the link but there are
+    /// entries not used by any tunnel.
+    HopIdMismatch {
+        /// Name of the path.
+        path: String,
+        /// Output HopID of the last entry.
+        out_hop: u16,
+        /// Unused input HopIDs on the other side.
+        candidates: Vec<u16>,
+    },
+    /// Output adapter of the entry does not exist.
+    MissingAdapter {
+        /// Output adapter number.
+        out_adapter: u8,
+    },
+    /// Enabled path entry that is not part of any tunnel.
+    Unreachable,
+    /// More path credits allocated than the adapter has buffers.
+    CreditsExceeded {
+        /// Sum of the path credits allocated.
+        allocated: u32,
+        /// Total buffers of the adapter.
+        buffers: u32,
+    },
+    /// Weight differs from the recommended value.
+    Weight {
+        /// Name of the path.
+        path: String,
+        /// Recommended weight.
+        expected: u32,
+        /// Programmed weight.
+        actual: u32,
+    },
+    /// Priority differs from the recommended value.
+    Priority {
+        /// Name of the path.
+        path: String,
+        /// Recommended priority.
+        expected: u32,
+        /// Programmed priority.
+        actual: u32,
+    },
+}
+
+/// Single problem found in the path configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    domain: u32,
+    route: u64,
+    adapter: u8,
+    hop: Option<u16>,
+    kind: IssueKind,
+}
+
+impl Issue {
+    /// Returns domain of the router.
+    pub fn domain(&self) -> u32 {
+        self.domain
+    }
+
+    /// Returns route of the router.
+    pub fn route(&self) -> u64 {
+        self.route
+    }
+
+    /// Returns adapter number.
+    pub fn adapter(&self) -> u8 {
+        self.adapter
+    }
+
+    /// Returns input HopID of the path entry if the issue is about a single entry.
+    pub fn hop(&self) -> Option<u16> {
+        self.hop
+    }
+
+    /// Returns type of the issue.
+    pub fn kind(&self) -> &IssueKind {
+        &self.kind
+    }
+
+    /// Returns severity of the issue.
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            IssueKind::Weight { .. } | IssueKind::Priority { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: Domain {} Route {:x} Adapter {}",
+            self.severity(),
+            self.domain,
+            self.route,
+            self.adapter
+        )?;
+        if let Some(hop) = self.hop {
+            write!(f, " HopID {hop}")?;
+        }
+        write!(f, ": ")?;
+
+        match &self.kind {
+            IssueKind::DanglingHop { path, out_hop } => {
+                write!(
+                    f,
+                    "{path} path dangles, no entry for HopID {out_hop} on the other side"
+                )
+            }
+            IssueKind::HopIdOutOfRange {
+                path,
+                out_hop,
+                max_hop,
+            } => write!(
+                f,
+                "{path} path output HopID {out_hop} is over the other side maximum {max_hop}"
+            ),
+            IssueKind::HopIdMismatch {
+                path,
+                out_hop,
+                candidates,
+            } => {
+                let candidates: Vec<_> = candidates.iter().map(|c| c.to_string()).collect();
+                write!(
+                    f,
+                    "{path} path output HopID {out_hop} does not match, unused entries on the other side: {}",
+                    candidates.join(", ")
+                )
+            }
+            IssueKind::MissingAdapter { out_adapter } => {
+                write!(f, "output adapter {out_adapter} does not exist")
+            }
+            IssueKind::Unreachable => write!(f, "enabled entry is not part of any tunnel"),
+            IssueKind::CreditsExceeded { allocated, buffers } => write!(
+                f,
+                "{allocated} path credits allocated but only {buffers} buffers"
+            ),
+            IssueKind::Weight {
+                path,
+                expected,
+                actual,
+            } => write!(f, "{path} path weight {actual}, expected {expected}"),
+            IssueKind::Priority {
+                path,
+                expected,
+                actual,
+            } => write!(f, "{path} path priority {actual}, expected {expected}"),
+        }
+    }
+}
+
+/// Returns recommended `(priority, weight)` for a path of given tunnel type.
+///
+/// These are the values used by the Linux connection manager.
+fn defaults(kind: tunnel::Type, path: &str) -> (u32, u32) {
+    match kind {
+        tunnel::Type::Pcie => (3, 1),
+        tunnel::Type::Usb3 | tunnel::Type::Usb3GenT => (3, 2),
+        tunnel::Type::DisplayPort if path == "Video" => (1, 1),
+        tunnel::Type::DisplayPort => (2, 1),
+        tunnel::Type::Dma => (5, 1),
+    }
+}
+
+fn issue(device: &Device, adapter: u8, hop: Option<u16>, kind: IssueKind) -> Issue {
+    Issue {
+        domain: device.domain_index(),
+        route: device.route(),
+        adapter,
+        hop,
+        kind,
+    }
+}
+
+/// Returns the device and adapter on the other side of the link from lane adapter `adapter`.
+fn peer<'a>(
+    device: &'a Device,
+    adapter: &Adapter,
+    devices: &'a [Device],
+) -> Option<(&'a Device, &'a Adapter)> {
+    if adapter.is_upstream() {
+        let peer = adapter.upstream_device(devices)?;
+        Some((peer, peer.adapter(peer.downstream_adapter(device)?)?))
+    } else {
+        let peer = adapter.downstream_device(devices)?;
+        Some((peer, peer.adapter(peer.upstream_adapter()?)?))
+    }
+}
+
+fn check_end(
+    path: &Path,
+    devices: &[Device],
+    used: &HashSet<(u32, u64, u8, u16)>,
+    issues: &mut Vec<Issue>,
+) {
+    let last = path.hops().last().unwrap();
+    let out_adapter = last.out_adapter();
+    let out_hop = last.entry().out_hop();
+
+    if !out_adapter.is_lane() {
+        return;
+    }
+
+    let device = last.device();
+    let path = path.name().to_string();
+    let kind = match peer(device, out_adapter, devices) {
+        Some((peer, peer_adapter)) => {
+            let max_hop = peer_adapter.max_hop().unwrap_or(u16::MAX);
+            let candidates: Vec<_> = peer_adapter
+                .paths()
+                .into_iter()
+                .flatten()
+                .map(|p| p.in_hop())
+                .filter(|hop| {
+                    !used.contains(&(
+                        peer.domain_index(),
+                        peer.route(),
+                        peer_adapter.adapter(),
+                        *hop,
+                    ))
+                })
+                .collect();
+
+            if out_hop > max_hop {
+                IssueKind::HopIdOutOfRange {
+                    path,
+                    out_hop,
+                    max_hop,
+                }
+            } else if !candidates.is_empty() {
+                IssueKind::HopIdMismatch {
+                    path,
+                    out_hop,
+                    candidates,
+                }
+            } else {
+                IssueKind::DanglingHop { path, out_hop }
+            }
+        }
+        // DMA paths end at the link to the other host.
+        None if devices.iter().any(|d| {
+            d.is_xdomain()
+                && d.domain_index() == device.domain_index()
+                && device.downstream_adapter(d) == Some(out_adapter.adapter())
+        }) =>
+        {
+            return;
+        }
+        None => IssueKind::DanglingHop { path, out_hop },
+    };
+
+    issues.push(issue(
+        device,
+        out_adapter.adapter(),
+        Some(last.entry().in_hop()),
+        kind,
+    ));
+}
+
+fn check_weights(tunnel: &Tunnel, path: &Path, issues: &mut Vec<Issue>) {
+    let (priority, weight) = defaults(tunnel.kind(), path.name());
+
+    for hop in path.hops() {
+        let adapter = hop.in_adapter();
+        let in_hop = hop.entry().in_hop();
+        let Some(cs_1) = adapter.path_register_by_offset(in_hop * 2 + 1) else {
+            continue;
+        };
+
+        let actual = cs_1.field("Priority");
+        if actual != priority {
+            issues.push(issue(
+                hop.device(),
+                adapter.adapter(),
+                Some(in_hop),
+                IssueKind::Priority {
+                    path: path.name().to_string(),
+                    expected: priority,
+                    actual,
+                },
+            ));
+        }
+
+        let actual = cs_1.field("Weight");
+        if actual != weight {
+            issues.push(issue(
+                hop.device(),
+                adapter.adapter(),
+                Some(in_hop),
+                IssueKind::Weight {
+                    path: path.name().to_string(),
+                    expected: weight,
+                    actual,
+                },
+            ));
+        }
+    }
+}
+
+fn check_adapter(
+    device: &Device,
+    adapter: &Adapter,
+    used: &HashSet<(u32, u64, u8, u16)>,
+    issues: &mut Vec<Issue>,
+) {
+    let Some(paths) = adapter.paths() else {
+        return;
+    };
+
+    let mut allocated = 0;
+
+    for entry in paths {
+        let in_hop = entry.in_hop();
+
+        if device.adapter(entry.out_adapter()).is_none() {
+            issues.push(issue(
+                device,
+                adapter.adapter(),
+                Some(in_hop),
+                IssueKind::MissingAdapter {
+                    out_adapter: entry.out_adapter(),
+                },
+            ));
+        } else if !used.contains(&(
+            device.domain_index(),
+            device.route(),
+            adapter.adapter(),
+            in_hop,
+        )) {
+            issues.push(issue(
+                device,
+                adapter.adapter(),
+                Some(in_hop),
+                IssueKind::Unreachable,
+            ));
+        }
+
+        if let Some(cs_0) = adapter.path_register_by_offset(in_hop * 2) {
+            allocated += cs_0.field("Path Credits Allocated");
+        }
+    }
+
+    if let Some(buffers) = adapter
+        .register_by_name("ADP_CS_4")
+        .map(|r| r.field("Total Buffers"))
+        && buffers > 0
+        && allocated > buffers
+    {
+        issues.push(issue(
+            device,
+            adapter.adapter(),
+            None,
+            IssueKind::CreditsExceeded { allocated, buffers },
+        ));
+    }
+}
+
+/// Checks path configuration of `devices` against `tunnels`.
+///
+/// The devices must have adapters and paths read and `tunnels` must be all the tunnels discovered
+/// from them. Returns all the issues found, errors first.
+pub fn check(devices: &[Device], tunnels: &[Tunnel]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let used: HashSet<_> = tunnels
+        .iter()
+        .flat_map(|t| t.paths())
+        .flat_map(|p| p.hops())
+        .map(|hop| {
+            (
+                hop.device().domain_index(),
+                hop.device().route(),
+                hop.entry().in_adapter(),
+                hop.entry().in_hop(),
+            )
+        })
+        .collect();
+
+    for tunnel in tunnels {
+        for path in tunnel.paths() {
+            check_end(path, devices, &used, &mut issues);
+            check_weights(tunnel, path, &mut issues);
+        }
+    }
+
+    for device in devices.iter().filter(|d| d.is_router()) {
+        for adapter in device.adapters().into_iter().flatten() {
+            check_adapter(device, adapter, &used, &mut issues);
+        }
+    }
+
+    issues.sort_by_key(|i| Reverse(i.severity()));
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        debugfs::Type,
+        test_util::{ADP_CS, adapter, path_cs_0, path_cs_1, router, upstream_lane},
+    };
+
+    // Path entry with the priority and weight recommended for PCIe.
+    fn entry(in_hop: u16, out_adapter: u8, out_hop: u16) -> (u16, u32, u32) {
+        (in_hop, path_cs_0(out_adapter, out_hop, 0), path_cs_1(3, 1))
+    }
+
+    // PCIe tunnel between adapters 9 of the host router and 0-1 through lane adapters 1.
+    fn pcie_tunnel() -> Vec<Device> {
+        let mut lane = adapter(0, 1, Type::Lane);
+        lane.set_test_paths(&[entry(8, 9, 8)]);
+        let mut down = adapter(0, 9, Type::PcieDown);
+        down.set_test_paths(&[entry(8, 1, 8)]);
+        let mut up_lane = upstream_lane(1, 1);
+        up_lane.set_test_paths(&[entry(8, 9, 8)]);
+        let mut up = adapter(1, 9, Type::PcieUp);
+        up.set_test_paths(&[entry(8, 1, 8)]);
+
+        vec![router(0, vec![lane, down]), router(1, vec![up_lane, up])]
+    }
+
+    fn adapter_mut(devices: &mut [Device], route: u64, adapter: u8) -> &mut Adapter {
+        devices
+            .iter_mut()
+            .find(|d| d.route() == route)
+            .and_then(|d| d.adapter_mut(adapter))
+            .unwrap()
+    }
+
+    fn found(devices: &[Device]) -> Vec<(u64, u8, Option<u16>, IssueKind)> {
+        let tunnels = Tunnel::discover_all(devices);
+        check(devices, &tunnels)
+            .into_iter()
+            .map(|i| (i.route(), i.adapter(), i.hop(), i.kind))
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        assert!(found(&pcie_tunnel()).is_empty());
+    }
+
+    #[test]
+    fn dangling() {
+        let path = || String::from("PCIe Down");
+
+        // Nothing on the other side of the link.
+        let mut devices = pcie_tunnel();
+        adapter_mut(&mut devices, 0, 9).set_test_paths(&[entry(8, 1, 12)]);
+        adapter_mut(&mut devices, 1, 1).set_test_paths(&[]);
+        assert_eq!(
+            found(&devices),
+            [(
+                0,
+                1,
+                Some(8),
+                IssueKind::DanglingHop {
+                    path: path(),
+                    out_hop: 12
+                }
+            )]
+        );
+
+        // No device connected to the lane adapter.
+        devices.truncate(1);
+        assert_eq!(
+            found(&devices),
+            [
+                (
+                    0,
+                    1,
+                    Some(8),
+                    IssueKind::DanglingHop {
+                        path: path(),
+                        out_hop: 12
+                    }
+                ),
+                (0, 1, Some(8), IssueKind::Unreachable),
+            ]
+        );
+    }
+
+    #[test]
+    fn hopids() {
+        let path = || String::from("PCIe Down");
+
+        let mut devices = pcie_tunnel();
+        adapter_mut(&mut devices, 0, 9).set_test_paths(&[entry(8, 1, 25)]);
+        assert_eq!(
+            found(&devices),
+            [
+                (
+                    0,
+                    1,
+                    Some(8),
+                    IssueKind::HopIdOutOfRange {
+                        path: path(),
+                        out_hop: 25,
+                        max_hop: 19
+                    }
+                ),
+                (1, 1, Some(8), IssueKind::Unreachable),
+            ]
+        );
+
+        adapter_mut(&mut devices, 0, 9).set_test_paths(&[entry(8, 1, 9)]);
+        assert_eq!(
+            found(&devices),
+            [
+                (
+                    0,
+                    1,
+                    Some(8),
+                    IssueKind::HopIdMismatch {
+                        path: path(),
+                        out_hop: 9,
+                        candidates: vec![8]
+                    }
+                ),
+                (1, 1, Some(8), IssueKind::Unreachable),
+            ]
+        );
+    }
+
+    #[test]
+    fn entries() {
+        let mut devices = pcie_tunnel();
+        adapter_mut(&mut devices, 0, 9).set_test_paths(&[entry(8, 1, 8), entry(9, 30, 8)]);
+        adapter_mut(&mut devices, 1, 1).set_test_paths(&[entry(8, 9, 8), entry(12, 9, 9)]);
+        assert_eq!(
+            found(&devices),
+            [
+                (0, 9, Some(9), IssueKind::MissingAdapter { out_adapter: 30 }),
+                (1, 1, Some(12), IssueKind::Unreachable),
+            ]
+        );
+    }
+
+    #[test]
+    fn credits() {
+        let mut devices = pcie_tunnel();
+        let down = adapter_mut(&mut devices, 0, 9);
+        // Total Buffers 10 in ADP_CS_4.
+        let mut regs = ADP_CS;
+        regs[4] = 10 << 20;
+        down.set_test_registers(&regs, &[1 << 31]);
+        down.set_test_paths(&[(8, path_cs_0(1, 8, 12), path_cs_1(3, 1))]);
+        assert_eq!(
+            found(&devices),
+            [(
+                0,
+                9,
+                None,
+                IssueKind::CreditsExceeded {
+                    allocated: 12,
+                    buffers: 10
+                }
+            )]
+        );
+
+        adapter_mut(&mut devices, 0, 9).set_test_paths(&[(
+            8,
+            path_cs_0(1, 8, 10),
+            path_cs_1(3, 1),
+        )]);
+        assert!(found(&devices).is_empty());
+    }
+
+    #[test]
+    fn weights() {
+        let path = || String::from("PCIe Up");
+
+        let mut devices = pcie_tunnel();
+        adapter_mut(&mut devices, 1, 9).set_test_paths(&[(8, path_cs_0(1, 8, 0), path_cs_1(2, 3))]);
+        adapter_mut(&mut devices, 1, 1).set_test_paths(&[entry(8, 9, 8), entry(9, 30, 8)]);
+
+        let found = found(&devices);
+        // Errors are listed first.
+        assert_eq!(found[0].3, IssueKind::MissingAdapter { out_adapter: 30 });
+        assert_eq!(
+            found[1..],
+            [
+                (
+                    1,
+                    9,
+                    Some(8),
+                    IssueKind::Priority {
+                        path: path(),
+                        expected: 3,
+                        actual: 2
+                    }
+                ),
+                (
+                    1,
+                    9,
+                    Some(8),
+                    IssueKind::Weight {
+                        path: path(),
+                        expected: 1,
+                        actual: 3
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn issues() {
+        assert_eq!(defaults(tunnel::Type::DisplayPort, "Video"), (1, 1));
+        assert_eq!(defaults(tunnel::Type::DisplayPort, "AUX TX"), (2, 1));
+        assert_eq!(defaults(tunnel::Type::Usb3, "USB 3 Down"), (3, 2));
+
+        let weight = Issue {
+            domain: 0,
+            route: 0x301,
+            adapter: 3,
+            hop: Some(8),
+            kind: IssueKind::Weight {
+                path: String::from("PCIe Down"),
+                expected: 1,
+                actual: 2,
+            },
+        };
+        assert_eq!(weight.severity(), Severity::Warning);
+        assert_eq!(
+            weight.to_string(),
+            "warning: Domain 0 Route 301 Adapter 3 HopID 8: PCIe Down path weight 2, expected 1"
+        );
+
+        let credits = Issue {
+            domain: 0,
+            route: 0,
+            adapter: 1,
+            hop: None,
+            kind: IssueKind::CreditsExceeded {
+                allocated: 130,
+                buffers: 120,
+            },
+        };
+        assert_eq!(credits.severity(), Severity::Error);
+        assert_eq!(
+            credits.to_string(),
+            "error: Domain 0 Route 0 Adapter 1: 130 path credits allocated but only 120 buffers"
+        );
+
+        let mismatch = Issue {
+            domain: 0,
+            route: 1,
+            adapter: 1,
+            hop: Some(9),
+            kind: IssueKind::HopIdMismatch {
+                path: String::from("Video"),
+                out_hop: 10,
+                candidates: vec![11, 12],
+            },
+        };
+        assert!(mismatch.to_string().ends_with("other side: 11, 12"));
+    }
+}
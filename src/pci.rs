@@ -0,0 +1,448 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Mapping between PCIe adapters and Linux PCI devices.
+//!
+//! Each PCIe adapter of a router is connected to a port of the PCIe switch (or root complex)
+//! inside the router. This resolves the PCI bridge under `/sys/bus/pci` for each PCIe adapter.
+//! The device and function numbers of the ports come from the router DROM. Bus numbers are found
+//! by following the PCIe tunnels from the host router down to each device router.
+//!
+//! Some routers do not list their PCIe adapters in the DROM. For these
+//! [`bridges_with_router_ops()`] can get the numbers with the Get PCIe Downstream Entry Mapping
+//! router operation instead. Router operations are sent to the router while the kernel
+//! connection manager may be using it so this is never done by default.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::pci;
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut devices = tbtools::find_devices(None)?;
+//!
+//! for device in &mut devices {
+//!     if device.is_router() {
+//!         device.read_adapters()?;
+//!         for adapter in device.adapters_mut().unwrap() {
+//!             // Needed to follow the PCIe tunnels.
+//!             adapter.read_paths().ok();
+//!         }
+//!         // DROM is optional.
+//!         device.read_drom().ok();
+//!     }
+//! }
+//!
+//! for bridge in pci::bridges(&devices) {
+//!     println!("{}: {}", bridge.adapter(), bridge.bdf());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Device,
+    debugfs::{Adapter, Type},
+    drom::DromEntry,
+    router_op::RouterOperation,
+    sysfs,
+    tunnel::Tunnel,
+};
+
+const SYSFS_PCI_DEVICES: &str = "/sys/bus/pci/devices";
+
+/// PCI bridge connected to a PCIe adapter of a router.
+#[derive(Clone, Debug)]
+pub struct Bridge {
+    domain: u32,
+    route: u64,
+    adapter: u8,
+    bdf: String,
+    syspath: PathBuf,
+}
+
+impl Bridge {
+    /// Returns domain of the router.
+    pub fn domain(&self) -> u32 {
+        self.domain
+    }
+
+    /// Returns route string of the router.
+    pub fn route(&self) -> u64 {
+        self.route
+    }
+
+    /// Returns PCIe adapter number.
+    pub fn adapter(&self) -> u8 {
+        self.adapter
+    }
+
+    /// Returns PCI address of the bridge such as `0000:00:07.0`.
+    pub fn bdf(&self) -> &str {
+        &self.bdf
+    }
+
+    /// Returns path of the bridge in `sysfs`.
+    pub fn sysfs_path(&self) -> &Path {
+        &self.syspath
+    }
+
+    fn attribute(&self, name: &str) -> Option<String> {
+        let value = fs::read_to_string(self.syspath.join(name)).ok()?;
+        Some(value.trim_end().to_string())
+    }
+
+    /// Returns current PCIe link speed such as `16.0 GT/s PCIe`.
+    pub fn link_speed(&self) -> Option<String> {
+        self.attribute("current_link_speed")
+    }
+
+    /// Returns current PCIe link width.
+    pub fn link_width(&self) -> Option<u32> {
+        self.attribute("current_link_width")?.parse().ok()
+    }
+
+    /// Returns name of the driver bound to the bridge.
+    pub fn driver(&self) -> Option<String> {
+        let driver = fs::read_link(self.syspath.join("driver")).ok()?;
+        Some(driver.file_name()?.to_str()?.to_string())
+    }
+
+    /// Returns PCI address followed by the link speed, width and driver that are available, for
+    /// example `0000:00:07.0 16.0 GT/s PCIe x4 (pcieport)`.
+    pub fn description(&self) -> String {
+        let mut description = self.bdf.clone();
+        if let Some(speed) = self.link_speed() {
+            description.push_str(&format!(" {speed}"));
+        }
+        if let Some(width) = self.link_width() {
+            description.push_str(&format!(" x{width}"));
+        }
+        if let Some(driver) = self.driver() {
+            description.push_str(&format!(" ({driver})"));
+        }
+        description
+    }
+
+    fn secondary_bus(&self) -> Option<u8> {
+        self.attribute("secondary_bus_number")?.parse().ok()
+    }
+}
+
+/// Splits PCI address into segment and bus.
+fn segment_bus(bdf: &str) -> Option<(u16, u8)> {
+    let mut parts = bdf.split(':');
+    let segment = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let bus = u8::from_str_radix(parts.next()?, 16).ok()?;
+    Some((segment, bus))
+}
+
+fn pci_device(devices: &Path, segment: u16, bus: u8, slot: (u8, u8)) -> Option<(String, PathBuf)> {
+    let bdf = format!("{segment:04x}:{bus:02x}:{:02x}.{:x}", slot.0, slot.1);
+    let syspath = fs::canonicalize(devices.join(&bdf)).ok()?;
+    Some((bdf, syspath))
+}
+
+/// Returns PCI segment and bus of the ports connected to the host router PCIe adapters.
+fn host_bus(host: &Device) -> Option<(u16, u8)> {
+    // Host router is below the domain which is below the NHI.
+    let nhi = host.sysfs_path().parent()?.parent()?.to_path_buf();
+    let (segment, bus) = segment_bus(nhi.file_name()?.to_str()?)?;
+
+    // Discrete host routers have the NHI behind a downstream port of the same PCIe switch as the
+    // PCIe adapters. Integrated ones have the ports on the same bus as the NHI.
+    match nhi.parent()?.file_name()?.to_str().and_then(segment_bus) {
+        Some(parent) => Some(parent),
+        None => Some((segment, bus)),
+    }
+}
+
+/// Returns PCI device and function numbers of the PCIe adapters of `device`.
+///
+/// If the DROM does not have them and `router_ops` is `true` runs the Get PCIe Downstream Entry
+/// Mapping router operation.
+fn slots(device: &Device, router_ops: bool) -> HashMap<u8, (u8, u8)> {
+    let mut slots = HashMap::new();
+
+    if let Some(drom) = device.drom() {
+        for entry in drom.entries() {
+            match entry {
+                DromEntry::PcieUpAdapter {
+                    adapter_num,
+                    function_num,
+                    device_num,
+                }
+                | DromEntry::PcieDownAdapter {
+                    adapter_num,
+                    function_num,
+                    device_num,
+                } => {
+                    slots.insert(adapter_num, (device_num, function_num));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    if router_ops
+        && slots.is_empty()
+        && let Ok(op) = RouterOperation::new(device)
+        && let Ok(entries) = op.pcie_downstream_mapping()
+    {
+        for entry in entries {
+            if let Some(adapter) = entry.adapter() {
+                let rid = if entry.non_fpb_rid() != 0 {
+                    entry.non_fpb_rid()
+                } else {
+                    entry.fpb_rid()
+                };
+                slots.insert(adapter, (((rid >> 3) & 0x1f) as u8, (rid & 0x7) as u8));
+            }
+        }
+    }
+
+    slots
+}
+
+fn pcie_adapters(device: &Device, kind: Type) -> impl Iterator<Item = &Adapter> {
+    device
+        .adapters()
+        .into_iter()
+        .flatten()
+        .filter(move |a| a.kind() == kind)
+}
+
+/// Resolves PCI bridges of all PCIe adapters in `devices`.
+///
+/// Adapters of the routers must be read. For device routers the path config spaces must be read
+/// as well so that the PCIe tunnels leading to them can be followed. If DROM of a router is read
+/// it is used to find the device and function numbers of its ports. Adapters that cannot be
+/// resolved are not included.
+///
+/// Only the DROM is used so nothing is sent to the routers. See
+/// [`bridges_with_router_ops()`] for routers without PCIe adapter entries in the DROM.
+pub fn bridges(devices: &[Device]) -> Vec<Bridge> {
+    bridges_in(&sysfs::path_buf(SYSFS_PCI_DEVICES), devices, false)
+}
+
+/// Resolves PCI bridges of all PCIe adapters in `devices` using router operations if needed.
+///
+/// Same as [`bridges()`] but for routers whose DROM does not have the PCIe adapter entries runs
+/// the Get PCIe Downstream Entry Mapping router operation. This races with the kernel connection
+/// manager that may be sending its own router operations at the same time so only use this when
+/// the connection manager is idle.
+pub fn bridges_with_router_ops(devices: &[Device]) -> Vec<Bridge> {
+    bridges_in(&sysfs::path_buf(SYSFS_PCI_DEVICES), devices, true)
+}
+
+fn bridges_in(pci_devices: &Path, devices: &[Device], router_ops: bool) -> Vec<Bridge> {
+    let mut routers: Vec<_> = devices.iter().filter(|d| d.is_router()).collect();
+    // Parents must be resolved before their children.
+    routers.sort_by_key(|d| d.depth());
+
+    let mut bridges = Vec::new();
+    // Maps the PCIe Up adapter of a device router to the segment and bus it is on.
+    let mut upstream = HashMap::new();
+
+    for router in routers {
+        let slots = slots(router, router_ops);
+
+        let down_bus = if router.is_host_router() {
+            host_bus(router)
+        } else {
+            pcie_adapters(router, Type::PcieUp).find_map(|adapter| {
+                let (segment, bus) =
+                    upstream.get(&(router.domain_index(), router.route(), adapter.adapter()))?;
+                // Upstream port is normally the only device on the bus.
+                let slot = slots.get(&adapter.adapter()).copied().unwrap_or((0, 0));
+                let (bdf, syspath) = pci_device(pci_devices, *segment, *bus, slot)?;
+                let bridge = Bridge {
+                    domain: router.domain_index(),
+                    route: router.route(),
+                    adapter: adapter.adapter(),
+                    bdf,
+                    syspath,
+                };
+                let down_bus = bridge.secondary_bus().map(|bus| (*segment, bus));
+                bridges.push(bridge);
+                down_bus
+            })
+        };
+
+        let Some((segment, bus)) = down_bus else {
+            continue;
+        };
+
+        for adapter in pcie_adapters(router, Type::PcieDown) {
+            let Some(slot) = slots.get(&adapter.adapter()) else {
+                continue;
+            };
+            let Some((bdf, syspath)) = pci_device(pci_devices, segment, bus, *slot) else {
+                continue;
+            };
+            let bridge = Bridge {
+                domain: router.domain_index(),
+                route: router.route(),
+                adapter: adapter.adapter(),
+                bdf,
+                syspath,
+            };
+
+            // Follow the tunnel to the PCIe Up adapter of the device router below.
+            if let Some(secondary) = bridge.secondary_bus()
+                && let Some(tunnels) = Tunnel::discover(router, adapter, devices)
+            {
+                for tunnel in tunnels {
+                    upstream.insert(
+                        (
+                            tunnel.dst_device().domain_index(),
+                            tunnel.dst_device().route(),
+                            tunnel.dst_adapter().adapter(),
+                        ),
+                        (segment, secondary),
+                    );
+                }
+            }
+
+            bridges.push(bridge);
+        }
+    }
+
+    bridges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        drom::Drom,
+        test_util::{
+            NHI, TempDir, adapter, path_cs_0, path_cs_1, router, router_at, upstream_lane, write,
+        },
+    };
+    use std::os::unix::fs::symlink;
+
+    // USB4 DROM with PCIe adapter entries `(adapter, device, function)`.
+    fn drom(device: &Device, entries: &[(u8, u8, u8)]) -> Drom {
+        let mut bytes = vec![0; 16];
+        bytes[13] = 3;
+        for (adapter, dev, func) in entries {
+            bytes.extend([3, 0x80 | adapter, (dev & 0x7) << 5 | (dev >> 3) << 3 | func]);
+        }
+        let length = (bytes.len() - 13) as u16;
+        bytes[14..16].copy_from_slice(&length.to_le_bytes());
+        Drom::parse(&bytes, device.adapters().unwrap()).unwrap()
+    }
+
+    fn port(root: &Path, bdf: &str, secondary: u8) {
+        let port = root.join("sys/devices/pci0000:00").join(bdf);
+        write(&port.join("secondary_bus_number"), format!("{secondary}\n"));
+        let devices = root.join("sys/bus/pci/devices");
+        fs::create_dir_all(&devices).unwrap();
+        symlink(&port, devices.join(bdf)).unwrap();
+    }
+
+    #[test]
+    fn bridge() {
+        let root = TempDir::new("pci");
+        let port = root.join("sys/devices/pci0000:00/0000:00:07.0");
+        let devices = root.join("sys/bus/pci/devices");
+        let driver = root.join("sys/bus/pci/drivers/pcieport");
+
+        fs::create_dir_all(&port).unwrap();
+        fs::create_dir_all(&devices).unwrap();
+        fs::create_dir_all(&driver).unwrap();
+        fs::write(port.join("current_link_speed"), "16.0 GT/s PCIe\n").unwrap();
+        fs::write(port.join("current_link_width"), "4\n").unwrap();
+        fs::write(port.join("secondary_bus_number"), "32\n").unwrap();
+        symlink(&driver, port.join("driver")).unwrap();
+        symlink(&port, devices.join("0000:00:07.0")).unwrap();
+
+        assert!(pci_device(&devices, 0, 0, (7, 1)).is_none());
+        let (bdf, syspath) = pci_device(&devices, 0, 0, (7, 0)).unwrap();
+        let bridge = Bridge {
+            domain: 0,
+            route: 0,
+            adapter: 16,
+            bdf,
+            syspath,
+        };
+
+        assert_eq!(bridge.bdf(), "0000:00:07.0");
+        assert_eq!(bridge.link_speed(), Some(String::from("16.0 GT/s PCIe")));
+        assert_eq!(bridge.link_width(), Some(4));
+        assert_eq!(bridge.driver(), Some(String::from("pcieport")));
+        assert_eq!(bridge.secondary_bus(), Some(32));
+        assert_eq!(
+            bridge.description(),
+            "0000:00:07.0 16.0 GT/s PCIe x4 (pcieport)"
+        );
+
+        assert_eq!(segment_bus("0000:3a:00.0"), Some((0, 0x3a)));
+        assert_eq!(segment_bus("domain0"), None);
+    }
+
+    #[test]
+    fn bridges() {
+        let root = TempDir::new("pci-bridges");
+        port(&root, "0000:00:07.0", 0x20);
+        port(&root, "0000:20:00.0", 0x21);
+        port(&root, "0000:21:01.0", 0x22);
+        let pci_devices = root.join("sys/bus/pci/devices");
+
+        // PCIe tunnel from host router adapter 9 through lane adapters 1 to adapter 9 of 0-1.
+        let pcie = path_cs_1(3, 1);
+        let mut host_lane = adapter(0, 1, Type::Lane);
+        host_lane.set_test_paths(&[(8, path_cs_0(9, 8, 0), pcie)]);
+        let mut host_down = adapter(0, 9, Type::PcieDown);
+        host_down.set_test_paths(&[(8, path_cs_0(1, 8, 0), pcie)]);
+        let mut host = router(0, vec![host_lane, host_down]);
+        host.drom = Some(drom(&host, &[(9, 7, 0)]));
+
+        let mut lane = upstream_lane(1, 1);
+        lane.set_test_paths(&[(8, path_cs_0(9, 8, 0), pcie)]);
+        let mut up = adapter(1, 9, Type::PcieUp);
+        up.set_test_paths(&[(8, path_cs_0(1, 8, 0), pcie)]);
+        let down = adapter(1, 10, Type::PcieDown);
+        let mut device = router(1, vec![lane, up, down]);
+        device.drom = Some(drom(&device, &[(9, 0, 0), (10, 1, 0)]));
+
+        assert_eq!(slots(&host, false), HashMap::from([(9, (7, 0))]));
+        assert_eq!(
+            slots(&device, false),
+            HashMap::from([(9, (0, 0)), (10, (1, 0))])
+        );
+
+        // Integrated host router has the ports on the NHI bus.
+        assert_eq!(host_bus(&host), Some((0, 0)));
+        let discrete = Path::new("/sys/devices/pci0000:00/0000:00:01.0/0000:01:00.0/0000:02:02.0");
+        let discrete = router_at(&discrete.join("0000:04:00.0"), 0, Vec::new());
+        assert_eq!(host_bus(&discrete), Some((0, 2)));
+
+        let devices = vec![host, device];
+        let bridges: Vec<_> = bridges_in(&pci_devices, &devices, false)
+            .iter()
+            .map(|b| (b.route(), b.adapter(), b.bdf().to_string()))
+            .collect();
+        assert_eq!(
+            bridges,
+            [
+                (0, 9, String::from("0000:00:07.0")),
+                (1, 9, String::from("0000:20:00.0")),
+                (1, 10, String::from("0000:21:01.0")),
+            ]
+        );
+
+        // Without DROM nothing is resolved unless router operations are allowed.
+        let host = router_at(Path::new(NHI), 0, vec![adapter(0, 9, Type::PcieDown)]);
+        assert!(slots(&host, false).is_empty());
+        assert!(bridges_in(&pci_devices, &[host], false).is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Rule based authorization policy.
+//!
+//! A policy is a list of rules read from a text file, one rule per line. Each rule starts with an
+//! action followed by any number of `key=value` matches. All the matches of a rule must match the
+//! router for the rule to apply and the first rule that applies decides what is done with the
+//! router. Empty lines and anything after `#` are ignored.
+//!
+//! The actions are:
+//!
+//! * `authorize` - Authorize the router (`authorized` = `1`).
+//! * `challenge` - Challenge the router with a key from the [`KeyStore`](crate::keystore::KeyStore)
+//!   or enroll a new key if there is none stored yet.
+//! * `reject` - Leave the router unauthorized.
+//!
+//! The matches are:
+//!
+//! * `vendor` - Vendor ID of the router.
+//! * `device` - Device ID of the router.
+//! * `unique_id` - UUID of the router.
+//! * `generation` - Thunderbolt generation of the router.
+//! * `iommu` - Whether the domain has IOMMU DMA protection (`yes` or `no`).
+//! * `security` - Security level of the domain, such as `user` or `secure`.
+//!
+//! # Examples
+//! ```
+//! # use std::io;
+//! use tbtools::policy::{Action, Policy};
+//!
+//! # fn main() -> io::Result<()> {
+//! let policy = Policy::parse(
+//!     "# Intel devices are trusted when IOMMU protects the memory\n\
+//!      authorize vendor=0x8087 iommu=yes\n\
+//!      challenge security=secure\n\
+//!      reject\n",
+//! )?;
+//! assert_eq!(policy.rules().len(), 3);
+//! assert_eq!(policy.rules()[1].action(), Action::Challenge);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt::{self, Display},
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::{Device, SecurityLevel, util};
+
+/// Default location of the policy file.
+pub const DEFAULT_PATH: &str = "/etc/tbtools/policy";
+
+/// What is done with a router that matches a rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Authorize the router.
+    Authorize,
+    /// Challenge the router with stored key or enroll a new one.
+    Challenge,
+    /// Leave the router unauthorized.
+    Reject,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Authorize => "authorize",
+            Self::Challenge => "challenge",
+            Self::Reject => "reject",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Single match of a rule.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Match {
+    /// Vendor ID of the router.
+    Vendor(u16),
+    /// Device ID of the router.
+    Device(u16),
+    /// UUID of the router.
+    UniqueId(uuid::Uuid),
+    /// Thunderbolt generation of the router.
+    Generation(u8),
+    /// IOMMU DMA protection of the domain.
+    Iommu(bool),
+    /// Security level of the domain.
+    SecurityLevel(SecurityLevel),
+}
+
+impl Match {
+    fn parse(key: &str, value: &str) -> std::result::Result<Self, String> {
+        let invalid = || format!("invalid value '{value}' for {key}");
+
+        let m = match key {
+            "vendor" => Self::Vendor(util::parse_number(value).ok_or_else(invalid)?),
+            "device" => Self::Device(util::parse_number(value).ok_or_else(invalid)?),
+            "unique_id" => Self::UniqueId(uuid::Uuid::parse_str(value).map_err(|_| invalid())?),
+            "generation" => Self::Generation(util::parse_number(value).ok_or_else(invalid)?),
+            "iommu" => Self::Iommu(match value {
+                "yes" => true,
+                "no" => false,
+                _ => return Err(invalid()),
+            }),
+            "security" => match SecurityLevel::from(value) {
+                SecurityLevel::Unknown => return Err(invalid()),
+                level => Self::SecurityLevel(level),
+            },
+            _ => return Err(format!("unknown match '{key}'")),
+        };
+
+        Ok(m)
+    }
+
+    fn matches(&self, props: &Properties) -> bool {
+        match self {
+            Self::Vendor(vendor) => props.vendor == *vendor,
+            Self::Device(device) => props.device == *device,
+            Self::UniqueId(uuid) => props.unique_id.as_ref() == Some(uuid),
+            Self::Generation(generation) => props.generation == Some(*generation),
+            Self::Iommu(iommu) => props.iommu == Some(*iommu),
+            Self::SecurityLevel(level) => props.security_level.as_ref() == Some(level),
+        }
+    }
+}
+
+/// Properties of the router the rules are matched against.
+struct Properties {
+    vendor: u16,
+    device: u16,
+    unique_id: Option<uuid::Uuid>,
+    generation: Option<u8>,
+    iommu: Option<bool>,
+    security_level: Option<SecurityLevel>,
+}
+
+impl Properties {
+    fn new(device: &Device) -> Self {
+        // IOMMU and security level are attributes of the domain.
+        let domain = device.domain();
+        Self {
+            vendor: device.vendor(),
+            device: device.device(),
+            unique_id: device
+                .unique_id()
+                .and_then(|id| uuid::Uuid::parse_str(&id).ok()),
+            generation: device.generation(),
+            iommu: domain.as_ref().and_then(|d| d.iommu_dma_protection()),
+            security_level: domain.as_ref().and_then(|d| d.security_level()),
+        }
+    }
+}
+
+/// Single rule of the policy.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    line: usize,
+    action: Action,
+    matches: Vec<Match>,
+}
+
+impl Rule {
+    /// Returns line number of the rule in the policy file.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the action of the rule.
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Returns the matches of the rule. Empty if the rule matches all routers.
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    fn applies(&self, props: &Properties) -> bool {
+        self.matches.iter().all(|m| m.matches(props))
+    }
+}
+
+/// Authorization policy.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Parses policy from string.
+    ///
+    /// Returns [`ErrorKind::InvalidData`] with the offending line number if the policy is not
+    /// valid.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line_num = i + 1;
+            let err =
+                |msg: String| Error::new(ErrorKind::InvalidData, format!("line {line_num}: {msg}"));
+
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(action) = words.next() else {
+                continue;
+            };
+
+            let action = match action {
+                "authorize" => Action::Authorize,
+                "challenge" => Action::Challenge,
+                "reject" => Action::Reject,
+                _ => return Err(err(format!("unknown action '{action}'"))),
+            };
+
+            let mut matches = Vec::new();
+            for word in words {
+                let (key, value) = word
+                    .split_once('=')
+                    .ok_or_else(|| err(format!("expected key=value, got '{word}'")))?;
+                matches.push(Match::parse(key, value).map_err(err)?);
+            }
+
+            rules.push(Rule {
+                line: line_num,
+                action,
+                matches,
+            });
+        }
+
+        Ok(Self { rules })
+    }
+
+    /// Reads and parses policy file from `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)?;
+        Self::parse(&s).map_err(|err| Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+
+    /// Returns rules of the policy.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Returns the first rule that applies to `device` or `None` if there is no such rule.
+    pub fn evaluate(&self, device: &Device) -> Option<&Rule> {
+        let props = Properties::new(device);
+        self.evaluate_properties(&props)
+    }
+
+    fn evaluate_properties(&self, props: &Properties) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.applies(props))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UUID: &str = "1d7f3a2c-0d3e-4f00-8000-123456789abc";
+
+    fn props() -> Properties {
+        Properties {
+            vendor: 0x8087,
+            device: 0x1234,
+            unique_id: uuid::Uuid::parse_str(UUID).ok(),
+            generation: Some(4),
+            iommu: Some(false),
+            security_level: Some(SecurityLevel::User),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let policy = Policy::parse(&format!(
+            "# comment\n\
+             \n\
+             reject iommu=no vendor=0x8086   # comment\n\
+             challenge unique_id={}\n\
+             authorize vendor=0x8087 device=0x1234 generation=4 security=user\n\
+             reject\n",
+            UUID.to_uppercase()
+        ))
+        .unwrap();
+
+        let rules = policy.rules();
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[0].line(), 3);
+        assert_eq!(rules[0].action(), Action::Reject);
+        assert_eq!(
+            rules[0].matches(),
+            [Match::Iommu(false), Match::Vendor(0x8086)]
+        );
+        assert_eq!(rules[1].action(), Action::Challenge);
+        assert_eq!(rules[2].matches().len(), 4);
+        assert!(rules[3].matches().is_empty());
+
+        for (s, line) in [
+            ("allow", 1),
+            ("authorize\nauthorize vendor", 2),
+            ("authorize foo=1", 1),
+            ("authorize iommu=maybe", 1),
+            ("authorize security=high", 1),
+            ("authorize unique_id=1234", 1),
+            ("\n\nauthorize vendor=0x10000", 3),
+        ] {
+            let err = Policy::parse(s).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert!(
+                err.to_string().starts_with(&format!("line {line}:")),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate() {
+        let policy = Policy::parse(
+            "reject vendor=0x8086\n\
+             challenge unique_id=1D7F3A2C-0D3E-4F00-8000-123456789ABC security=secure\n\
+             authorize vendor=0x8087 iommu=no generation=4\n\
+             reject\n",
+        )
+        .unwrap();
+
+        let mut props = props();
+        assert_eq!(policy.evaluate_properties(&props).unwrap().line(), 3);
+
+        props.security_level = Some(SecurityLevel::Secure);
+        assert_eq!(policy.evaluate_properties(&props).unwrap().line(), 2);
+
+        props.security_level = None;
+        props.generation = Some(3);
+        assert_eq!(policy.evaluate_properties(&props).unwrap().line(), 4);
+
+        props.vendor = 0x8086;
+        assert_eq!(
+            policy.evaluate_properties(&props).unwrap().action(),
+            Action::Reject
+        );
+
+        assert!(
+            Policy::parse("authorize vendor=0x8087\n")
+                .unwrap()
+                .evaluate_properties(&props)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,760 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Router operations.
+//!
+//! USB4 routers implement operations that are run by writing the input data to `ROUTER_CS_9` -
+//! `ROUTER_CS_24`, metadata to `ROUTER_CS_25` and finally the opcode with Operation Valid bit set
+//! to `ROUTER_CS_26`. The router clears the Operation Valid bit when the operation completes and
+//! places the results back to the same registers.
+//!
+//! The registers are accessed through the current [`Backend`] so with the default one the kernel
+//! must have `CONFIG_USB4_DEBUGFS_WRITE=y` set. Note the kernel connection manager runs router
+//! operations too and there is nothing preventing the two from racing.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::{Address, router_op::RouterOperation};
+//!
+//! # fn main() -> io::Result<()> {
+//! let address = Address::Router { domain: 0, route: 0 };
+//! if let Some(device) = tbtools::find_device(&address)? {
+//!     let op = RouterOperation::new(&device)?;
+//!     println!("NVM sector size: {}", op.nvm_sector_size()?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt::{self, Display},
+    io::{Error, ErrorKind, Result},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ConfigSpace, Device,
+    backend::{self, Backend},
+    debugfs::Register,
+    genmask_t, usb4,
+};
+
+/// Default time to wait for an operation to complete.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Maximum number of data double words an operation can take or return.
+pub const DATA_DWORDS: usize = 16;
+
+/// Number of bytes [`RouterOperation::nvm_read_all()`] reads at the time.
+pub const NVM_READ_CHUNK: usize = DATA_DWORDS * 4;
+/// Number of times [`RouterOperation::nvm_read_all()`] retries reading a chunk.
+pub const NVM_READ_RETRIES: u32 = 3;
+
+const NVM_READ_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+const NVM_READ_OFFSET_MASK: u32 = genmask_t!(u32, 23, 2);
+const NVM_READ_LENGTH_MASK: u32 = genmask_t!(u32, 27, 24);
+const NVM_READ_LENGTH_SHIFT: u32 = 24;
+const DROM_ADDRESS_MASK: u32 = genmask_t!(u32, 14, 2);
+const DROM_SIZE_MASK: u32 = genmask_t!(u32, 19, 15);
+const DROM_SIZE_SHIFT: u32 = 15;
+const NVM_SECTOR_SIZE_MASK: u32 = genmask_t!(u32, 23, 0);
+const DP_RESOURCE_ADAPTER_MASK: u32 = genmask_t!(u32, 5, 0);
+const PCIE_MAPPING_ENTRIES_MASK: u32 = genmask_t!(u32, 7, 0);
+const PCIE_MAPPING_INDEX_MASK: u32 = genmask_t!(u32, 15, 8);
+const PCIE_MAPPING_INDEX_SHIFT: u32 = 8;
+const PCIE_MAPPING_NATIVE: u32 = 1 << 0;
+const PCIE_MAPPING_ADAPTER_MASK: u32 = genmask_t!(u32, 6, 1);
+const PCIE_MAPPING_ADAPTER_SHIFT: u32 = 1;
+
+/// Router operation opcodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    QueryDpResource,
+    AllocateDpResource,
+    DeallocateDpResource,
+    ConnectDpOut,
+    NvmWrite,
+    NvmAuthenticateWrite,
+    NvmRead,
+    NvmSetOffset,
+    DromRead,
+    GetNvmSectorSize,
+    GetPcieDownstreamMapping,
+    GetCapabilities,
+    SetCapabilities,
+    BufferAllocationRequest,
+    BlockSidebandPortOperations,
+    UnblockSidebandPortOperations,
+    GetContainerId,
+    GetConnectorsInformation,
+    /// Opcode not known by the library, for example vendor specific one.
+    Other(u16),
+}
+
+impl From<Opcode> for u16 {
+    fn from(opcode: Opcode) -> Self {
+        match opcode {
+            Opcode::QueryDpResource => 0x10,
+            Opcode::AllocateDpResource => 0x11,
+            Opcode::DeallocateDpResource => 0x12,
+            Opcode::ConnectDpOut => 0x13,
+            Opcode::NvmWrite => 0x20,
+            Opcode::NvmAuthenticateWrite => 0x21,
+            Opcode::NvmRead => 0x22,
+            Opcode::NvmSetOffset => 0x23,
+            Opcode::DromRead => 0x24,
+            Opcode::GetNvmSectorSize => 0x25,
+            Opcode::GetPcieDownstreamMapping => 0x30,
+            Opcode::GetCapabilities => 0x31,
+            Opcode::SetCapabilities => 0x32,
+            Opcode::BufferAllocationRequest => 0x33,
+            Opcode::BlockSidebandPortOperations => 0x34,
+            Opcode::UnblockSidebandPortOperations => 0x35,
+            Opcode::GetContainerId => 0x36,
+            Opcode::GetConnectorsInformation => 0x37,
+            Opcode::Other(opcode) => opcode,
+        }
+    }
+}
+
+impl From<u16> for Opcode {
+    fn from(opcode: u16) -> Self {
+        match opcode {
+            0x10 => Self::QueryDpResource,
+            0x11 => Self::AllocateDpResource,
+            0x12 => Self::DeallocateDpResource,
+            0x13 => Self::ConnectDpOut,
+            0x20 => Self::NvmWrite,
+            0x21 => Self::NvmAuthenticateWrite,
+            0x22 => Self::NvmRead,
+            0x23 => Self::NvmSetOffset,
+            0x24 => Self::DromRead,
+            0x25 => Self::GetNvmSectorSize,
+            0x30 => Self::GetPcieDownstreamMapping,
+            0x31 => Self::GetCapabilities,
+            0x32 => Self::SetCapabilities,
+            0x33 => Self::BufferAllocationRequest,
+            0x34 => Self::BlockSidebandPortOperations,
+            0x35 => Self::UnblockSidebandPortOperations,
+            0x36 => Self::GetContainerId,
+            0x37 => Self::GetConnectorsInformation,
+            _ => Self::Other(opcode),
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::QueryDpResource => "Query DP Resource Availability",
+            Self::AllocateDpResource => "Allocate DP Resource",
+            Self::DeallocateDpResource => "De-allocate DP Resource",
+            Self::ConnectDpOut => "Connect DP OUT Adapter",
+            Self::NvmWrite => "NVM Write",
+            Self::NvmAuthenticateWrite => "NVM Authenticate Write",
+            Self::NvmRead => "NVM Read",
+            Self::NvmSetOffset => "NVM Set Offset",
+            Self::DromRead => "DROM Read",
+            Self::GetNvmSectorSize => "Get NVM Sector Size",
+            Self::GetPcieDownstreamMapping => "Get PCIe Downstream Entry Mapping",
+            Self::GetCapabilities => "Get Capabilities",
+            Self::SetCapabilities => "Set Capabilities",
+            Self::BufferAllocationRequest => "Buffer Allocation Request",
+            Self::BlockSidebandPortOperations => "Block Sideband Port Operations",
+            Self::UnblockSidebandPortOperations => "Unblock Sideband Port Operations",
+            Self::GetContainerId => "Get Container-ID",
+            Self::GetConnectorsInformation => "Get Connectors Information",
+            Self::Other(opcode) => return write!(f, "Opcode {opcode:#x}"),
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Results of a completed router operation.
+#[derive(Clone, Debug)]
+pub struct Response {
+    status: u8,
+    metadata: u32,
+    data: Vec<u32>,
+}
+
+impl Response {
+    /// Returns the operation status. Zero means success and the meaning of other values depends
+    /// on the operation.
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    /// Returns output metadata (`ROUTER_CS_25`).
+    pub fn metadata(&self) -> u32 {
+        self.metadata
+    }
+
+    /// Returns output data (`ROUTER_CS_9` - `ROUTER_CS_24`).
+    pub fn data(&self) -> &[u32] {
+        &self.data
+    }
+
+    fn check(self, opcode: Opcode) -> Result<Self> {
+        if self.status != 0 {
+            return Err(Error::other(format!(
+                "{opcode} failed with status {:#x}",
+                self.status
+            )));
+        }
+        Ok(self)
+    }
+}
+
+/// Single entry returned by Get PCIe Downstream Entry Mapping operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcieDownstreamEntry {
+    index: u8,
+    adapter: Option<u8>,
+    fpb_rid: u16,
+    non_fpb_rid: u16,
+}
+
+impl PcieDownstreamEntry {
+    /// Index of the entry.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Returns `true` if this is a native PCIe downstream port (not tunneled).
+    pub fn is_native(&self) -> bool {
+        self.adapter.is_none()
+    }
+
+    /// PCIe downstream adapter the port is tunneled through or `None` for native ports.
+    pub fn adapter(&self) -> Option<u8> {
+        self.adapter
+    }
+
+    /// PCI requester ID (bus, device and function) of the port when FPB is enabled.
+    pub fn fpb_rid(&self) -> u16 {
+        self.fpb_rid
+    }
+
+    /// PCI requester ID (bus, device and function) of the port when FPB is not enabled.
+    pub fn non_fpb_rid(&self) -> u16 {
+        self.non_fpb_rid
+    }
+}
+
+fn register_by_offset(regs: &[Register], offset: usize) -> Option<&Register> {
+    regs.iter().find(|r| usize::from(r.offset()) == offset)
+}
+
+/// Runs router operations on a single router.
+pub struct RouterOperation {
+    backend: Arc<dyn Backend>,
+    router: String,
+    timeout: Duration,
+}
+
+impl RouterOperation {
+    /// Creates a new instance for running operations on `device`.
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if `device` is not a router.
+    pub fn new(device: &Device) -> Result<Self> {
+        if !device.is_router() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a router", device.kernel_name()),
+            ));
+        }
+
+        Ok(Self::with_backend(backend::get(), &device.kernel_name()))
+    }
+
+    fn with_backend(backend: Arc<dyn Backend>, router: &str) -> Self {
+        Self {
+            backend,
+            router: router.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets time to wait for an operation to complete. Default is [`DEFAULT_TIMEOUT`].
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    fn read_opcode_regs(&self) -> Result<Vec<Register>> {
+        let regs = self.backend.read_registers(&self.router, None)?;
+
+        if register_by_offset(&regs, usb4::ROUTER_CS_26).is_none() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} does not have router operation registers", self.router),
+            ));
+        }
+
+        Ok(regs)
+    }
+
+    /// Returns value of register at `offset` or [`ErrorKind::InvalidData`] if the backend did not
+    /// return it.
+    fn register_value(&self, regs: &[Register], offset: usize) -> Result<u32> {
+        register_by_offset(regs, offset)
+            .map(Register::value)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} register {offset:#x} missing", self.router),
+                )
+            })
+    }
+
+    /// Runs operation `opcode` with optional input `metadata` and `data`.
+    ///
+    /// Waits until the operation completes. Returns [`ErrorKind::Unsupported`] if the router does
+    /// not support the operation, [`ErrorKind::ResourceBusy`] if there is already an operation
+    /// running and [`ErrorKind::TimedOut`] if the operation does not complete in time. Non-zero
+    /// status is not treated as error here.
+    pub fn run(&self, opcode: Opcode, metadata: Option<u32>, data: &[u32]) -> Result<Response> {
+        if data.len() > DATA_DWORDS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("at most {DATA_DWORDS} double words of data supported"),
+            ));
+        }
+
+        let regs = self.read_opcode_regs()?;
+        if self.register_value(&regs, usb4::ROUTER_CS_26)? & usb4::ROUTER_CS_26_OV != 0 {
+            return Err(Error::new(
+                ErrorKind::ResourceBusy,
+                "another router operation is in progress",
+            ));
+        }
+
+        // Opcode must be written last as it starts the operation.
+        let mut writes: Vec<_> = data
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let offset = (usb4::ROUTER_CS_9 + i) as u16;
+                Register::new(offset, offset, 0, 0, *value)
+            })
+            .collect();
+        if let Some(metadata) = metadata {
+            let offset = usb4::ROUTER_CS_25 as u16;
+            writes.push(Register::new(offset, offset, 0, 0, metadata));
+        }
+        let offset = usb4::ROUTER_CS_26 as u16;
+        let value =
+            (u16::from(opcode) as u32 & usb4::ROUTER_CS_26_OPCODE_MASK) | usb4::ROUTER_CS_26_OV;
+        writes.push(Register::new(offset, offset, 0, 0, value));
+
+        self.backend
+            .write_registers(&self.router, None, ConfigSpace::Router, &writes)?;
+
+        let start = Instant::now();
+
+        let regs = loop {
+            let regs = self.read_opcode_regs()?;
+            if self.register_value(&regs, usb4::ROUTER_CS_26)? & usb4::ROUTER_CS_26_OV == 0 {
+                break regs;
+            }
+            if start.elapsed() > self.timeout {
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!("{opcode} did not complete in time"),
+                ));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        let value = self.register_value(&regs, usb4::ROUTER_CS_26)?;
+        if value & usb4::ROUTER_CS_26_ONS != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{opcode} not supported by the router"),
+            ));
+        }
+
+        Ok(Response {
+            status: ((value & usb4::ROUTER_CS_26_STATUS_MASK) >> usb4::ROUTER_CS_26_STATUS_SHIFT)
+                as u8,
+            metadata: self.register_value(&regs, usb4::ROUTER_CS_25)?,
+            data: (usb4::ROUTER_CS_9..usb4::ROUTER_CS_25)
+                .map(|offset| self.register_value(&regs, offset))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Reads `len` bytes starting from byte `address` using an operation that returns at most
+    /// [`DATA_DWORDS`] at the time. Metadata is built by `metadata(dword address, dwords)`.
+    fn read_data(
+        &self,
+        opcode: Opcode,
+        address: u32,
+        len: usize,
+        metadata: impl Fn(u32, u32) -> u32,
+    ) -> Result<Vec<u8>> {
+        let start = address & !3;
+        let end = address as usize + len;
+        let mut bytes = Vec::new();
+        let mut offset = start as usize;
+
+        while offset < end {
+            let dwords = (end - offset).div_ceil(4).min(DATA_DWORDS);
+            let response = self
+                .run(
+                    opcode,
+                    Some(metadata(offset as u32 / 4, dwords as u32)),
+                    &[],
+                )?
+                .check(opcode)?;
+
+            for dword in &response.data[..dwords] {
+                bytes.extend_from_slice(&dword.to_le_bytes());
+            }
+            offset += dwords * 4;
+        }
+
+        let skip = (address - start) as usize;
+        Ok(bytes[skip..skip + len].to_vec())
+    }
+
+    /// Reads `len` bytes of router NVM starting from byte `address`.
+    pub fn nvm_read(&self, address: u32, len: usize) -> Result<Vec<u8>> {
+        self.read_data(Opcode::NvmRead, address, len, |dwaddress, dwords| {
+            ((dwaddress << 2) & NVM_READ_OFFSET_MASK)
+                | ((dwords << NVM_READ_LENGTH_SHIFT) & NVM_READ_LENGTH_MASK)
+        })
+    }
+
+    /// Reads first `len` bytes of the active router NVM.
+    ///
+    /// Reads [`NVM_READ_CHUNK`] bytes at the time and retries each chunk up to
+    /// [`NVM_READ_RETRIES`] times as the kernel connection manager may be running operations at
+    /// the same time. Calls `progress` with the number of bytes read so far after each chunk.
+    ///
+    /// For Intel routers the active NVM starts from the digital section so this matches the image
+    /// file from [`Image::digital_offset()`](crate::nvm::Image::digital_offset()) onwards.
+    pub fn nvm_read_all(&self, len: usize, mut progress: impl FnMut(usize)) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(len);
+
+        while bytes.len() < len {
+            let address = bytes.len();
+            let chunk = (len - address).min(NVM_READ_CHUNK);
+            let mut retries = 0;
+
+            let data = loop {
+                match self.nvm_read(address as u32, chunk) {
+                    Ok(data) => break data,
+                    Err(err)
+                        if err.kind() != ErrorKind::Unsupported && retries < NVM_READ_RETRIES =>
+                    {
+                        retries += 1;
+                        thread::sleep(NVM_READ_RETRY_DELAY);
+                    }
+                    Err(err) => {
+                        return Err(Error::new(
+                            err.kind(),
+                            format!("reading NVM at {address:#x} failed: {err}"),
+                        ));
+                    }
+                }
+            };
+
+            bytes.extend_from_slice(&data);
+            progress(bytes.len());
+        }
+
+        Ok(bytes)
+    }
+
+    /// Reads `len` bytes of router DROM starting from byte `address`.
+    pub fn drom_read(&self, address: u32, len: usize) -> Result<Vec<u8>> {
+        self.read_data(Opcode::DromRead, address, len, |dwaddress, dwords| {
+            ((dwaddress << 2) & DROM_ADDRESS_MASK) | ((dwords << DROM_SIZE_SHIFT) & DROM_SIZE_MASK)
+        })
+    }
+
+    /// Returns router NVM sector size in bytes.
+    pub fn nvm_sector_size(&self) -> Result<u32> {
+        let response = self
+            .run(Opcode::GetNvmSectorSize, None, &[])?
+            .check(Opcode::GetNvmSectorSize)?;
+        Ok(response.metadata & NVM_SECTOR_SIZE_MASK)
+    }
+
+    /// Returns `true` if DisplayPort resource of DP IN `adapter` is available.
+    pub fn query_dp_resource(&self, adapter: u8) -> Result<bool> {
+        let metadata = adapter as u32 & DP_RESOURCE_ADAPTER_MASK;
+        let response = self.run(Opcode::QueryDpResource, Some(metadata), &[])?;
+        Ok(response.status == 0)
+    }
+
+    /// Allocates DisplayPort resource for DP IN `adapter`.
+    ///
+    /// Returns [`ErrorKind::ResourceBusy`] if the resource is already allocated.
+    pub fn allocate_dp_resource(&self, adapter: u8) -> Result<()> {
+        let metadata = adapter as u32 & DP_RESOURCE_ADAPTER_MASK;
+        let response = self.run(Opcode::AllocateDpResource, Some(metadata), &[])?;
+        if response.status != 0 {
+            return Err(Error::new(
+                ErrorKind::ResourceBusy,
+                format!("DP resource of adapter {adapter} not available"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Releases DisplayPort resource of DP IN `adapter`.
+    pub fn deallocate_dp_resource(&self, adapter: u8) -> Result<()> {
+        let metadata = adapter as u32 & DP_RESOURCE_ADAPTER_MASK;
+        self.run(Opcode::DeallocateDpResource, Some(metadata), &[])?
+            .check(Opcode::DeallocateDpResource)?;
+        Ok(())
+    }
+
+    /// Returns all PCIe downstream port mapping entries of the router.
+    pub fn pcie_downstream_mapping(&self) -> Result<Vec<PcieDownstreamEntry>> {
+        let opcode = Opcode::GetPcieDownstreamMapping;
+        let mut entries = Vec::new();
+
+        // The router returns the next entry each time the operation is run.
+        loop {
+            let response = self.run(opcode, None, &[])?.check(opcode)?;
+
+            let total = (response.metadata & PCIE_MAPPING_ENTRIES_MASK) as usize;
+            let index =
+                ((response.metadata & PCIE_MAPPING_INDEX_MASK) >> PCIE_MAPPING_INDEX_SHIFT) as u8;
+
+            if total == 0 {
+                break;
+            }
+
+            let adapter = if response.data[0] & PCIE_MAPPING_NATIVE != 0 {
+                None
+            } else {
+                Some(
+                    ((response.data[0] & PCIE_MAPPING_ADAPTER_MASK) >> PCIE_MAPPING_ADAPTER_SHIFT)
+                        as u8,
+                )
+            };
+
+            entries.push(PcieDownstreamEntry {
+                index,
+                adapter,
+                fpb_rid: (response.data[1] >> 16) as u16,
+                non_fpb_rid: response.data[1] as u16,
+            });
+
+            if index as usize + 1 >= total || entries.len() >= total {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Runs Get Capabilities operation and returns the raw response.
+    pub fn capabilities(&self) -> Result<Response> {
+        self.run(Opcode::GetCapabilities, None, &[])?
+            .check(Opcode::GetCapabilities)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Router that runs operations using `handler` as soon as the opcode is written.
+    struct MockRouter {
+        regs: Mutex<Vec<u32>>,
+        handler: fn(&mut [u32]),
+        // Register left out when reading, like a gap in debugfs output.
+        missing: Option<usize>,
+    }
+
+    impl MockRouter {
+        fn new(handler: fn(&mut [u32])) -> Arc<Self> {
+            Self::with_missing(handler, None)
+        }
+
+        fn with_missing(handler: fn(&mut [u32]), missing: Option<usize>) -> Arc<Self> {
+            Arc::new(Self {
+                regs: Mutex::new(vec![0; usb4::ROUTER_CS_26 + 1]),
+                handler,
+                missing,
+            })
+        }
+    }
+
+    impl Backend for MockRouter {
+        fn read_registers(&self, _: &str, _: Option<u8>) -> Result<Vec<Register>> {
+            Ok(self
+                .regs
+                .lock()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != self.missing)
+                .map(|(i, value)| Register::new(i as u16, i as u16, 0, 0, *value))
+                .collect())
+        }
+
+        fn read_paths(&self, _: &str, _: u8) -> Result<Vec<Register>> {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+
+        fn read_counters(&self, _: &str, _: u8) -> Result<Vec<Register>> {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+
+        fn write_registers(
+            &self,
+            _: &str,
+            _: Option<u8>,
+            _: ConfigSpace,
+            regs: &[Register],
+        ) -> Result<()> {
+            let mut values = self.regs.lock().unwrap();
+            for reg in regs {
+                values[reg.offset() as usize] = reg.value();
+                if reg.offset() as usize == usb4::ROUTER_CS_26 {
+                    (self.handler)(&mut values);
+                }
+            }
+            Ok(())
+        }
+
+        fn read_drom(&self, _: &str) -> Result<Vec<u8>> {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+    }
+
+    fn nvm_read(regs: &mut [u32]) {
+        let dwaddress = (regs[usb4::ROUTER_CS_25] & NVM_READ_OFFSET_MASK) >> 2;
+        let dwords = match (regs[usb4::ROUTER_CS_25] & NVM_READ_LENGTH_MASK) >> 24 {
+            0 => 16,
+            n => n as usize,
+        };
+        for i in 0..dwords {
+            // Each byte holds its own address.
+            let address = (dwaddress + i as u32) * 4;
+            regs[usb4::ROUTER_CS_9 + i] =
+                u32::from_le_bytes([0, 1, 2, 3].map(|b| (address + b) as u8));
+        }
+        regs[usb4::ROUTER_CS_26] &= !usb4::ROUTER_CS_26_OV;
+    }
+
+    #[test]
+    fn read_nvm() {
+        let op = RouterOperation::with_backend(MockRouter::new(nvm_read), "0-0");
+
+        let bytes = op.nvm_read(6, 100).unwrap();
+        assert_eq!(bytes.len(), 100);
+        assert!(bytes.iter().enumerate().all(|(i, b)| *b == (i + 6) as u8));
+    }
+
+    #[test]
+    fn read_nvm_all() {
+        static READS: AtomicUsize = AtomicUsize::new(0);
+
+        let op = RouterOperation::with_backend(
+            MockRouter::new(|regs| {
+                // Fail every third read so that the chunk gets retried.
+                if READS.fetch_add(1, Ordering::Relaxed) % 3 == 2 {
+                    regs[usb4::ROUTER_CS_26] = 1 << usb4::ROUTER_CS_26_STATUS_SHIFT;
+                } else {
+                    nvm_read(regs);
+                }
+            }),
+            "0-0",
+        );
+
+        let mut progress = Vec::new();
+        let bytes = op.nvm_read_all(200, |n| progress.push(n)).unwrap();
+        assert_eq!(bytes.len(), 200);
+        assert!(bytes.iter().enumerate().all(|(i, b)| *b == i as u8));
+        assert_eq!(progress, [64, 128, 192, 200]);
+
+        let op = RouterOperation::with_backend(
+            MockRouter::new(|regs| regs[usb4::ROUTER_CS_26] = 1 << usb4::ROUTER_CS_26_STATUS_SHIFT),
+            "0-0",
+        );
+        let err = op.nvm_read_all(200, |_| {}).unwrap_err();
+        assert!(err.to_string().starts_with("reading NVM at 0x0 failed"));
+    }
+
+    #[test]
+    fn errors() {
+        let op = RouterOperation::with_backend(
+            MockRouter::new(|regs| regs[usb4::ROUTER_CS_26] = usb4::ROUTER_CS_26_ONS),
+            "0-0",
+        );
+        let err = op.run(Opcode::GetCapabilities, None, &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+        let mut op = RouterOperation::with_backend(MockRouter::new(|_| {}), "0-0");
+        op.set_timeout(Duration::from_millis(20));
+        let err = op.run(Opcode::GetCapabilities, None, &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        // Previous operation is still running.
+        let err = op.run(Opcode::GetCapabilities, None, &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResourceBusy);
+
+        let op = RouterOperation::with_backend(
+            MockRouter::new(|regs| regs[usb4::ROUTER_CS_26] = 1 << usb4::ROUTER_CS_26_STATUS_SHIFT),
+            "0-0",
+        );
+        assert!(!op.query_dp_resource(5).unwrap());
+        assert!(op.nvm_sector_size().is_err());
+
+        let op = RouterOperation::with_backend(
+            MockRouter::with_missing(nvm_read, Some(usb4::ROUTER_CS_9 + 3)),
+            "0-0",
+        );
+        let err = op.nvm_read(0, 64).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let op = RouterOperation::with_backend(MockRouter::with_missing(nvm_read, Some(1)), "0-0");
+        assert_eq!(op.nvm_read(4, 4).unwrap(), [4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn pcie_mapping() {
+        let op = RouterOperation::with_backend(
+            MockRouter::new(|regs| {
+                // Two entries, first native and second tunneled through adapter 9.
+                let index = (regs[usb4::ROUTER_CS_25] >> 8) & 0xff;
+                let index = if regs[usb4::ROUTER_CS_25] & 0xff == 0 {
+                    0
+                } else {
+                    index + 1
+                };
+                regs[usb4::ROUTER_CS_25] = (index << 8) | 2;
+                regs[usb4::ROUTER_CS_9] = if index == 0 { 1 } else { 9 << 1 };
+                regs[usb4::ROUTER_CS_9 + 1] = 0x0008_0010 + index;
+                regs[usb4::ROUTER_CS_26] = 0;
+            }),
+            "0-0",
+        );
+
+        let entries = op.pcie_downstream_mapping().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_native());
+        assert_eq!(entries[0].fpb_rid(), 0x0008);
+        assert_eq!(entries[0].non_fpb_rid(), 0x0010);
+        assert_eq!(entries[1].index(), 1);
+        assert_eq!(entries[1].adapter(), Some(9));
+        assert_eq!(entries[1].non_fpb_rid(), 0x0011);
+    }
+}
@@ -0,0 +1,404 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! [`Serialize`] implementations of the library types.
+//!
+//! The simple enums and plain data types derive the implementation in place. The rest are
+//! implemented here on top of the public accessors so that the schema stays the same even if the
+//! internal representation changes. The schema is documented in the crate level documentation.
+
+use serde::{
+    Serialize, Serializer,
+    ser::{SerializeSeq, SerializeStruct},
+};
+
+use crate::{
+    Device,
+    debugfs::{Adapter, BitFields, Name, Register},
+    drom::Drom,
+    margining::{Lanes, Margin, Mode, ResultValue, Results, Test},
+    monitor::{Event, TunnelEvent},
+    tunnel::{Hop, Path, Tunnel},
+};
+
+#[derive(Serialize)]
+struct Field<'a> {
+    name: &'a str,
+    short_name: Option<&'a str>,
+    start_bit: u8,
+    end_bit: u8,
+    value: u32,
+    value_name: Option<&'a str>,
+}
+
+struct Fields<'a>(&'a Register);
+
+impl Serialize for Fields<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let fields = self.0.fields().map_or(&[][..], |f| f.as_slice());
+        let mut seq = serializer.serialize_seq(Some(fields.len()))?;
+
+        for field in fields {
+            let value = self.0.field_value(field);
+            seq.serialize_element(&Field {
+                name: field.name(),
+                short_name: field.short_name(),
+                start_bit: *field.range().start(),
+                end_bit: *field.range().end(),
+                value,
+                value_name: field.value_name(value),
+            })?;
+        }
+
+        seq.end()
+    }
+}
+
+impl Serialize for Register {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Register", 8)?;
+        s.serialize_field("offset", &self.offset())?;
+        s.serialize_field("relative_offset", &self.relative_offset())?;
+        s.serialize_field("cap_id", &self.cap_id())?;
+        s.serialize_field("vs_cap_id", &self.vs_cap_id())?;
+        s.serialize_field("name", &self.name())?;
+        s.serialize_field("value", &self.value())?;
+        s.serialize_field("data", &self.data())?;
+        s.serialize_field("fields", &Fields(self))?;
+        s.end()
+    }
+}
+
+impl Serialize for Adapter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Adapter", 9)?;
+        s.serialize_field("adapter", &self.adapter())?;
+        s.serialize_field("kind", &self.kind())?;
+        s.serialize_field("state", &self.state())?;
+        s.serialize_field("upstream", &self.is_upstream())?;
+        s.serialize_field("registers", &self.registers())?;
+        s.serialize_field("path_registers", &self.path_registers())?;
+        s.serialize_field("paths", &self.paths())?;
+        s.serialize_field("counter_registers", &self.counter_registers())?;
+        s.serialize_field("sideband_registers", &self.sideband_registers())?;
+        s.end()
+    }
+}
+
+struct Entries<'a>(&'a Drom);
+
+impl Serialize for Entries<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.entries())
+    }
+}
+
+impl Serialize for Drom {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Drom", 8)?;
+        s.serialize_field("version", &self.version())?;
+        s.serialize_field("length", &self.length())?;
+        s.serialize_field("crc8", &self.crc8())?;
+        s.serialize_field("crc8_valid", &self.is_crc8_valid())?;
+        s.serialize_field("crc32", &self.crc32())?;
+        s.serialize_field("crc32_valid", &self.is_crc32_valid())?;
+        s.serialize_field("uuid", &self.uuid())?;
+        s.serialize_field("entries", &Entries(self))?;
+        s.end()
+    }
+}
+
+impl Serialize for Device {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Device", 26)?;
+        s.serialize_field("kernel_name", &self.kernel_name())?;
+        s.serialize_field("kind", &self.kind())?;
+        s.serialize_field("domain", &self.domain_index())?;
+        s.serialize_field("route", &self.route())?;
+        s.serialize_field("adapter", &self.adapter_num())?;
+        s.serialize_field("index", &self.index())?;
+        s.serialize_field("depth", &self.depth())?;
+        s.serialize_field("vendor", &self.vendor())?;
+        s.serialize_field("device", &self.device())?;
+        s.serialize_field("vendor_name", &self.vendor_name())?;
+        s.serialize_field("device_name", &self.device_name())?;
+        s.serialize_field("authorized", &self.authorized())?;
+        s.serialize_field("unique_id", &self.unique_id())?;
+        s.serialize_field("generation", &self.generation())?;
+        s.serialize_field("security_level", &self.security_level())?;
+        s.serialize_field("usb4_version", &self.usb4_version())?;
+        s.serialize_field("nvm_version", &self.nvm_version())?;
+        s.serialize_field("rx_speed", &self.rx_speed())?;
+        s.serialize_field("rx_lanes", &self.rx_lanes())?;
+        s.serialize_field("tx_speed", &self.tx_speed())?;
+        s.serialize_field("tx_lanes", &self.tx_lanes())?;
+        s.serialize_field("registers", &self.registers())?;
+        s.serialize_field("sideband_registers", &self.sideband_registers())?;
+        s.serialize_field("adapters", &self.adapters())?;
+        s.serialize_field("drom", &self.drom())?;
+        s.serialize_field("boot_acl", &self.boot_acl())?;
+        s.end()
+    }
+}
+
+impl Serialize for Hop<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let entry = self.entry();
+        let mut s = serializer.serialize_struct("Hop", 6)?;
+        s.serialize_field("domain", &self.device().domain_index())?;
+        s.serialize_field("route", &self.device().route())?;
+        s.serialize_field("in_adapter", &entry.in_adapter())?;
+        s.serialize_field("in_hop", &entry.in_hop())?;
+        s.serialize_field("out_adapter", &entry.out_adapter())?;
+        s.serialize_field("out_hop", &entry.out_hop())?;
+        s.end()
+    }
+}
+
+impl Serialize for Path<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Path", 2)?;
+        s.serialize_field("name", self.name())?;
+        s.serialize_field("hops", self.hops())?;
+        s.end()
+    }
+}
+
+#[derive(Serialize)]
+struct Endpoint {
+    domain: u32,
+    route: u64,
+    adapter: u8,
+}
+
+impl Serialize for Tunnel<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Tunnel", 6)?;
+        s.serialize_field("kind", &self.kind())?;
+        s.serialize_field(
+            "src",
+            &Endpoint {
+                domain: self.src_device().domain_index(),
+                route: self.src_device().route(),
+                adapter: self.src_adapter().adapter(),
+            },
+        )?;
+        s.serialize_field(
+            "dst",
+            &Endpoint {
+                domain: self.dst_device().domain_index(),
+                route: self.dst_device().route(),
+                adapter: self.dst_adapter().adapter(),
+            },
+        )?;
+        s.serialize_field("direction", &self.direction())?;
+        s.serialize_field("complete", &self.is_complete())?;
+        s.serialize_field("paths", self.paths())?;
+        s.end()
+    }
+}
+
+// These serialize the same way they are displayed.
+macro_rules! serialize_display {
+    ($($ty:ty),+) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: Serializer,
+                {
+                    serializer.collect_str(self)
+                }
+            }
+        )+
+    };
+}
+
+serialize_display!(Lanes, Margin, Mode, Test, TunnelEvent);
+
+impl Serialize for ResultValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (value, exceeds) = match *self {
+            Self::Ok(value) => (value, false),
+            Self::Exceeds(value) => (value, true),
+        };
+        let mut s = serializer.serialize_struct("ResultValue", 2)?;
+        s.serialize_field("value", &value)?;
+        s.serialize_field("exceeds", &exceeds)?;
+        s.end()
+    }
+}
+
+impl Serialize for Results {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let unit = match self.test() {
+            Test::Voltage => "mV",
+            Test::Time => "UI",
+        };
+        let mut s = serializer.serialize_struct("Results", 5)?;
+        s.serialize_field("test", &self.test())?;
+        s.serialize_field("lanes", &self.lanes())?;
+        s.serialize_field("unit", unit)?;
+        s.serialize_field("margins", &self.margins())?;
+        s.serialize_field("error_counter", &self.error_counter(Lanes::All))?;
+        s.end()
+    }
+}
+
+impl Serialize for Event {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (device, change) = match self {
+            Self::Add(device) | Self::Remove(device) => (device, None),
+            Self::Change(device, change) => (device, change.as_ref()),
+        };
+        let mut s = serializer.serialize_struct("Event", 3)?;
+        s.serialize_field("event", &self.to_string())?;
+        s.serialize_field("device", device)?;
+        s.serialize_field("change", &change)?;
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::{
+        Kind, SecurityLevel, Version,
+        debugfs::{self, PathEntry},
+        drom::DromEntry,
+        margining::{LaneResult, LaneVoltageResult},
+        monitor::ChangeEvent,
+        test_util::router,
+        tunnel,
+    };
+
+    use super::*;
+
+    #[test]
+    fn enums() {
+        assert_eq!(
+            serde_json::to_value(Kind::Xdomain).unwrap(),
+            json!("xdomain")
+        );
+        assert_eq!(
+            serde_json::to_value(SecurityLevel::DpOnly).unwrap(),
+            json!("dponly")
+        );
+        assert_eq!(
+            serde_json::to_value(debugfs::Type::Usb3GenTDown).unwrap(),
+            json!("usb3_gen_t_down")
+        );
+        assert_eq!(
+            serde_json::to_value(tunnel::Type::DisplayPort).unwrap(),
+            json!("display_port")
+        );
+        assert_eq!(
+            serde_json::from_value::<Version>(json!({ "major": 1, "minor": 2 })).unwrap(),
+            Version { major: 1, minor: 2 }
+        );
+    }
+
+    #[test]
+    fn values() {
+        let entry: PathEntry = serde_json::from_value(json!({
+            "in_adapter": 1,
+            "in_hop": 8,
+            "out_adapter": 3,
+            "out_hop": 9,
+        }))
+        .unwrap();
+        assert_eq!(entry.out_adapter(), 3);
+        assert_eq!(serde_json::to_value(entry).unwrap()["out_hop"], json!(9));
+
+        assert_eq!(
+            serde_json::to_value(DromEntry::LaneAdapter {
+                adapter_num: 1,
+                lane_1_adapter: false,
+                dual_lane_adapter: true,
+                dual_lane_adapter_num: 2,
+            })
+            .unwrap(),
+            json!({
+                "lane_adapter": {
+                    "adapter_num": 1,
+                    "lane_1_adapter": false,
+                    "dual_lane_adapter": true,
+                    "dual_lane_adapter_num": 2,
+                }
+            })
+        );
+
+        assert_eq!(
+            serde_json::to_value(LaneResult::Voltage(LaneVoltageResult::Both {
+                low: ResultValue::Ok(0.25),
+                high: ResultValue::Exceeds(1.5),
+            }))
+            .unwrap(),
+            json!({
+                "voltage": {
+                    "both": {
+                        "low": { "value": 0.25, "exceeds": false },
+                        "high": { "value": 1.5, "exceeds": true },
+                    }
+                }
+            })
+        );
+
+        assert_eq!(
+            serde_json::to_value(ChangeEvent::Tunnel {
+                event: TunnelEvent::LowBandwidth,
+                details: None,
+            })
+            .unwrap(),
+            json!({ "type": "tunnel", "event": "low bandwidth", "details": null })
+        );
+    }
+
+    #[test]
+    fn device() {
+        let device = serde_json::to_value(router(1, Vec::new())).unwrap();
+        assert_eq!(device["kernel_name"], json!("0-1"));
+        assert_eq!(device["kind"], json!("router"));
+        assert_eq!(device["route"], json!(1));
+        assert_eq!(device["boot_acl"], json!(null));
+        assert_eq!(device.as_object().unwrap().len(), 26);
+    }
+}
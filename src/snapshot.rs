@@ -0,0 +1,492 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Capture and replay of the Thunderbolt/USB4 bus state.
+//!
+//! A [`Snapshot`] holds the `sysfs` attributes of every device on the bus along with the `debugfs`
+//! files (registers, paths, counters and DROM) of each of them. It can be saved into a single file
+//! and later loaded on another machine. Once [`replay()`] is called all the library functions read
+//! from the snapshot instead of the live system and any attempt to write returns an error.
+//!
+//! # Examples
+//! Take a snapshot of the current system.
+//! ```no_run
+//! # use std::{io, path::Path};
+//! use tbtools::snapshot::Snapshot;
+//!
+//! # fn main() -> io::Result<()> {
+//! let snapshot = Snapshot::capture()?;
+//! snapshot.save(Path::new("snapshot.json"))?;
+//! # Ok(())
+//! # }
+//! ```
+//! Replay it later on.
+//! ```no_run
+//! # use std::{io, path::Path};
+//! use tbtools::snapshot::{self, Snapshot};
+//!
+//! # fn main() -> io::Result<()> {
+//! snapshot::replay(Snapshot::load(Path::new("snapshot.json"))?);
+//!
+//! for device in tbtools::find_devices(None)? {
+//!     println!("{}", device.kernel_name());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Error, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    Address, ConfigSpace, Device, Kind,
+    backend::{self, Backend},
+    debugfs::{
+        self, DEBUGFS_COUNTERS, DEBUGFS_DROM, DEBUGFS_PATH, DEBUGFS_REGS, DEBUGFS_SB_REGS, Register,
+    },
+    device::Attributes,
+};
+
+/// Version of the snapshot file format.
+const VERSION: u32 = 1;
+
+/// `sysfs` attributes that are captured for each device.
+///
+/// `key` is only captured for services. For routers it holds the secret key used with the secure
+/// security level, which must not end up in a file that is shared for debugging.
+const ATTRIBUTES: [&str; 21] = [
+    "authorized",
+    "boot_acl",
+    "deauthorization",
+    "device",
+    "device_name",
+    "generation",
+    "iommu_dma_protection",
+    "key",
+    "nvm_version",
+    "prtcid",
+    "prtcrevs",
+    "prtcstns",
+    "prtcvers",
+    "rx_lanes",
+    "rx_speed",
+    "security",
+    "tx_lanes",
+    "tx_speed",
+    "unique_id",
+    "vendor",
+    "vendor_name",
+];
+
+/// uevent properties that are captured for each device.
+const PROPERTIES: [&str; 2] = ["DEVTYPE", "USB4_VERSION"];
+
+/// `debugfs` files that are captured for routers, retimers and adapters.
+const DEBUGFS_FILES: [&str; 4] = [
+    DEBUGFS_REGS,
+    DEBUGFS_PATH,
+    DEBUGFS_COUNTERS,
+    DEBUGFS_SB_REGS,
+];
+
+static ACTIVE: RwLock<Option<Arc<Snapshot>>> = RwLock::new(None);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct DeviceSnapshot {
+    kernel_name: String,
+    syspath: PathBuf,
+    attributes: BTreeMap<String, String>,
+    properties: BTreeMap<String, String>,
+    /// Contents of the `debugfs` files relative to the device directory (for example `regs` or
+    /// `port1/path`).
+    debugfs: BTreeMap<String, String>,
+    drom: Option<Vec<u8>>,
+}
+
+impl DeviceSnapshot {
+    fn capture(device: &Device) -> Result<Self> {
+        let attrs = device.attributes()?;
+        let kernel_name = device.kernel_name();
+
+        let attributes = ATTRIBUTES
+            .iter()
+            .filter(|name| **name != "key" || device.kind() == Kind::Service)
+            .filter_map(|name| Some((name.to_string(), attrs.attribute(name)?)))
+            .collect();
+        let properties = PROPERTIES
+            .iter()
+            .filter_map(|name| Some((name.to_string(), attrs.property(name)?)))
+            .collect();
+
+        let mut debugfs = BTreeMap::new();
+        let mut drom = None;
+
+        let path_buf = debugfs::path_buf()?.join(&kernel_name);
+
+        // Only routers and retimers have debugfs directory and not all the files are readable
+        // (for example disabled adapters) so errors are ignored here.
+        if let Ok(entries) = fs::read_dir(&path_buf) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = entry.path();
+
+                if path.is_dir() && name.starts_with("port") {
+                    for file in DEBUGFS_FILES {
+                        if let Ok(contents) = fs::read_to_string(path.join(file)) {
+                            debugfs.insert(format!("{name}/{file}"), contents);
+                        }
+                    }
+                } else if DEBUGFS_FILES.contains(&name.as_str()) {
+                    if let Ok(contents) = fs::read_to_string(&path) {
+                        debugfs.insert(name, contents);
+                    }
+                } else if name == DEBUGFS_DROM {
+                    drom = fs::read(&path).ok();
+                }
+            }
+        }
+
+        Ok(Self {
+            kernel_name,
+            syspath: device.sysfs_path(),
+            attributes,
+            properties,
+            debugfs,
+            drom,
+        })
+    }
+
+    fn parse(&self) -> Option<Device> {
+        Device::parse_attributes(self)
+    }
+}
+
+impl Attributes for DeviceSnapshot {
+    fn devtype(&self) -> Option<String> {
+        self.property("DEVTYPE")
+    }
+
+    fn sysname(&self) -> Option<String> {
+        Some(self.kernel_name.clone())
+    }
+
+    fn syspath(&self) -> PathBuf {
+        self.syspath.clone()
+    }
+
+    fn attribute(&self, name: &str) -> Option<String> {
+        self.attributes.get(name).cloned()
+    }
+
+    fn property(&self, name: &str) -> Option<String> {
+        self.properties.get(name).cloned()
+    }
+}
+
+/// Captured state of the Thunderbolt/USB4 bus.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    devices: Vec<DeviceSnapshot>,
+}
+
+impl Snapshot {
+    /// Captures the current state of all devices on the bus.
+    ///
+    /// Needs `debugfs` to be mounted and typically root privileges to read the registers.
+    pub fn capture() -> Result<Self> {
+        let devices = crate::find_devices(None)?
+            .iter()
+            .map(DeviceSnapshot::capture)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version: VERSION,
+            devices,
+        })
+    }
+
+    /// Loads snapshot from file `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Self = serde_json::from_reader(reader)?;
+
+        if snapshot.version != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Saves snapshot into file `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    /// Returns number of devices in the snapshot.
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns `true` if the snapshot does not have any devices.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub(crate) fn find_devices(&self, address: Option<&Address>) -> Vec<Device> {
+        let mut devices: Vec<_> = self
+            .devices
+            .iter()
+            .filter_map(DeviceSnapshot::parse)
+            .filter(|d| address.is_none_or(|a| a.matches(d)))
+            .collect();
+
+        devices.sort();
+        devices
+    }
+
+    pub(crate) fn device(&self, syspath: &Path) -> Option<Device> {
+        self.devices
+            .iter()
+            .find(|d| d.syspath == syspath)
+            .and_then(DeviceSnapshot::parse)
+    }
+
+    fn find_router(&self, router: &str) -> Result<&DeviceSnapshot> {
+        self.devices
+            .iter()
+            .find(|d| d.kernel_name == router)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{router} not in snapshot")))
+    }
+
+    fn read_file(&self, router: &str, adapter: Option<u8>, name: &str) -> Result<&str> {
+        let key = match adapter {
+            Some(adapter) => format!("port{adapter}/{name}"),
+            None => name.to_string(),
+        };
+
+        self.find_router(router)?
+            .debugfs
+            .get(&key)
+            .map(String::as_str)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("{router}/{key} not in snapshot"),
+                )
+            })
+    }
+}
+
+impl Backend for Snapshot {
+    fn read_registers(&self, router: &str, adapter: Option<u8>) -> Result<Vec<Register>> {
+        let contents = self.read_file(router, adapter, DEBUGFS_REGS)?;
+        Ok(debugfs::parse_registers(contents))
+    }
+
+    fn read_paths(&self, router: &str, adapter: u8) -> Result<Vec<Register>> {
+        let contents = self.read_file(router, Some(adapter), DEBUGFS_PATH)?;
+        Ok(debugfs::parse_registers(contents))
+    }
+
+    fn read_counters(&self, router: &str, adapter: u8) -> Result<Vec<Register>> {
+        let contents = self.read_file(router, Some(adapter), DEBUGFS_COUNTERS)?;
+        Ok(debugfs::parse_registers(contents))
+    }
+
+    fn read_sideband(&self, device: &str, adapter: Option<u8>) -> Result<Vec<Register>> {
+        let contents = self.read_file(device, adapter, DEBUGFS_SB_REGS)?;
+        Ok(debugfs::parse_sideband_registers(contents))
+    }
+
+    fn write_registers(
+        &self,
+        _router: &str,
+        _adapter: Option<u8>,
+        _space: ConfigSpace,
+        _regs: &[Register],
+    ) -> Result<()> {
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "snapshot is read-only",
+        ))
+    }
+
+    fn clear_counters(&self, _router: &str, _adapter: u8) -> Result<()> {
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "snapshot is read-only",
+        ))
+    }
+
+    fn read_drom(&self, router: &str) -> Result<Vec<u8>> {
+        self.find_router(router)?.drom.clone().ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{router}/drom not in snapshot"),
+            )
+        })
+    }
+}
+
+/// Starts replaying `snapshot`.
+///
+/// After this all the devices and registers are read from the `snapshot` instead of the live
+/// system.
+pub fn replay(snapshot: Snapshot) {
+    let snapshot = Arc::new(snapshot);
+    backend::set_shared(snapshot.clone());
+    *ACTIVE.write().unwrap() = Some(snapshot);
+}
+
+/// Loads snapshot from file `path` and starts replaying it.
+///
+/// Same as calling [`Snapshot::load()`] and [`replay()`] but the returned error includes the
+/// path.
+pub fn replay_file(path: &Path) -> Result<()> {
+    let snapshot = Snapshot::load(path).map_err(|err| {
+        Error::new(
+            err.kind(),
+            format!("failed to load snapshot {}: {err}", path.display()),
+        )
+    })?;
+    replay(snapshot);
+    Ok(())
+}
+
+/// Returns `true` if a snapshot is being replayed.
+pub fn is_active() -> bool {
+    active().is_some()
+}
+
+pub(crate) fn active() -> Option<Arc<Snapshot>> {
+    ACTIVE.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{SysfsRoot, TempDir, write};
+    use std::os::unix::fs::symlink;
+
+    fn snapshot() -> Snapshot {
+        let json = r#"{
+            "version": 1,
+            "devices": [
+                {
+                    "kernel_name": "domain0",
+                    "syspath": "/sys/devices/pci0000:00/0000:00:0d.2/domain0",
+                    "attributes": { "security": "user", "iommu_dma_protection": "1" },
+                    "properties": { "DEVTYPE": "thunderbolt_domain" },
+                    "debugfs": {},
+                    "drom": null
+                },
+                {
+                    "kernel_name": "0-0",
+                    "syspath": "/sys/devices/pci0000:00/0000:00:0d.2/domain0/0-0",
+                    "attributes": { "vendor": "0x8087", "device": "0x7eb2", "generation": "4" },
+                    "properties": { "DEVTYPE": "thunderbolt_device", "USB4_VERSION": "1.0" },
+                    "debugfs": { "regs": "0x0000 0 0x00 0x00 0x7eb28087\n" },
+                    "drom": [1, 2, 3]
+                }
+            ]
+        }"#;
+
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn find_devices() {
+        let snapshot = snapshot();
+
+        let devices = snapshot.find_devices(None);
+        assert_eq!(devices.len(), 2);
+        assert!(devices[0].is_domain());
+        assert!(devices[1].is_host_router());
+        assert_eq!(devices[1].vendor(), 0x8087);
+        assert_eq!(devices[1].device(), 0x7eb2);
+
+        let address = Address::Router {
+            domain: 0,
+            route: 0,
+        };
+        let devices = snapshot.find_devices(Some(&address));
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].kernel_name(), "0-0");
+
+        let address = Address::Router {
+            domain: 1,
+            route: 0,
+        };
+        assert!(snapshot.find_devices(Some(&address)).is_empty());
+    }
+
+    #[test]
+    fn backend() {
+        let snapshot = snapshot();
+
+        let regs = snapshot.read_registers("0-0", None).unwrap();
+        assert_eq!(regs.len(), 1);
+        assert_eq!(regs[0].offset(), 0);
+        assert_eq!(regs[0].value(), 0x7eb28087);
+
+        assert_eq!(snapshot.read_drom("0-0").unwrap(), [1, 2, 3]);
+
+        let err = snapshot.read_registers("0-0", Some(1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let err = snapshot.read_paths("0-1", 1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let err = snapshot
+            .write_registers("0-0", None, ConfigSpace::Router, &regs)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn capture() {
+        let root = TempDir::new("snapshot");
+        let host = root.join("sys/devices/pci0000:00/0000:00:0d.2/domain0/0-0");
+        let service = host.join("0-1/0-1.1");
+        let devices = root.join("sys/bus/thunderbolt/devices");
+
+        write(&host.join("uevent"), "DEVTYPE=thunderbolt_device\n");
+        write(&host.join("key"), "00112233\n");
+        write(&service.join("uevent"), "DEVTYPE=thunderbolt_service\n");
+        write(&service.join("key"), "network\n");
+        for (name, value) in [("prtcid", "1"), ("prtcvers", "1"), ("prtcrevs", "1")] {
+            write(&service.join(name), format!("{value}\n"));
+        }
+        write(&service.join("prtcstns"), "0x00000000\n");
+        fs::create_dir_all(root.join("sys/kernel/debug/thunderbolt")).unwrap();
+
+        fs::create_dir_all(&devices).unwrap();
+        symlink(&host, devices.join("0-0")).unwrap();
+        symlink(&service, devices.join("0-1.1")).unwrap();
+
+        let _root = SysfsRoot::set(&root);
+        let snapshot = Snapshot::capture().unwrap();
+
+        // Router key is secret but the service key is only the protocol name.
+        let router = snapshot.devices.iter().find(|d| d.kernel_name == "0-0");
+        assert_eq!(router.unwrap().attribute("key"), None);
+        let service = snapshot.devices.iter().find(|d| d.kernel_name == "0-1.1");
+        assert_eq!(
+            service.unwrap().attribute("key"),
+            Some(String::from("network"))
+        );
+    }
+}
@@ -0,0 +1,208 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Alternate root directory for `sysfs`, `debugfs` and `tracefs`.
+//!
+//! By default the library accesses the real `/sys` and enumerates devices through `udev`. If an
+//! alternate root is set either with [`set_root()`] or through the `TBTOOLS_ROOT` environment
+//! variable, all these paths are looked up under that directory instead and devices are found by
+//! walking `sys/bus/thunderbolt/devices` there directly without `udev`. This allows running the
+//! library against a directory tree copied from a real system, for example in tests.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::{io, path::Path};
+//! use tbtools::sysfs;
+//!
+//! # fn main() -> io::Result<()> {
+//! sysfs::set_root(Some(Path::new("fixtures/system1")));
+//!
+//! for device in tbtools::find_devices(None)? {
+//!     println!("{}", device.kernel_name());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{Result, Write},
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::{Address, Device, device::Attributes};
+
+/// Environment variable that can be used to set the alternate root.
+pub const ROOT_ENV: &str = "TBTOOLS_ROOT";
+
+const SYSFS_BUS_DEVICES: &str = "/sys/bus/thunderbolt/devices";
+const SYSFS_UEVENT: &str = "uevent";
+
+static ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Sets alternate root directory.
+///
+/// Passing `None` goes back to using [`ROOT_ENV`] if set, and the real root otherwise.
+pub fn set_root(root: Option<&Path>) {
+    *ROOT.write().unwrap() = root.map(Path::to_path_buf);
+}
+
+/// Returns the alternate root directory or `None` if the real root is used.
+pub fn root() -> Option<PathBuf> {
+    if let Some(root) = ROOT.read().unwrap().as_ref() {
+        return Some(root.clone());
+    }
+    env::var_os(ROOT_ENV)
+        .filter(|root| !root.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns absolute `path` under the current root.
+pub(crate) fn path_buf(path: &str) -> PathBuf {
+    match root() {
+        Some(root) => root.join(path.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Device read directly from `sysfs` without `udev`.
+pub(crate) struct SysfsDevice {
+    syspath: PathBuf,
+    properties: HashMap<String, String>,
+}
+
+impl SysfsDevice {
+    pub(crate) fn new(syspath: &Path) -> Option<Self> {
+        let uevent = fs::read_to_string(syspath.join(SYSFS_UEVENT)).ok()?;
+        let properties = uevent
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        Some(Self {
+            syspath: syspath.to_path_buf(),
+            properties,
+        })
+    }
+}
+
+impl Attributes for SysfsDevice {
+    fn devtype(&self) -> Option<String> {
+        self.property("DEVTYPE")
+    }
+
+    fn sysname(&self) -> Option<String> {
+        Some(self.syspath.file_name()?.to_str()?.to_string())
+    }
+
+    fn syspath(&self) -> PathBuf {
+        self.syspath.clone()
+    }
+
+    fn attribute(&self, name: &str) -> Option<String> {
+        let value = fs::read_to_string(self.syspath.join(name)).ok()?;
+        Some(value.trim_end_matches('\n').to_string())
+    }
+
+    fn property(&self, name: &str) -> Option<String> {
+        self.properties.get(name).cloned()
+    }
+}
+
+pub(crate) fn find_devices_in(devices: &Path, address: Option<&Address>) -> Result<Vec<Device>> {
+    let mut found = Vec::new();
+
+    for entry in fs::read_dir(devices)? {
+        // Entries are symlinks to the actual device directories.
+        let syspath = fs::canonicalize(entry?.path())?;
+
+        if let Some(device) = SysfsDevice::new(&syspath).and_then(|d| Device::parse_attributes(&d))
+            && address.is_none_or(|a| a.matches(&device))
+        {
+            found.push(device);
+        }
+    }
+
+    found.sort();
+
+    Ok(found)
+}
+
+/// Writes `value` to attribute `name` of device in `syspath`.
+pub(crate) fn set_attribute(syspath: &Path, name: &str, value: &str) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(syspath.join(name))?;
+    file.write_all(value.as_bytes())
+}
+
+/// Finds devices by walking `sysfs` under the alternate root.
+pub(crate) fn find_devices(address: Option<&Address>) -> Result<Vec<Device>> {
+    find_devices_in(&path_buf(SYSFS_BUS_DEVICES), address)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{TempDir, write};
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn walk() {
+        let root = TempDir::new("sysfs");
+        let domain = root.join("sys/devices/pci0000:00/0000:00:0d.2/domain0");
+        let router = domain.join("0-0");
+        let devices = root.join("sys/bus/thunderbolt/devices");
+
+        write(
+            &domain.join("uevent"),
+            "DEVTYPE=thunderbolt_domain\nSECURITY=user\n",
+        );
+        write(&domain.join("security"), "user\n");
+        write(&domain.join("iommu_dma_protection"), "1\n");
+        write(
+            &domain.join("boot_acl"),
+            ",1d7f3a2c-0d3e-4f00-8000-123456789abc,,\n",
+        );
+        write(
+            &router.join("uevent"),
+            "DEVTYPE=thunderbolt_device\nUSB4_VERSION=1.0\n",
+        );
+        write(&router.join("vendor"), "0x8087\n");
+        write(&router.join("device"), "0x7eb2\n");
+        write(&router.join("generation"), "4\n");
+        write(&router.join("device_name"), "Gen14\n");
+
+        fs::create_dir_all(&devices).unwrap();
+        symlink(&domain, devices.join("domain0")).unwrap();
+        symlink(&router, devices.join("0-0")).unwrap();
+
+        let found = find_devices_in(&devices, None).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().any(|d| d.is_domain()));
+
+        let domain = found.iter().find(|d| d.is_domain()).unwrap();
+        let acl = domain.boot_acl().unwrap();
+        assert_eq!(acl.len(), 4);
+        assert_eq!(
+            acl[1].as_deref(),
+            Some("1d7f3a2c-0d3e-4f00-8000-123456789abc")
+        );
+        assert!(acl[0].is_none() && acl[3].is_none());
+
+        let router = found.iter().find(|d| d.is_host_router()).unwrap();
+        assert_eq!(router.vendor(), 0x8087);
+        assert_eq!(router.device(), 0x7eb2);
+        assert_eq!(router.device_name(), Some(String::from("Gen14")));
+
+        let address = Address::Domain { domain: 0 };
+        let found = find_devices_in(&devices, Some(&address)).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kernel_name(), "domain0");
+    }
+}
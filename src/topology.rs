@@ -0,0 +1,385 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Topology of the Thunderbolt/USB4 domains.
+//!
+//! [`Topology`] owns all the devices returned by [`find_devices()`](crate::find_devices()) and
+//! links them together so that parents, children and the USB4 links between the routers can be
+//! looked up without re-deriving them from the route strings.
+//!
+//! # Examples
+//! Print the domains in tree format.
+//! ```no_run
+//! # use std::io;
+//! use tbtools::topology::Topology;
+//!
+//! # fn main() -> io::Result<()> {
+//! let topology = Topology::read()?;
+//!
+//! for (depth, device) in topology.walk() {
+//!     print!("{}{}", " ".repeat(depth * 2), device.kernel_name());
+//!     if let Some(link) = topology.upstream_link(device) {
+//!         print!(" ({} Gb/s)", link.rx_bandwidth().unwrap_or(0));
+//!     }
+//!     println!();
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Result;
+
+use crate::{Device, Kind, usb4};
+
+/// USB4 link between a downstream lane adapter of a router and the device connected to it.
+#[derive(Clone, Debug)]
+pub struct Link {
+    parent: usize,
+    child: usize,
+    adapter: u8,
+    rx_speed: Option<u32>,
+    rx_lanes: Option<u32>,
+    tx_speed: Option<u32>,
+    tx_lanes: Option<u32>,
+    retimers: Vec<usize>,
+}
+
+impl Link {
+    /// Returns the downstream lane adapter of the parent router this link is connected to.
+    pub fn adapter(&self) -> u8 {
+        self.adapter
+    }
+
+    /// Returns Rx speed per lane of the link in Gb/s as seen by the child device.
+    pub fn rx_speed(&self) -> Option<u32> {
+        self.rx_speed
+    }
+
+    /// Returns Tx speed per lane of the link in Gb/s as seen by the child device.
+    pub fn tx_speed(&self) -> Option<u32> {
+        self.tx_speed
+    }
+
+    /// Returns number of Rx lanes the link is using.
+    pub fn rx_lanes(&self) -> Option<u32> {
+        self.rx_lanes
+    }
+
+    /// Returns number of Tx lanes the link is using.
+    pub fn tx_lanes(&self) -> Option<u32> {
+        self.tx_lanes
+    }
+
+    /// Returns total Rx bandwidth of the link in Gb/s.
+    pub fn rx_bandwidth(&self) -> Option<u32> {
+        Some(self.rx_speed? * self.rx_lanes?)
+    }
+
+    /// Returns total Tx bandwidth of the link in Gb/s.
+    pub fn tx_bandwidth(&self) -> Option<u32> {
+        Some(self.tx_speed? * self.tx_lanes?)
+    }
+
+    /// Returns `true` if the lanes of the link are bonded.
+    pub fn is_bonded(&self) -> bool {
+        self.rx_lanes.unwrap_or(1) > 1 || self.tx_lanes.unwrap_or(1) > 1
+    }
+}
+
+/// All devices of the system and the links between them.
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    devices: Vec<Device>,
+    parents: Vec<Option<usize>>,
+    links: Vec<Link>,
+}
+
+/// Returns route of the parent router of a device with `route`.
+fn parent_route(route: u64, depth: u32) -> u64 {
+    route & ((1 << ((depth - 1) * usb4::ROUTE_SHIFT)) - 1)
+}
+
+impl Topology {
+    /// Builds topology from `devices`.
+    pub fn new(mut devices: Vec<Device>) -> Self {
+        devices.sort();
+
+        let find = |kind: Kind, domain: u32, route: u64| {
+            devices
+                .iter()
+                .position(|d| d.kind() == kind && d.domain_index() == domain && d.route() == route)
+        };
+
+        let parents: Vec<_> = devices
+            .iter()
+            .map(|device| {
+                let domain = device.domain_index();
+                match device.kind() {
+                    Kind::Router if device.is_host_router() => find(Kind::Domain, domain, 0),
+                    Kind::Router | Kind::Xdomain => find(
+                        Kind::Router,
+                        domain,
+                        parent_route(device.route(), device.depth()),
+                    ),
+                    Kind::Retimer => find(Kind::Router, domain, device.route()),
+                    Kind::Service => find(Kind::Xdomain, domain, device.route()),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let links = devices
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_device_router() || d.is_xdomain())
+            .filter_map(|(child, device)| {
+                let parent = parents[child]?;
+                let adapter = devices[parent].downstream_adapter(device)?;
+                // Retimers are listed under the downstream port of the parent router.
+                let retimers = devices
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, d)| {
+                        d.is_retimer() && parents[*i] == Some(parent) && d.adapter_num() == adapter
+                    })
+                    .map(|(i, _)| i)
+                    .collect();
+
+                Some(Link {
+                    parent,
+                    child,
+                    adapter,
+                    rx_speed: device.rx_speed(),
+                    rx_lanes: device.rx_lanes(),
+                    tx_speed: device.tx_speed(),
+                    tx_lanes: device.tx_lanes(),
+                    retimers,
+                })
+            })
+            .collect();
+
+        Self {
+            devices,
+            parents,
+            links,
+        }
+    }
+
+    /// Reads all devices of the system and builds topology from them.
+    pub fn read() -> Result<Self> {
+        Ok(Self::new(crate::find_devices(None)?))
+    }
+
+    /// Returns all devices sorted by domain and route.
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Returns mutable access to the devices so that registers can be read.
+    pub fn devices_mut(&mut self) -> &mut [Device] {
+        &mut self.devices
+    }
+
+    /// Consumes the topology returning the devices.
+    pub fn into_devices(self) -> Vec<Device> {
+        self.devices
+    }
+
+    /// Returns all USB4 links.
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    fn index(&self, device: &Device) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|d| d.kind() == device.kind() && d.kernel_name() == device.kernel_name())
+    }
+
+    /// Returns router with `route` in `domain`.
+    pub fn router(&self, domain: u32, route: u64) -> Option<&Device> {
+        self.devices
+            .iter()
+            .find(|d| d.is_router() && d.domain_index() == domain && d.route() == route)
+    }
+
+    /// Returns domain devices.
+    pub fn domains(&self) -> impl Iterator<Item = &Device> {
+        self.devices.iter().filter(|d| d.is_domain())
+    }
+
+    /// Returns parent of `device`.
+    ///
+    /// Parent of a host router is the domain, parent of a device router or XDomain is the router
+    /// it is connected to, parent of a retimer is the router it is attached to and parent of a
+    /// service is the XDomain providing it.
+    pub fn parent(&self, device: &Device) -> Option<&Device> {
+        let parent = self.parents[self.index(device)?]?;
+        Some(&self.devices[parent])
+    }
+
+    /// Returns direct children of `device`.
+    pub fn children(&self, device: &Device) -> impl Iterator<Item = &Device> {
+        let index = self.index(device);
+        self.devices
+            .iter()
+            .zip(&self.parents)
+            .filter(move |(_, parent)| index.is_some() && **parent == index)
+            .map(|(device, _)| device)
+    }
+
+    /// Returns parents of `device` starting from the closest one.
+    pub fn ancestors(&self, device: &Device) -> Vec<&Device> {
+        let mut ancestors = Vec::new();
+        let mut current = self.index(device);
+
+        while let Some(parent) = current.and_then(|i| self.parents[i]) {
+            ancestors.push(&self.devices[parent]);
+            current = Some(parent);
+        }
+
+        ancestors
+    }
+
+    /// Returns link connecting device router or XDomain `device` to its parent.
+    pub fn upstream_link(&self, device: &Device) -> Option<&Link> {
+        let index = self.index(device)?;
+        self.links.iter().find(|l| l.child == index)
+    }
+
+    /// Returns links from the downstream ports of `device`.
+    pub fn downstream_links(&self, device: &Device) -> Vec<&Link> {
+        let index = self.index(device);
+        self.links
+            .iter()
+            .filter(|l| Some(l.parent) == index)
+            .collect()
+    }
+
+    /// Returns router at the upstream end of `link`.
+    pub fn link_parent(&self, link: &Link) -> &Device {
+        &self.devices[link.parent]
+    }
+
+    /// Returns device at the downstream end of `link`.
+    pub fn link_child(&self, link: &Link) -> &Device {
+        &self.devices[link.child]
+    }
+
+    /// Returns retimers on `link`.
+    pub fn link_retimers(&self, link: &Link) -> impl Iterator<Item = &Device> {
+        link.retimers.iter().map(|i| &self.devices[*i])
+    }
+
+    /// Walks all devices depth first starting from the domains.
+    ///
+    /// Returns each device with its depth in the tree where the domains are at depth `0`.
+    /// Devices whose parent is not found are treated as roots.
+    pub fn walk(&self) -> Vec<(usize, &Device)> {
+        let mut walk = Vec::new();
+        let mut stack: Vec<_> = (0..self.devices.len())
+            .rev()
+            .filter(|i| self.parents[*i].is_none())
+            .map(|i| (0, i))
+            .collect();
+
+        while let Some((depth, index)) = stack.pop() {
+            walk.push((depth, &self.devices[index]));
+            for child in (0..self.devices.len()).rev() {
+                if self.parents[child] == Some(index) {
+                    stack.push((depth + 1, child));
+                }
+            }
+        }
+
+        walk
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        sysfs,
+        test_util::{TempDir, write},
+    };
+    use std::{fs, os::unix::fs::symlink};
+
+    #[test]
+    fn topology() {
+        let root = TempDir::new("topology");
+        let domain = root.join("sys/devices/pci0000:00/0000:00:0d.2/domain0");
+        let host = domain.join("0-0");
+        let devices = root.join("sys/bus/thunderbolt/devices");
+
+        write(&domain.join("uevent"), "DEVTYPE=thunderbolt_domain\n");
+        write(&host.join("uevent"), "DEVTYPE=thunderbolt_device\n");
+        write(
+            &host.join("0-0:1.1/uevent"),
+            "DEVTYPE=thunderbolt_retimer\n",
+        );
+        // Two devices connected to the first port in daisy chain and one to the second port.
+        for (path, rx_lanes) in [("0-1", "2"), ("0-1/0-301", "1"), ("0-3", "2")] {
+            let router = host.join(path);
+            write(&router.join("uevent"), "DEVTYPE=thunderbolt_device\n");
+            write(&router.join("rx_speed"), "20.0 Gb/s\n");
+            write(&router.join("rx_lanes"), format!("{rx_lanes}\n"));
+            write(&router.join("tx_speed"), "20.0 Gb/s\n");
+            write(&router.join("tx_lanes"), format!("{rx_lanes}\n"));
+        }
+
+        fs::create_dir_all(&devices).unwrap();
+        for (name, path) in [
+            ("domain0", domain.clone()),
+            ("0-0", host.clone()),
+            ("0-0:1.1", host.join("0-0:1.1")),
+            ("0-1", host.join("0-1")),
+            ("0-301", host.join("0-1/0-301")),
+            ("0-3", host.join("0-3")),
+        ] {
+            symlink(path, devices.join(name)).unwrap();
+        }
+
+        let topology = Topology::new(sysfs::find_devices_in(&devices, None).unwrap());
+        assert_eq!(topology.devices().len(), 6);
+        assert_eq!(topology.links().len(), 3);
+
+        let names: Vec<_> = topology
+            .walk()
+            .iter()
+            .map(|(depth, d)| format!("{depth}:{}", d.kernel_name()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "0:domain0",
+                "1:0-0",
+                "2:0-0:1.1",
+                "2:0-1",
+                "3:0-301",
+                "2:0-3"
+            ]
+        );
+
+        let host = topology.router(0, 0).unwrap();
+        assert!(topology.parent(host).unwrap().is_domain());
+        assert_eq!(topology.children(host).count(), 3);
+
+        let links = topology.downstream_links(host);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].adapter(), 1);
+        assert!(links[0].is_bonded());
+        assert_eq!(links[0].rx_bandwidth(), Some(40));
+        assert_eq!(topology.link_retimers(links[0]).count(), 1);
+        assert_eq!(links[1].adapter(), 3);
+        assert_eq!(topology.link_retimers(links[1]).count(), 0);
+
+        let chained = topology.router(0, 0x301).unwrap();
+        let link = topology.upstream_link(chained).unwrap();
+        assert_eq!(link.adapter(), 3);
+        assert!(!link.is_bonded());
+        assert_eq!(topology.link_parent(link).route(), 1);
+        assert_eq!(topology.ancestors(chained).len(), 3);
+    }
+}
@@ -55,7 +55,7 @@
 use crate::{
     Address, ConfigSpace, Pdf,
     debugfs::{BitField, BitFields, DATA_DIR, Name},
-    genmask_t, util,
+    genmask_t, sysfs, util,
 };
 use lazy_static::lazy_static;
 use nix::{
@@ -926,7 +926,7 @@ impl Entry {
 }
 
 fn path_buf() -> Result<PathBuf> {
-    let path_buf = PathBuf::from(TRACEFS_ROOT);
+    let path_buf = sysfs::path_buf(TRACEFS_ROOT);
 
     if !path_buf.exists() {
         return Err(Error::from(ErrorKind::NotFound));
@@ -948,7 +948,12 @@ fn trace_events_thunderbolt_path(attr: &str) -> Result<PathBuf> {
 /// Mounts tracefs if not already mounted. User must be `root`.
 ///
 /// Call this before any other function in this module to make sure tracefs is properly mounted.
+/// Does nothing if an alternate [`root`](crate::sysfs::root()) is used.
 pub fn mount() -> Result<()> {
+    if sysfs::root().is_some() {
+        return Ok(());
+    }
+
     match mount::mount(
         None::<&PathBuf>,
         &PathBuf::from(TRACEFS_ROOT),
@@ -15,7 +15,8 @@
 //! # fn main() -> io::Result<()> {
 //! # let mut devices: Vec<_> = tbtools::find_devices(None)?;
 //! // Note devices need to have read_adapters() called and for each involved adapter read_paths()
-//! // so that Tunnel::discover() can work.
+//! // so that Tunnel::discover() can work. Tunnel::discover_all() does the same for all devices
+//! // and also returns what is left of partially torn down tunnels.
 //! for device in &devices {
 //!     if !device.is_router() {
 //!         continue;
@@ -47,10 +48,13 @@ const DP_VIDEO_HOPID: u16 = 9;
 const PCIE_HOPID: u16 = 8;
 
 /// Type of the tunnel.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Type {
     /// USB 3.x GenX tunnel.
     Usb3,
+    /// USB 3.x Gen T tunnel.
+    Usb3GenT,
     /// DisplayPort tunnel.
     DisplayPort,
     /// PCIe tunnel.
@@ -63,6 +67,7 @@ impl Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = match *self {
             Self::Usb3 => "USB 3",
+            Self::Usb3GenT => "USB 3 Gen T",
             Self::DisplayPort => "DisplayPort",
             Self::Pcie => "PCIe",
             Self::Dma => "DMA",
@@ -72,7 +77,8 @@ impl Display for Type {
 }
 
 /// Direction of the tunnel (from host router perspective).
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Direction {
     /// Tunnel flows downstream from the host router.
     Downstream,
@@ -176,57 +182,201 @@ impl<'a> Path<'a> {
 /// A Tunnel is formed from one or more [`Paths`](Path). All these together are then used as
 /// "virtual" wire for the underlying native protocol. It is also possible to have "null" tunnels
 /// between hosts which allow software traffic to be transferred between them.
+///
+/// Tunnels that are only partially set up or torn down are returned as well. For these
+/// [`is_complete()`](Self::is_complete) returns `false` and only the paths that could be followed
+/// are included.
 pub struct Tunnel<'a> {
     kind: Type,
     paths: Vec<Path<'a>>,
+    src_device: &'a Device,
+    src_adapter: &'a Adapter,
+    dst_device: &'a Device,
+    dst_adapter: &'a Adapter,
+    complete: bool,
+}
+
+fn same_adapter(
+    device: &Device,
+    adapter: &Adapter,
+    other: &Device,
+    other_adapter: &Adapter,
+) -> bool {
+    device.domain_index() == other.domain_index()
+        && device.route() == other.route()
+        && adapter.adapter() == other_adapter.adapter()
 }
 
 impl<'a> Tunnel<'a> {
+    fn new(kind: Type, paths: Vec<Path<'a>>, complete: bool) -> Self {
+        Self {
+            kind,
+            src_device: paths[0].src_device(),
+            src_adapter: paths[0].src_adapter(),
+            dst_device: paths[0].dst_device(),
+            dst_adapter: paths[0].dst_adapter(),
+            paths,
+            complete,
+        }
+    }
+
+    // Tunnel where only the path in the reverse direction is left.
+    fn reverse(kind: Type, path: Path<'a>) -> Self {
+        Self {
+            kind,
+            src_device: path.dst_device(),
+            src_adapter: path.dst_adapter(),
+            dst_device: path.src_device(),
+            dst_adapter: path.src_adapter(),
+            paths: vec![path],
+            complete: false,
+        }
+    }
+
     /// Discovers all tunnels starting from source adapter `adapter`.
     ///
-    /// The `adapter` must be enabled belong to `device` and `devices` must contain all the devices
-    /// in the domain or at least the ones that the tunnels pass through.
+    /// The `adapter` must belong to `device` and `devices` must contain all the devices in the
+    /// domain or at least the ones that the tunnels pass through. Source adapters are USB 3 Down
+    /// (including Gen T), DisplayPort IN, PCIe Down and Host Interface. Returns `None` if no path
+    /// starts from `adapter`.
     pub fn discover(
         device: &'a Device,
         adapter: &'a Adapter,
         devices: &'a [Device],
     ) -> Option<Vec<Self>> {
-        if !adapter.is_enabled() {
-            return None;
-        }
-
         match adapter.kind() {
-            debugfs::Type::Usb3Down => Self::discover_usb3(device, adapter, devices),
+            debugfs::Type::Usb3Down => Self::discover_bidir(
+                Type::Usb3,
+                ("USB 3 Down", "USB 3 Up"),
+                debugfs::Type::Usb3Up,
+                USB3_HOPID,
+                device,
+                adapter,
+                devices,
+            ),
+            debugfs::Type::Usb3GenTDown => Self::discover_bidir(
+                Type::Usb3GenT,
+                ("USB 3 Gen T Down", "USB 3 Gen T Up"),
+                debugfs::Type::Usb3GenTUp,
+                USB3_HOPID,
+                device,
+                adapter,
+                devices,
+            ),
             debugfs::Type::DisplayPortIn => Self::discover_dp(device, adapter, devices),
-            debugfs::Type::PcieDown => Self::discover_pcie(device, adapter, devices),
+            debugfs::Type::PcieDown => Self::discover_bidir(
+                Type::Pcie,
+                ("PCIe Down", "PCIe Up"),
+                debugfs::Type::PcieUp,
+                PCIE_HOPID,
+                device,
+                adapter,
+                devices,
+            ),
             debugfs::Type::HostInterface => Self::discover_dma(device, adapter, devices),
             _ => None,
         }
     }
 
-    fn discover_usb3(
+    /// Discovers all tunnels in `devices`.
+    ///
+    /// In addition to the tunnels found from the source adapters this returns paths in the
+    /// reverse direction whose forward paths are already gone (for instance `AUX RX` from
+    /// DisplayPort OUT) as incomplete tunnels.
+    pub fn discover_all(devices: &'a [Device]) -> Vec<Self> {
+        let routers = || devices.iter().filter(|d| d.is_router());
+
+        let mut tunnels: Vec<_> = routers()
+            .flat_map(|device| {
+                device
+                    .adapters()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |adapter| Self::discover(device, adapter, devices))
+                    .flatten()
+            })
+            .collect();
+
+        let mut reverse = Vec::new();
+
+        for device in routers() {
+            for adapter in device.adapters().into_iter().flatten() {
+                let (kind, name, hopid) = match adapter.kind() {
+                    debugfs::Type::Usb3Up => (Type::Usb3, "USB 3 Up", USB3_HOPID),
+                    debugfs::Type::Usb3GenTUp => (Type::Usb3GenT, "USB 3 Gen T Up", USB3_HOPID),
+                    debugfs::Type::PcieUp => (Type::Pcie, "PCIe Up", PCIE_HOPID),
+                    debugfs::Type::DisplayPortOut => (Type::DisplayPort, "AUX RX", DP_AUX_RX_HOPID),
+                    _ => continue,
+                };
+
+                if tunnels.iter().any(|t| t.uses(device, adapter, hopid)) {
+                    continue;
+                }
+
+                if let Some(path) = Self::discover_path(name, device, adapter, hopid, devices) {
+                    reverse.push(Self::reverse(kind, path));
+                }
+            }
+        }
+
+        tunnels.append(&mut reverse);
+        tunnels
+    }
+
+    /// Discovers tunnels that go through `adapter` of `device`.
+    ///
+    /// Unlike [`discover()`](Self::discover) the `adapter` can be any adapter along the tunnel,
+    /// for example DisplayPort OUT or a lane adapter.
+    pub fn discover_at(device: &Device, adapter: &Adapter, devices: &'a [Device]) -> Vec<Self> {
+        let mut tunnels = Self::discover_all(devices);
+
+        tunnels.retain(|t| {
+            t.paths.iter().flat_map(|p| p.hops()).any(|hop| {
+                same_adapter(hop.device(), hop.in_adapter(), device, adapter)
+                    || same_adapter(hop.device(), hop.out_adapter(), device, adapter)
+            })
+        });
+
+        tunnels
+    }
+
+    // Returns `true` if any path of the tunnel starts from `hopid` of `adapter`.
+    fn uses(&self, device: &Device, adapter: &Adapter, hopid: u16) -> bool {
+        self.paths.iter().flat_map(|p| p.hops()).any(|hop| {
+            same_adapter(hop.device(), hop.in_adapter(), device, adapter)
+                && hop.entry().in_hop() == hopid
+        })
+    }
+
+    fn discover_bidir(
+        kind: Type,
+        names: (&str, &str),
+        dst_kind: debugfs::Type,
+        hopid: u16,
         src_device: &'a Device,
         src_adapter: &'a Adapter,
         devices: &'a [Device],
     ) -> Option<Vec<Self>> {
-        let mut paths = Vec::new();
+        let down_path = Self::discover_path(names.0, src_device, src_adapter, hopid, devices)?;
+        let dst_device = down_path.dst_device();
+        let dst_adapter = down_path.dst_adapter();
+        let mut paths = vec![down_path];
+
+        // Only follow the reverse path if the forward one reached the other end.
+        let up_path = if dst_adapter.kind() == dst_kind {
+            Self::discover_path(names.1, dst_device, dst_adapter, hopid, devices)
+        } else {
+            None
+        };
 
-        let down_path =
-            Self::discover_path("USB 3 Down", src_device, src_adapter, USB3_HOPID, devices)?;
-        let up_path = Self::discover_path(
-            "USB 3 Up",
-            down_path.dst_device(),
-            down_path.dst_adapter(),
-            USB3_HOPID,
-            devices,
-        )?;
-        paths.push(down_path);
-        paths.push(up_path);
-
-        Some(vec![Self {
-            kind: Type::Usb3,
-            paths,
-        }])
+        let complete = up_path.as_ref().is_some_and(|p| {
+            same_adapter(p.dst_device(), p.dst_adapter(), src_device, src_adapter)
+        }) && src_adapter.is_enabled()
+            && dst_adapter.is_enabled();
+
+        paths.extend(up_path);
+
+        Some(vec![Self::new(kind, paths, complete)])
     }
 
     fn discover_dp(
@@ -234,52 +384,53 @@ impl<'a> Tunnel<'a> {
         src_adapter: &'a Adapter,
         devices: &'a [Device],
     ) -> Option<Vec<Self>> {
-        let mut paths = Vec::new();
+        let is_dp_out = |p: &Path| p.dst_adapter().kind() == debugfs::Type::DisplayPortOut;
 
         let video_path =
-            Self::discover_path("Video", src_device, src_adapter, DP_VIDEO_HOPID, devices)?;
+            Self::discover_path("Video", src_device, src_adapter, DP_VIDEO_HOPID, devices);
         let aux_tx_path =
-            Self::discover_path("AUX TX", src_device, src_adapter, DP_AUX_TX_HOPID, devices)?;
-        let aux_rx_path = Self::discover_path(
-            "AUX RX",
-            aux_tx_path.dst_device(),
-            aux_tx_path.dst_adapter(),
-            DP_AUX_RX_HOPID,
-            devices,
-        )?;
-        paths.push(video_path);
-        paths.push(aux_tx_path);
-        paths.push(aux_rx_path);
-
-        Some(vec![Self {
-            kind: Type::DisplayPort,
-            paths,
-        }])
-    }
+            Self::discover_path("AUX TX", src_device, src_adapter, DP_AUX_TX_HOPID, devices);
+        let aux_rx_path = aux_tx_path.as_ref().filter(|p| is_dp_out(p)).and_then(|p| {
+            Self::discover_path(
+                "AUX RX",
+                p.dst_device(),
+                p.dst_adapter(),
+                DP_AUX_RX_HOPID,
+                devices,
+            )
+        });
+
+        let complete = match (&video_path, &aux_tx_path, &aux_rx_path) {
+            (Some(video), Some(aux_tx), Some(aux_rx)) => {
+                is_dp_out(video)
+                    && same_adapter(
+                        video.dst_device(),
+                        video.dst_adapter(),
+                        aux_tx.dst_device(),
+                        aux_tx.dst_adapter(),
+                    )
+                    && same_adapter(
+                        aux_rx.dst_device(),
+                        aux_rx.dst_adapter(),
+                        src_device,
+                        src_adapter,
+                    )
+                    && src_adapter.is_enabled()
+                    && video.dst_adapter().is_enabled()
+            }
+            _ => false,
+        };
 
-    fn discover_pcie(
-        src_device: &'a Device,
-        src_adapter: &'a Adapter,
-        devices: &'a [Device],
-    ) -> Option<Vec<Self>> {
-        let mut paths = Vec::new();
+        let paths: Vec<_> = [video_path, aux_tx_path, aux_rx_path]
+            .into_iter()
+            .flatten()
+            .collect();
 
-        let down_path =
-            Self::discover_path("PCIe Down", src_device, src_adapter, PCIE_HOPID, devices)?;
-        let up_path = Self::discover_path(
-            "PCIe Up",
-            down_path.dst_device(),
-            down_path.dst_adapter(),
-            PCIE_HOPID,
-            devices,
-        )?;
-        paths.push(down_path);
-        paths.push(up_path);
-
-        Some(vec![Self {
-            kind: Type::Pcie,
-            paths,
-        }])
+        if paths.is_empty() {
+            return None;
+        }
+
+        Some(vec![Self::new(Type::DisplayPort, paths, complete)])
     }
 
     fn discover_one_dma(
@@ -327,10 +478,7 @@ impl<'a> Tunnel<'a> {
             return None;
         }
 
-        Some(Self {
-            kind: Type::Dma,
-            paths,
-        })
+        Some(Self::new(Type::Dma, paths, true))
     }
 
     fn discover_dma(
@@ -367,6 +515,18 @@ impl<'a> Tunnel<'a> {
         let mut hops = Vec::new();
 
         while let Some(path_entry) = adapter.path(in_hop) {
+            // Entry pointing to non-existent adapter ends the path.
+            if device.adapter(path_entry.out_adapter()).is_none() {
+                break;
+            }
+            // Misconfigured entries may loop back to an entry already visited.
+            if hops.iter().any(|h: &Hop| {
+                same_adapter(h.device(), h.in_adapter(), device, adapter)
+                    && h.entry().in_hop() == in_hop
+            }) {
+                break;
+            }
+
             let hop = Hop::new(path_entry, device);
             let out_adapter = hop.out_adapter();
             let out_hop = hop.entry().out_hop();
@@ -404,22 +564,31 @@ impl<'a> Tunnel<'a> {
 
     /// Returns starting point of this tunnel.
     pub fn src_device(&self) -> &Device {
-        self.paths[0].src_device()
+        self.src_device
     }
 
     /// Returns starting adapter of this tunnel.
     pub fn src_adapter(&self) -> &Adapter {
-        self.paths[0].src_adapter()
+        self.src_adapter
     }
 
     /// Returns ending point of this tunnel.
+    ///
+    /// For incomplete tunnels this is where the path ends which may be a lane adapter.
     pub fn dst_device(&self) -> &Device {
-        self.paths[0].dst_device()
+        self.dst_device
     }
 
     /// Returns ending adapter of this tunnel.
     pub fn dst_adapter(&self) -> &Adapter {
-        self.paths[0].dst_adapter()
+        self.dst_adapter
+    }
+
+    /// Returns `true` if all the paths of the tunnel are set up and both ends are enabled.
+    ///
+    /// Returns `false` for half configured or partially torn down tunnels.
+    pub fn is_complete(&self) -> bool {
+        self.complete
     }
 
     /// Returns the individual paths that make up this tunnel.
@@ -443,3 +612,118 @@ impl<'a> Tunnel<'a> {
         self.paths.len() > 1
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{ADP_CS, adapter, hop, router, upstream_lane};
+
+    fn names(tunnel: &Tunnel) -> Vec<String> {
+        tunnel
+            .paths()
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn dp_torn_down() {
+        // Only AUX RX from DP OUT 11 of 0-1 to DP IN 5 of the host router is left.
+        let lane = {
+            let mut lane = adapter(0, 1, debugfs::Type::Lane);
+            lane.set_test_paths(&[hop(10, 5, 8)]);
+            lane
+        };
+        let dp_out = {
+            let mut dp_out = adapter(1, 11, debugfs::Type::DisplayPortOut);
+            dp_out.set_test_paths(&[hop(8, 1, 10)]);
+            dp_out
+        };
+        let devices = [
+            router(0, vec![lane, adapter(0, 5, debugfs::Type::DisplayPortIn)]),
+            router(1, vec![upstream_lane(1, 1), dp_out]),
+        ];
+
+        let tunnels = Tunnel::discover_all(&devices);
+        assert_eq!(tunnels.len(), 1);
+
+        let tunnel = &tunnels[0];
+        assert_eq!(tunnel.kind(), Type::DisplayPort);
+        assert!(!tunnel.is_complete());
+        assert_eq!(names(tunnel), ["AUX RX"]);
+        assert_eq!(tunnel.src_device().route(), 0);
+        assert_eq!(tunnel.src_adapter().adapter(), 5);
+        assert_eq!(tunnel.dst_device().route(), 1);
+        assert_eq!(tunnel.dst_adapter().adapter(), 11);
+        assert!(matches!(tunnel.direction(), Direction::Downstream));
+        assert!(!tunnel.bidirectional());
+
+        let lane = devices[0].adapter(1).unwrap();
+        assert_eq!(Tunnel::discover_at(&devices[0], lane, &devices).len(), 1);
+    }
+
+    #[test]
+    fn usb3_gen_t() {
+        let mut lane = adapter(0, 1, debugfs::Type::Lane);
+        lane.set_test_paths(&[hop(8, 16, 8)]);
+        let mut down = adapter(0, 16, debugfs::Type::Usb3GenTDown);
+        down.set_test_paths(&[hop(8, 1, 8)]);
+        let mut up_lane = upstream_lane(1, 1);
+        up_lane.set_test_paths(&[hop(8, 16, 8)]);
+        let mut up = adapter(1, 16, debugfs::Type::Usb3GenTUp);
+        up.set_test_paths(&[hop(8, 1, 8)]);
+
+        let mut devices = [router(0, vec![lane, down]), router(1, vec![up_lane, up])];
+
+        let tunnels = Tunnel::discover_all(&devices);
+        assert_eq!(tunnels.len(), 1);
+
+        let tunnel = &tunnels[0];
+        assert_eq!(tunnel.kind(), Type::Usb3GenT);
+        assert!(tunnel.is_complete());
+        assert_eq!(names(tunnel), ["USB 3 Gen T Down", "USB 3 Gen T Up"]);
+        assert_eq!(tunnel.dst_adapter().kind(), debugfs::Type::Usb3GenTUp);
+        assert!(tunnel.paths().iter().all(|p| p.hops().len() == 2));
+
+        // No Gen T port has its paths enabled.
+        devices[1]
+            .adapter_mut(16)
+            .unwrap()
+            .set_test_registers(&ADP_CS, &[1 << 16, 0, 0, 0]);
+
+        let tunnels = Tunnel::discover_all(&devices);
+        assert_eq!(tunnels.len(), 1);
+        assert!(!tunnels[0].is_complete());
+    }
+
+    #[test]
+    fn loop_back() {
+        // Lane adapters of both routers route HopID 8 back to the link.
+        let mut lane = adapter(0, 1, debugfs::Type::Lane);
+        lane.set_test_paths(&[hop(8, 1, 8)]);
+        let mut down = adapter(0, 9, debugfs::Type::PcieDown);
+        down.set_test_paths(&[hop(8, 1, 8)]);
+        let mut up_lane = upstream_lane(1, 1);
+        up_lane.set_test_paths(&[hop(8, 1, 8)]);
+
+        let devices = [
+            router(0, vec![lane, down]),
+            router(1, vec![up_lane, adapter(1, 9, debugfs::Type::PcieUp)]),
+        ];
+
+        let tunnels = Tunnel::discover_all(&devices);
+        assert_eq!(tunnels.len(), 1);
+
+        let tunnel = &tunnels[0];
+        assert_eq!(tunnel.kind(), Type::Pcie);
+        assert!(!tunnel.is_complete());
+        assert_eq!(names(tunnel), ["PCIe Down"]);
+
+        let hops: Vec<_> = tunnel.paths()[0]
+            .hops()
+            .iter()
+            .map(|h| (h.device().route(), h.in_adapter().adapter()))
+            .collect();
+        assert_eq!(hops, [(0, 9), (1, 1), (0, 1)]);
+    }
+}
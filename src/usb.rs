@@ -0,0 +1,450 @@
+// Thunderbolt/USB4 debug tools
+//
+// Copyright (C) 2026, Intel Corporation
+
+//! Mapping between USB 3 adapters and Linux USB ports.
+//!
+//! USB 3 tunnels connect a SuperSpeed port of the host controller to the internal hub of a device
+//! router. This resolves the USB port in `/sys/bus/usb` for each USB 3 adapter that is part of a
+//! tunnel so that the USB devices enumerated behind it can be listed.
+//!
+//! For the host router the port is found through the `usb4_portN` directory of the lane adapter
+//! the tunnel goes out from, which is linked to the USB Type-C connector and from there to the
+//! USB ports of the connector. If the router DROM has USB 3 port mapping entry it is used to find
+//! the port numbers. For device routers the DROM USB 3 port mapping tells which downstream port
+//! of the internal hub each USB 3 Down adapter is connected to.
+//!
+//! # Examples
+//! ```no_run
+//! # use std::io;
+//! use tbtools::usb;
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut devices = tbtools::find_devices(None)?;
+//!
+//! for device in &mut devices {
+//!     if device.is_router() {
+//!         device.read_adapters()?;
+//!         for adapter in device.adapters_mut().unwrap() {
+//!             adapter.read_paths().ok();
+//!         }
+//!         device.read_drom().ok();
+//!     }
+//! }
+//!
+//! for port in usb::ports(&devices) {
+//!     for usb_device in port.devices() {
+//!         println!("{}: {}", port.name(), usb_device.name());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{Device, debugfs::Type, drom::DromEntry, sysfs, tunnel::Tunnel};
+
+const SYSFS_USB_DEVICES: &str = "/sys/bus/usb/devices";
+
+/// USB device enumerated behind a USB 3 tunnel.
+#[derive(Clone, Debug)]
+pub struct UsbDevice {
+    name: String,
+    syspath: PathBuf,
+}
+
+impl UsbDevice {
+    fn attribute(&self, name: &str) -> Option<String> {
+        let value = fs::read_to_string(self.syspath.join(name)).ok()?;
+        Some(value.trim_end().to_string())
+    }
+
+    /// Returns name of the device such as `3-1.2`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns path of the device in `sysfs`.
+    pub fn sysfs_path(&self) -> &Path {
+        &self.syspath
+    }
+
+    /// Returns USB vendor ID.
+    pub fn vendor(&self) -> Option<u16> {
+        u16::from_str_radix(&self.attribute("idVendor")?, 16).ok()
+    }
+
+    /// Returns USB product ID.
+    pub fn product(&self) -> Option<u16> {
+        u16::from_str_radix(&self.attribute("idProduct")?, 16).ok()
+    }
+
+    /// Returns manufacturer string of the device.
+    pub fn manufacturer_name(&self) -> Option<String> {
+        self.attribute("manufacturer")
+    }
+
+    /// Returns product string of the device.
+    pub fn product_name(&self) -> Option<String> {
+        self.attribute("product")
+    }
+
+    /// Returns negotiated speed in Mb/s.
+    pub fn speed(&self) -> Option<u32> {
+        self.attribute("speed")?.parse().ok()
+    }
+}
+
+/// USB port a USB 3 adapter is connected to.
+#[derive(Clone, Debug)]
+pub struct Port {
+    domain: u32,
+    route: u64,
+    adapter: u8,
+    bus: u32,
+    path: String,
+    usb_devices: PathBuf,
+}
+
+impl Port {
+    /// Returns domain of the router.
+    pub fn domain(&self) -> u32 {
+        self.domain
+    }
+
+    /// Returns route string of the router.
+    pub fn route(&self) -> u64 {
+        self.route
+    }
+
+    /// Returns USB 3 adapter number.
+    pub fn adapter(&self) -> u8 {
+        self.adapter
+    }
+
+    /// Returns USB bus number.
+    pub fn bus(&self) -> u32 {
+        self.bus
+    }
+
+    /// Returns port numbers from the root hub separated with `.`, for example `1.2`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns name of the USB device connected to the port such as `3-1.2`.
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.bus, self.path)
+    }
+
+    /// Returns all USB devices enumerated behind this port.
+    pub fn devices(&self) -> Vec<UsbDevice> {
+        let name = self.name();
+        let Ok(entries) = fs::read_dir(&self.usb_devices) else {
+            return Vec::new();
+        };
+
+        let mut devices: Vec<_> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let n = entry.file_name().to_str()?.to_string();
+                // Skip interfaces.
+                if n.contains(':') {
+                    return None;
+                }
+                if n != name && !n.starts_with(&format!("{name}.")) {
+                    return None;
+                }
+                Some(UsbDevice {
+                    syspath: fs::canonicalize(entry.path()).ok()?,
+                    name: n,
+                })
+            })
+            .collect();
+
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        devices
+    }
+}
+
+/// Returns USB 3 port numbers of the USB 3 adapters of `device` from DROM.
+fn port_mapping(device: &Device) -> HashMap<u8, u8> {
+    let mut mapping = HashMap::new();
+
+    if let Some(drom) = device.drom() {
+        for entry in drom.entries() {
+            if let DromEntry::Usb3PortMapping(maps) = entry {
+                for map in maps.iter().filter(|m| m.tunneling) {
+                    mapping.insert(map.usb3_adapter_num, map.usb3_port_num);
+                }
+            }
+        }
+    }
+
+    mapping
+}
+
+/// Splits USB port name such as `usb3-port1` into bus and port number.
+fn parse_port_name(name: &str) -> Option<(u32, u8)> {
+    let (bus, port) = name.strip_prefix("usb")?.split_once("-port")?;
+    Some((bus.parse().ok()?, port.parse().ok()?))
+}
+
+/// Finds the SuperSpeed root hub port linked to `usb4_port` through its USB Type-C connector.
+fn root_port(usb4_port: &Path, usb_devices: &Path) -> Option<(u32, u8)> {
+    fs::read_dir(usb4_port.join("connector"))
+        .ok()?
+        .flatten()
+        .filter_map(|entry| parse_port_name(entry.file_name().to_str()?))
+        .find(|(bus, _)| {
+            // Only SuperSpeed root hubs can be tunneled.
+            fs::read_to_string(usb_devices.join(format!("usb{bus}/speed")))
+                .ok()
+                .and_then(|speed| speed.trim_end().parse::<u32>().ok())
+                .is_some_and(|speed| speed >= 5000)
+        })
+}
+
+/// Resolves USB ports of all USB 3 adapters in `devices` that are part of a tunnel.
+///
+/// Adapters and path config spaces of the routers must be read. If DROM of a router is read its
+/// USB 3 port mapping is used to find the port numbers.
+pub fn ports(devices: &[Device]) -> Vec<Port> {
+    ports_in(&sysfs::path_buf(SYSFS_USB_DEVICES), devices)
+}
+
+fn ports_in(usb_devices: &Path, devices: &[Device]) -> Vec<Port> {
+    let mut routers: Vec<_> = devices.iter().filter(|d| d.is_router()).collect();
+    // Parents must be resolved before their children.
+    routers.sort_by_key(|d| d.depth());
+
+    let mut ports = Vec::new();
+    // Maps the USB 3 Up adapter of a device router to the bus and port it is connected to.
+    let mut upstream: HashMap<(u32, u64, u8), (u32, String)> = HashMap::new();
+
+    for router in routers {
+        let Some(adapters) = router.adapters() else {
+            continue;
+        };
+        let mapping = port_mapping(router);
+
+        // Internal hub of a device router is the USB device connected to its upstream port.
+        let hub = adapters
+            .iter()
+            .filter(|a| matches!(a.kind(), Type::Usb3Up | Type::Usb3GenTUp))
+            .find_map(|adapter| {
+                let key = (router.domain_index(), router.route(), adapter.adapter());
+                let (bus, path) = upstream.get(&key)?.clone();
+                ports.push(Port {
+                    domain: key.0,
+                    route: key.1,
+                    adapter: key.2,
+                    bus,
+                    path: path.clone(),
+                    usb_devices: usb_devices.to_path_buf(),
+                });
+                Some((bus, path))
+            });
+
+        for adapter in adapters
+            .iter()
+            .filter(|a| matches!(a.kind(), Type::Usb3Down | Type::Usb3GenTDown))
+        {
+            let Some(tunnels) = Tunnel::discover(router, adapter, devices) else {
+                continue;
+            };
+
+            let port = if router.is_host_router() {
+                // The tunnel leaves the host router through the USB4 port.
+                tunnels.iter().find_map(|tunnel| {
+                    let hop = tunnel.paths().first()?.hops().first()?;
+                    let lane = hop.out_adapter();
+                    let lane = if lane.is_lane1() {
+                        lane.adapter() - 1
+                    } else {
+                        lane.adapter()
+                    };
+                    let usb4_port = router.sysfs_path().join(format!("usb4_port{lane}"));
+                    let (bus, port) = root_port(&usb4_port, usb_devices)?;
+                    let port = mapping.get(&adapter.adapter()).copied().unwrap_or(port);
+                    Some((bus, port.to_string()))
+                })
+            } else {
+                hub.as_ref().and_then(|(bus, path)| {
+                    let port = mapping.get(&adapter.adapter())?;
+                    Some((*bus, format!("{path}.{port}")))
+                })
+            };
+
+            let Some((bus, path)) = port else {
+                continue;
+            };
+
+            for tunnel in &tunnels {
+                upstream.insert(
+                    (
+                        tunnel.dst_device().domain_index(),
+                        tunnel.dst_device().route(),
+                        tunnel.dst_adapter().adapter(),
+                    ),
+                    (bus, path.clone()),
+                );
+            }
+
+            ports.push(Port {
+                domain: router.domain_index(),
+                route: router.route(),
+                adapter: adapter.adapter(),
+                bus,
+                path,
+                usb_devices: usb_devices.to_path_buf(),
+            });
+        }
+    }
+
+    ports
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        drom::Drom,
+        test_util::{TempDir, adapter, hop, router, router_at, upstream_lane, write},
+    };
+    use std::os::unix::fs::symlink;
+
+    // USB4 DROM with USB 3 port mapping entries `(adapter, port)`.
+    fn drom(device: &Device, entries: &[(u8, u8)]) -> Drom {
+        let mut bytes = vec![0; 16];
+        bytes[13] = 3;
+        for (adapter, port) in entries {
+            bytes.extend([5, 0xb, *port, 0, 0x80 | adapter]);
+        }
+        let length = (bytes.len() - 13) as u16;
+        bytes[14..16].copy_from_slice(&length.to_le_bytes());
+        Drom::parse(&bytes, device.adapters().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn ports() {
+        let root = TempDir::new("usb");
+        let xhci = root.join("sys/devices/pci0000:00/0000:00:0d.0");
+        let usb_devices = root.join("sys/bus/usb/devices");
+        let connector = root.join("sys/devices/platform/typec/port0");
+        let usb4_port = root.join("sys/devices/pci0000:00/0000:00:0d.2/domain0/0-0/usb4_port1");
+
+        write(&xhci.join("usb1/speed"), "480\n");
+        write(&xhci.join("usb2/speed"), "20000\n");
+        write(&xhci.join("usb2/2-1/idVendor"), "8087\n");
+        write(&xhci.join("usb2/2-1/product"), "USB3.2 Hub\n");
+        write(&xhci.join("usb2/2-1/2-1.3/idProduct"), "5678\n");
+        fs::create_dir_all(xhci.join("usb2/2-1/2-1:1.0")).unwrap();
+        fs::create_dir_all(xhci.join("usb2/2-2")).unwrap();
+        fs::create_dir_all(xhci.join("usb1/1-0:1.0/usb1-port1")).unwrap();
+        fs::create_dir_all(xhci.join("usb2/2-0:1.0/usb2-port1")).unwrap();
+
+        fs::create_dir_all(&usb_devices).unwrap();
+        for name in ["usb1", "usb2", "2-1", "2-1.3", "2-1:1.0", "2-2"] {
+            let path = match name {
+                "usb1" | "usb2" => xhci.join(name),
+                "2-1" => xhci.join("usb2/2-1"),
+                "2-1.3" => xhci.join("usb2/2-1/2-1.3"),
+                "2-1:1.0" => xhci.join("usb2/2-1/2-1:1.0"),
+                _ => xhci.join("usb2/2-2"),
+            };
+            symlink(path, usb_devices.join(name)).unwrap();
+        }
+
+        fs::create_dir_all(&connector).unwrap();
+        fs::create_dir_all(&usb4_port).unwrap();
+        symlink(
+            xhci.join("usb1/1-0:1.0/usb1-port1"),
+            connector.join("usb1-port1"),
+        )
+        .unwrap();
+        symlink(
+            xhci.join("usb2/2-0:1.0/usb2-port1"),
+            connector.join("usb2-port1"),
+        )
+        .unwrap();
+        symlink(&usb4_port, connector.join("usb4_port1")).unwrap();
+        symlink(&connector, usb4_port.join("connector")).unwrap();
+
+        assert_eq!(root_port(&usb4_port, &usb_devices), Some((2, 1)));
+
+        let port = Port {
+            domain: 0,
+            route: 0,
+            adapter: 17,
+            bus: 2,
+            path: String::from("1"),
+            usb_devices: usb_devices.clone(),
+        };
+        assert_eq!(port.name(), "2-1");
+
+        let devices = port.devices();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name(), "2-1");
+        assert_eq!(devices[0].vendor(), Some(0x8087));
+        assert_eq!(devices[0].product_name(), Some(String::from("USB3.2 Hub")));
+        assert_eq!(devices[1].name(), "2-1.3");
+        assert_eq!(devices[1].product(), Some(0x5678));
+    }
+
+    #[test]
+    fn ports_in() {
+        let root = TempDir::new("usb-ports");
+        let nhi = root.join("sys/devices/pci0000:00/0000:00:0d.2");
+        let usb_devices = root.join("sys/bus/usb/devices");
+        write(&usb_devices.join("usb1/speed"), "480\n");
+        write(&usb_devices.join("usb2/speed"), "20000\n");
+        let connector = nhi.join("domain0/0-0/usb4_port1/connector");
+        fs::create_dir_all(connector.join("usb1-port1")).unwrap();
+        fs::create_dir_all(connector.join("usb2-port1")).unwrap();
+
+        // USB 3 tunnel from the host router to 0-1 and from its USB 3 Down adapter 17 on to
+        // 0-301.
+        let mut lane = adapter(0, 1, Type::Lane);
+        lane.set_test_paths(&[hop(8, 16, 8)]);
+        let mut down = adapter(0, 16, Type::Usb3Down);
+        down.set_test_paths(&[hop(8, 1, 8)]);
+        let host = router_at(&nhi, 0, vec![lane, down]);
+
+        let mut lane = upstream_lane(1, 1);
+        lane.set_test_paths(&[hop(8, 16, 8)]);
+        let mut lane3 = adapter(1, 3, Type::Lane);
+        lane3.set_test_paths(&[hop(8, 17, 8)]);
+        let mut up = adapter(1, 16, Type::Usb3Up);
+        up.set_test_paths(&[hop(8, 1, 8)]);
+        let mut down = adapter(1, 17, Type::Usb3Down);
+        down.set_test_paths(&[hop(8, 3, 8)]);
+        let mut hub = router(1, vec![lane, lane3, up, down]);
+        hub.drom = Some(drom(&hub, &[(17, 3)]));
+
+        let mut lane = upstream_lane(0x301, 1);
+        lane.set_test_paths(&[hop(8, 16, 8)]);
+        let mut up = adapter(0x301, 16, Type::Usb3Up);
+        up.set_test_paths(&[hop(8, 1, 8)]);
+        let device = router(0x301, vec![lane, up]);
+
+        let ports: Vec<_> = super::ports_in(&usb_devices, &[host, hub, device])
+            .iter()
+            .map(|p| (p.route(), p.adapter(), p.name()))
+            .collect();
+        assert_eq!(
+            ports,
+            [
+                (0, 16, String::from("2-1")),
+                (1, 16, String::from("2-1")),
+                (1, 17, String::from("2-1.3")),
+                (0x301, 16, String::from("2-1.3")),
+            ]
+        );
+    }
+}
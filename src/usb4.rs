@@ -9,6 +9,15 @@ use crate::genmask_t;
 
 pub(crate) const ROUTE_SHIFT: u32 = 8;
 
+pub const ROUTER_CS_9: usize = 0x09;
+pub const ROUTER_CS_25: usize = 0x19;
+pub const ROUTER_CS_26: usize = 0x1a;
+pub const ROUTER_CS_26_OPCODE_MASK: u32 = genmask_t!(u32, 15, 0);
+pub const ROUTER_CS_26_STATUS_MASK: u32 = genmask_t!(u32, 29, 24);
+pub const ROUTER_CS_26_STATUS_SHIFT: u32 = 24;
+pub const ROUTER_CS_26_ONS: u32 = 1 << 30;
+pub const ROUTER_CS_26_OV: u32 = 1 << 31;
+
 pub const TMU_RTR_CS_0_UCAP: u32 = 1 << 30;
 pub const TMU_RTR_CS_0_FREQ_WINDOW_MASK: u32 = genmask_t!(u32, 26, 16);
 pub const TMU_RTR_CS_0_FREQ_WINDOW_SHIFT: u32 = 16;